@@ -48,7 +48,7 @@
           preBuild = ''
             export DATABASE_URL=sqlite:./db.sqlite3
             sqlx database create
-            sqlx migrate run
+            sqlx migrate run --source migrations/sqlite
           '';
         });
 
@@ -0,0 +1,40 @@
+CREATE TABLE IF NOT EXISTS rooms
+(
+    id          BIGSERIAL PRIMARY KEY,
+    admin_code  TEXT        NOT NULL,
+    name        TEXT        NOT NULL,
+    options     TEXT        NOT NULL,
+    status      BIGINT      NOT NULL DEFAULT 0,    -- 0 = waiting, 1 = started, 2 = ended
+    method      BIGINT      NOT NULL DEFAULT 0,    -- 0 = borda, 1 = instant runoff, 2 = schulze, 3 = ranked pairs, 4 = single transferable vote, 5 = approval, 6 = score, 7 = star, 8 = majority judgment
+    seats       BIGINT      NOT NULL DEFAULT 1,
+    tie_break   BIGINT      NOT NULL DEFAULT 0,    -- 0 = alphabetical, 1 = random, 2 = first preferences, 3 = leave tied
+    seed        BIGINT      NOT NULL DEFAULT 0,
+    partial     BOOLEAN     NOT NULL DEFAULT FALSE,
+    max_ranks   BIGINT      NULL,
+    ballot_type BIGINT      NOT NULL DEFAULT 0,    -- 0 = ranking, 1 = approval, 2 = score, 3 = star, 4 = majority judgment
+    max_score   BIGINT      NOT NULL DEFAULT 5,
+    expires_at  TIMESTAMPTZ NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX idx_rooms_admin_code ON rooms (admin_code);
+
+CREATE TABLE IF NOT EXISTS voters
+(
+    id         BIGSERIAL PRIMARY KEY,
+    voter_code TEXT        NOT NULL,
+    options    TEXT        NULL,
+    approved   BOOLEAN     NOT NULL DEFAULT FALSE,
+    room_id    BIGINT      NOT NULL REFERENCES rooms(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX idx_voters_voter_code ON voters (voter_code);
+
+CREATE TABLE IF NOT EXISTS events
+(
+    id         BIGSERIAL PRIMARY KEY,
+    room_id    BIGINT      NOT NULL,
+    event      TEXT        NULL, -- JSON of the event, NULL when the room's streams end
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
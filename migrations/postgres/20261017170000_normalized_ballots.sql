@@ -0,0 +1,51 @@
+CREATE TABLE IF NOT EXISTS room_options
+(
+    id       BIGSERIAL PRIMARY KEY,
+    room_id  BIGINT NOT NULL REFERENCES rooms(id),
+    label    TEXT   NOT NULL,
+    position BIGINT NOT NULL -- order of the option on ballots, starting at 0
+);
+
+CREATE INDEX idx_room_options_room_id ON room_options (room_id);
+
+CREATE TABLE IF NOT EXISTS ballot_entries
+(
+    voter_id  BIGINT NOT NULL REFERENCES voters(id),
+    option_id BIGINT NOT NULL REFERENCES room_options(id),
+    rank      BIGINT NOT NULL, -- 0 = first choice, options ranked equal share a rank
+    score     BIGINT NULL,     -- the score on rated ballots, which also rank options by score
+    PRIMARY KEY (voter_id, option_id)
+);
+
+INSERT INTO room_options (room_id, label, position)
+SELECT rooms.id, options.label, options.position - 1
+FROM rooms, jsonb_array_elements_text(rooms.options::jsonb) WITH ORDINALITY AS options(label, position);
+
+-- Ranked ballots are a list of options or of groups of equal options. Labels are matched to
+-- the first option with that label, which is all older rooms could tell apart.
+INSERT INTO ballot_entries (voter_id, option_id, rank)
+SELECT voters.id, MIN(room_options.id), MIN(groups.rank) - 1
+FROM voters
+    CROSS JOIN jsonb_array_elements(voters.options::jsonb) WITH ORDINALITY AS groups(entry, rank)
+    CROSS JOIN jsonb_array_elements_text(
+        CASE jsonb_typeof(groups.entry) WHEN 'array' THEN groups.entry ELSE jsonb_build_array(groups.entry) END
+    ) AS entries(label)
+    JOIN room_options ON room_options.room_id = voters.room_id AND room_options.label = entries.label
+WHERE jsonb_typeof(voters.options::jsonb) = 'array'
+GROUP BY voters.id, entries.label;
+
+-- Rated ballots map each option to its score.
+INSERT INTO ballot_entries (voter_id, option_id, rank, score)
+SELECT voters.id, MIN(room_options.id), ratings.rank, ratings.score
+FROM voters
+    CROSS JOIN LATERAL (
+        SELECT scores.key AS label, scores.value::BIGINT AS score,
+            dense_rank() OVER (ORDER BY scores.value::BIGINT DESC) - 1 AS rank
+        FROM jsonb_each_text(voters.options::jsonb) AS scores
+    ) AS ratings
+    JOIN room_options ON room_options.room_id = voters.room_id AND room_options.label = ratings.label
+WHERE jsonb_typeof(voters.options::jsonb) = 'object'
+GROUP BY voters.id, ratings.label, ratings.rank, ratings.score;
+
+ALTER TABLE rooms DROP COLUMN options;
+ALTER TABLE voters DROP COLUMN options;
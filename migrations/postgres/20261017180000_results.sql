@@ -0,0 +1,112 @@
+CREATE TABLE IF NOT EXISTS results
+(
+    room_id              BIGINT PRIMARY KEY REFERENCES rooms(id),
+    method               BIGINT             NOT NULL, -- the settings the votes were counted with, as in rooms
+    seats                BIGINT             NOT NULL,
+    quota                DOUBLE PRECISION   NULL,     -- the Droop quota of STV counts
+    tie_break            BIGINT             NOT NULL,
+    seed                 BIGINT             NOT NULL,
+    partial              BOOLEAN            NOT NULL,
+    max_score            BIGINT             NOT NULL,
+    voters               BIGINT             NOT NULL, -- the number of ballots counted
+    runoff_first         TEXT               NULL,     -- the finalists of a STAR runoff and the ballots preferring each
+    runoff_first_voters  BIGINT             NULL,
+    runoff_second        TEXT               NULL,
+    runoff_second_voters BIGINT             NULL,
+    runoff_no_preference BIGINT             NULL,
+    created_at           TIMESTAMPTZ        NOT NULL DEFAULT now()
+);
+
+-- The score of each option, best first.
+CREATE TABLE IF NOT EXISTS result_scores
+(
+    room_id  BIGINT           NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    position BIGINT           NOT NULL, -- the ranking of the option, starting at 0
+    label    TEXT             NOT NULL,
+    score    DOUBLE PRECISION NOT NULL,
+    elected  BOOLEAN          NOT NULL,
+    tied     BOOLEAN          NOT NULL,
+    PRIMARY KEY (room_id, position)
+);
+
+-- The groups of options that stayed tied after the tie-break.
+CREATE TABLE IF NOT EXISTS result_ties
+(
+    room_id  BIGINT NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    tie      BIGINT NOT NULL,
+    position BIGINT NOT NULL,
+    label    TEXT   NOT NULL,
+    PRIMARY KEY (room_id, tie, position)
+);
+
+-- The options of the pairwise matrix, in ballot order.
+CREATE TABLE IF NOT EXISTS result_options
+(
+    room_id  BIGINT NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    position BIGINT NOT NULL,
+    label    TEXT   NOT NULL,
+    PRIMARY KEY (room_id, position)
+);
+
+-- How many ballots prefer the option at `position` over the one at `against`.
+CREATE TABLE IF NOT EXISTS result_pairwise
+(
+    room_id  BIGINT NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    position BIGINT NOT NULL,
+    against  BIGINT NOT NULL,
+    voters   BIGINT NOT NULL,
+    PRIMARY KEY (room_id, position, against)
+);
+
+-- The rounds of STV and IRV counts.
+CREATE TABLE IF NOT EXISTS result_rounds
+(
+    room_id   BIGINT           NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    round     BIGINT           NOT NULL,
+    exhausted DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (room_id, round)
+);
+
+CREATE TABLE IF NOT EXISTS result_tallies
+(
+    room_id  BIGINT           NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    round    BIGINT           NOT NULL,
+    position BIGINT           NOT NULL,
+    label    TEXT             NOT NULL,
+    votes    DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (room_id, round, position)
+);
+
+-- The options elected (0), eliminated (1) or tied for last (2) in each round.
+CREATE TABLE IF NOT EXISTS result_outcomes
+(
+    room_id  BIGINT NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    round    BIGINT NOT NULL,
+    outcome  BIGINT NOT NULL,
+    position BIGINT NOT NULL,
+    label    TEXT   NOT NULL,
+    PRIMARY KEY (room_id, round, outcome, position)
+);
+
+CREATE TABLE IF NOT EXISTS result_transfers
+(
+    room_id    BIGINT           NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    round      BIGINT           NOT NULL,
+    position   BIGINT           NOT NULL,
+    from_label TEXT             NOT NULL,
+    to_label   TEXT             NULL,     -- NULL when the ballots were exhausted
+    votes      DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (room_id, round, position)
+);
+
+-- How many voters gave each option each grade, for Majority Judgment.
+CREATE TABLE IF NOT EXISTS result_profiles
+(
+    room_id  BIGINT NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    position BIGINT NOT NULL,
+    label    TEXT   NOT NULL,
+    grade    BIGINT NOT NULL,
+    voters   BIGINT NOT NULL,
+    PRIMARY KEY (room_id, position, grade)
+);
+
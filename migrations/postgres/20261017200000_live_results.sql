@@ -0,0 +1 @@
+ALTER TABLE rooms ADD COLUMN live BOOLEAN NOT NULL DEFAULT FALSE; -- whether results are counted again after every ballot
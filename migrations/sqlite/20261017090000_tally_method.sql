@@ -0,0 +1 @@
+ALTER TABLE rooms ADD COLUMN method INTEGER NOT NULL DEFAULT 0; -- 0 = borda, 1 = instant runoff
@@ -0,0 +1 @@
+ALTER TABLE rooms ADD COLUMN seats INTEGER NOT NULL DEFAULT 1;
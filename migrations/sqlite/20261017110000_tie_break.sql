@@ -0,0 +1,2 @@
+ALTER TABLE rooms ADD COLUMN tie_break INTEGER NOT NULL DEFAULT 0; -- 0 = alphabetical, 1 = random, 2 = first preferences, 3 = leave tied
+ALTER TABLE rooms ADD COLUMN seed      INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+ALTER TABLE rooms ADD COLUMN partial   BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE rooms ADD COLUMN max_ranks INTEGER NULL;
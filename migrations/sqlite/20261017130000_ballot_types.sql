@@ -0,0 +1,2 @@
+ALTER TABLE rooms ADD COLUMN ballot_type INTEGER NOT NULL DEFAULT 0; -- 0 = ranking, 1 = approval, 2 = score, 3 = star
+ALTER TABLE rooms ADD COLUMN max_score   INTEGER NOT NULL DEFAULT 5;
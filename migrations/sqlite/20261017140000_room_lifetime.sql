@@ -0,0 +1,3 @@
+ALTER TABLE rooms ADD COLUMN expires_at TIMESTAMP NULL; -- always set when a room is created
+
+UPDATE rooms SET expires_at = datetime(created_at, '+1 hour');
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS events
+(
+    id         INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    room_id    INTEGER                           NOT NULL,
+    event      TEXT                              NULL, -- JSON of the event, NULL when the room's streams end
+    created_at TIMESTAMP                         NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
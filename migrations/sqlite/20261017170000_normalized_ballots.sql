@@ -0,0 +1,52 @@
+CREATE TABLE IF NOT EXISTS room_options
+(
+    id       INTEGER PRIMARY KEY NOT NULL,
+    room_id  INTEGER             NOT NULL REFERENCES rooms(id),
+    label    TEXT                NOT NULL,
+    position INTEGER             NOT NULL -- order of the option on ballots, starting at 0
+);
+
+CREATE INDEX idx_room_options_room_id ON room_options (room_id);
+
+CREATE TABLE IF NOT EXISTS ballot_entries
+(
+    voter_id  INTEGER NOT NULL REFERENCES voters(id),
+    option_id INTEGER NOT NULL REFERENCES room_options(id),
+    rank      INTEGER NOT NULL, -- 0 = first choice, options ranked equal share a rank
+    score     INTEGER NULL,     -- the score on rated ballots, which also rank options by score
+    PRIMARY KEY (voter_id, option_id)
+);
+
+INSERT INTO room_options (room_id, label, position)
+SELECT rooms.id, options.value, options.key
+FROM rooms, json_each(rooms.options) AS options
+WHERE json_valid(rooms.options);
+
+-- Ranked ballots are a list of options or of groups of equal options. Labels are matched to
+-- the first option with that label, which is all older rooms could tell apart.
+INSERT OR IGNORE INTO ballot_entries (voter_id, option_id, rank)
+SELECT voters.id,
+    (SELECT MIN(room_options.id) FROM room_options WHERE room_options.room_id = voters.room_id AND room_options.label = entries.value),
+    groups.key
+FROM voters,
+    json_each(voters.options) AS groups,
+    json_each(CASE groups.type WHEN 'array' THEN groups.value ELSE json_array(groups.value) END) AS entries
+WHERE json_valid(voters.options) AND json_type(voters.options) = 'array'
+    AND EXISTS (SELECT 1 FROM room_options WHERE room_options.room_id = voters.room_id AND room_options.label = entries.value);
+
+-- Rated ballots map each option to its score.
+INSERT OR IGNORE INTO ballot_entries (voter_id, option_id, rank, score)
+SELECT ratings.voter_id,
+    (SELECT MIN(room_options.id) FROM room_options WHERE room_options.room_id = ratings.room_id AND room_options.label = ratings.label),
+    ratings.rank,
+    ratings.score
+FROM (
+    SELECT voters.id AS voter_id, voters.room_id, scores.key AS label, scores.value AS score,
+        dense_rank() OVER (PARTITION BY voters.id ORDER BY scores.value DESC) - 1 AS rank
+    FROM voters, json_each(voters.options) AS scores
+    WHERE json_valid(voters.options) AND json_type(voters.options) = 'object'
+) AS ratings
+WHERE EXISTS (SELECT 1 FROM room_options WHERE room_options.room_id = ratings.room_id AND room_options.label = ratings.label);
+
+ALTER TABLE rooms DROP COLUMN options;
+ALTER TABLE voters DROP COLUMN options;
@@ -0,0 +1,111 @@
+CREATE TABLE IF NOT EXISTS results
+(
+    room_id              INTEGER PRIMARY KEY NOT NULL REFERENCES rooms(id),
+    method               INTEGER             NOT NULL, -- the settings the votes were counted with, as in rooms
+    seats                INTEGER             NOT NULL,
+    quota                REAL                NULL,     -- the Droop quota of STV counts
+    tie_break            INTEGER             NOT NULL,
+    seed                 INTEGER             NOT NULL,
+    partial              BOOLEAN             NOT NULL,
+    max_score            INTEGER             NOT NULL,
+    voters               INTEGER             NOT NULL, -- the number of ballots counted
+    runoff_first         TEXT                NULL,     -- the finalists of a STAR runoff and the ballots preferring each
+    runoff_first_voters  INTEGER             NULL,
+    runoff_second        TEXT                NULL,
+    runoff_second_voters INTEGER             NULL,
+    runoff_no_preference INTEGER             NULL,
+    created_at           TIMESTAMP           NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- The score of each option, best first.
+CREATE TABLE IF NOT EXISTS result_scores
+(
+    room_id  INTEGER NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    position INTEGER NOT NULL, -- the ranking of the option, starting at 0
+    label    TEXT    NOT NULL,
+    score    REAL    NOT NULL,
+    elected  BOOLEAN NOT NULL,
+    tied     BOOLEAN NOT NULL,
+    PRIMARY KEY (room_id, position)
+);
+
+-- The groups of options that stayed tied after the tie-break.
+CREATE TABLE IF NOT EXISTS result_ties
+(
+    room_id  INTEGER NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    tie      INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    label    TEXT    NOT NULL,
+    PRIMARY KEY (room_id, tie, position)
+);
+
+-- The options of the pairwise matrix, in ballot order.
+CREATE TABLE IF NOT EXISTS result_options
+(
+    room_id  INTEGER NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    label    TEXT    NOT NULL,
+    PRIMARY KEY (room_id, position)
+);
+
+-- How many ballots prefer the option at `position` over the one at `against`.
+CREATE TABLE IF NOT EXISTS result_pairwise
+(
+    room_id  INTEGER NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    against  INTEGER NOT NULL,
+    voters   INTEGER NOT NULL,
+    PRIMARY KEY (room_id, position, against)
+);
+
+-- The rounds of STV and IRV counts.
+CREATE TABLE IF NOT EXISTS result_rounds
+(
+    room_id   INTEGER NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    round     INTEGER NOT NULL,
+    exhausted REAL    NOT NULL,
+    PRIMARY KEY (room_id, round)
+);
+
+CREATE TABLE IF NOT EXISTS result_tallies
+(
+    room_id  INTEGER NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    round    INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    label    TEXT    NOT NULL,
+    votes    REAL    NOT NULL,
+    PRIMARY KEY (room_id, round, position)
+);
+
+-- The options elected (0), eliminated (1) or tied for last (2) in each round.
+CREATE TABLE IF NOT EXISTS result_outcomes
+(
+    room_id  INTEGER NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    round    INTEGER NOT NULL,
+    outcome  INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    label    TEXT    NOT NULL,
+    PRIMARY KEY (room_id, round, outcome, position)
+);
+
+CREATE TABLE IF NOT EXISTS result_transfers
+(
+    room_id    INTEGER NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    round      INTEGER NOT NULL,
+    position   INTEGER NOT NULL,
+    from_label TEXT    NOT NULL,
+    to_label   TEXT    NULL, -- NULL when the ballots were exhausted
+    votes      REAL    NOT NULL,
+    PRIMARY KEY (room_id, round, position)
+);
+
+-- How many voters gave each option each grade, for Majority Judgment.
+CREATE TABLE IF NOT EXISTS result_profiles
+(
+    room_id  INTEGER NOT NULL REFERENCES results(room_id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    label    TEXT    NOT NULL,
+    grade    INTEGER NOT NULL,
+    voters   INTEGER NOT NULL,
+    PRIMARY KEY (room_id, position, grade)
+);
@@ -0,0 +1 @@
+ALTER TABLE rooms ADD COLUMN visibility INTEGER NOT NULL DEFAULT 0; -- who sees the results: 0 = the admin, 1 = approved voters once the vote ends, 2 = anyone with the link
@@ -0,0 +1 @@
+ALTER TABLE rooms ADD COLUMN live BOOLEAN NOT NULL DEFAULT 0; -- whether results are counted again after every ballot
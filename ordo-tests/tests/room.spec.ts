@@ -35,6 +35,7 @@ test("whole flow", async ({ page: roomPage, browser }) => {
 
   await expect(roomPage.getByTestId("voter-count")).toHaveText("1");
   await expect(visitorPage.getByTestId("voter-count")).toHaveText("1");
+  await expect(roomPage.getByTestId("presence")).toHaveText("ONLINE");
 
   await expect(roomPage.getByRole("button", { name: "APPROVE", exact: true  })).toBeVisible();
   await expect(visitorPage.getByText("WAITING TO BE APPROVED.")).toBeVisible();
@@ -72,8 +73,9 @@ test("whole flow", async ({ page: roomPage, browser }) => {
   await expect(roomPage.getByText(`RESULTS FOR "${name}"`)).toBeVisible();
   await expect(roomPage.getByText("SCORE")).toBeVisible();
   for (const c of characters) {
-    await expect(roomPage.getByText(c)).toBeVisible();
+    await expect(roomPage.getByTestId("result-scores").getByText(c)).toBeVisible();
   }
+  await expect(roomPage.getByText("HEAD TO HEAD")).toBeVisible();
 
   await visitorContext.close();
 });
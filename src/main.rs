@@ -1,22 +1,46 @@
-use std::{env, net::SocketAddr};
+use std::{env, net::SocketAddr, time::Duration};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use color_eyre::eyre::ContextCompat;
 use events::Broadcasters;
-use sqlx::{migrate::MigrateDatabase, Pool, Sqlite};
+use store::Db;
 use tracing_subscriber::fmt::format::FmtSpan;
 use warp::Filter;
 
 ///  Effortlessly set up and conduct ranked choice voting
 #[derive(Parser)]
 struct Args {
-    /// Path to the sqlite database file. If the file doesn't exist, it will be created. This option is not needed if we have a DATABASE_URL environment variable.
+    /// Path to the sqlite database file. If the file doesn't exist, it will be created. This option is not needed if we have a DATABASE_URL environment variable, which can also be a `postgres://` URL.
     #[arg(short, long)]
     database: Option<String>,
 
     /// The address to bind to.
     #[arg(short, long, default_value = "0.0.0.0:3030")]
     address: String,
+
+    /// The longest a room can stay open, in hours, before it is deleted along with its voters.
+    #[arg(long, default_value_t = 24, value_parser = clap::value_parser!(i64).range(1..))]
+    max_lifetime: i64,
+
+    /// How often to look for expired rooms to delete, in seconds.
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+    sweep_interval: u64,
+
+    /// Where live updates are shared. Use `database` when running several instances on the same database.
+    #[arg(long, value_enum, default_value_t = EventsBackend::Memory)]
+    events: EventsBackend,
+
+    /// How often to look for live updates from other instances, in milliseconds, when sharing them through the database.
+    #[arg(long, default_value_t = 250, value_parser = clap::value_parser!(u64).range(1..))]
+    poll_interval: u64,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum EventsBackend {
+    /// Keep live updates in this instance.
+    Memory,
+    /// Share live updates with every instance through the `events` table.
+    Database,
 }
 
 #[tokio::main]
@@ -37,19 +61,22 @@ async fn main() -> color_eyre::Result<()> {
         .or(env::var("DATABASE_URL").ok())
         .wrap_err("No database file provided. Set the DATABASE_URL environment variable or supply the file via the --database flag.")?;
 
-    let exists = Sqlite::database_exists(&database).await.unwrap_or(false);
-
-    if !exists {
-        Sqlite::create_database(&database).await?;
-    }
-
-    let conn: Pool<Sqlite> = Pool::connect(&database).await?;
+    let store = store::connect(&database).await?;
 
-    sqlx::migrate!().run(&conn).await?;
+    let broadcasters = match args.events {
+        EventsBackend::Memory => Broadcasters::new(),
+        EventsBackend::Database => {
+            Broadcasters::database(store.clone(), Duration::from_millis(args.poll_interval)).await?
+        }
+    };
 
-    let broadcasters = Broadcasters::new();
+    tokio::spawn(sweeper::run(
+        store.clone(),
+        broadcasters.clone(),
+        Duration::from_secs(args.sweep_interval),
+    ));
 
-    let routes = routes(conn, broadcasters);
+    let routes = routes(store, broadcasters, args.max_lifetime);
     let static_files = warp::path("static").and(statics::routes());
 
     let routes = static_files
@@ -109,18 +136,35 @@ mod statics {
 }
 
 pub fn routes(
-    conn: sqlx::Pool<sqlx::Sqlite>,
+    store: Db,
     broadcasters: Broadcasters,
+    max_lifetime: i64,
 ) -> impl warp::Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-    homepage::route(conn.clone())
-        .or(rooms::route(conn.clone(), broadcasters.clone()))
-        .or(voters::route(conn.clone(), broadcasters.clone()))
+    homepage::route(store.clone(), max_lifetime)
+        .or(rooms::route(
+            store.clone(),
+            broadcasters.clone(),
+            max_lifetime,
+        ))
+        .or(voters::route(store.clone(), broadcasters.clone()))
+        .or(files::route(store.clone()))
         .with(warp::compression::gzip())
-        .or(events::route(conn, broadcasters))
+        .or(api::route(
+            store.clone(),
+            broadcasters.clone(),
+            max_lifetime,
+        ))
+        .or(events::route(store, broadcasters))
 }
 
 mod homepage {
-    use crate::{names, rejections, utils, views, with_state};
+    use crate::{
+        names, rejections,
+        rooms::Visibility,
+        store::Db,
+        tally::{BallotType, Method, TieBreak},
+        utils, views, with_state,
+    };
 
     use maud::{html, Markup};
     use warp::Filter;
@@ -128,37 +172,30 @@ mod homepage {
     struct Homepage {
         room_count: i32,
         voter_count: i32,
+        max_lifetime: i64,
     }
 
     pub fn route(
-        conn: sqlx::Pool<sqlx::Sqlite>,
+        store: Db,
+        max_lifetime: i64,
     ) -> impl warp::Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
         warp::path::end()
             .and(warp::get())
-            .and(with_state(conn))
+            .and(with_state(store))
+            .and(with_state(max_lifetime))
             .and_then(handler)
     }
 
-    async fn handler(conn: sqlx::Pool<sqlx::Sqlite>) -> Result<impl warp::Reply, warp::Rejection> {
-        let rooms = sqlx::query!(r#"SELECT count(id) as count FROM rooms"#)
-            .fetch_one(&conn)
-            .await
-            .map_err(|e| {
-                tracing::error!("error while counting rooms: {e}");
-                warp::reject::custom(rejections::InternalServerError)
-            })?;
-
-        let voters = sqlx::query!(r#"SELECT count(id) as count FROM voters"#)
-            .fetch_one(&conn)
-            .await
-            .map_err(|e| {
-                tracing::error!("error while counting voters: {e}");
-                warp::reject::custom(rejections::InternalServerError)
-            })?;
+    async fn handler(store: Db, max_lifetime: i64) -> Result<impl warp::Reply, warp::Rejection> {
+        let counts = store.counts().await.map_err(|e| {
+            tracing::error!("error while counting rooms and voters: {e}");
+            warp::reject::custom(rejections::InternalServerError)
+        })?;
 
         Ok(view(Homepage {
-            room_count: rooms.count,
-            voter_count: voters.count,
+            room_count: counts.rooms,
+            voter_count: counts.voters,
+            max_lifetime,
         }))
     }
 
@@ -169,7 +206,8 @@ mod homepage {
                 section."two-cols h-full" {
                     div."center" {
                         div."w-500 grid gap-lg" {
-                            (create_room_form())
+                            (create_room_form(data.max_lifetime))
+                            a."button w-full text-center" href=(names::import_url()) { "IMPORT BALLOTS FROM A FILE" }
                             (general_stats(&data))
                         }
                     }
@@ -181,7 +219,7 @@ mod homepage {
         )
     }
 
-    fn create_room_form() -> Markup {
+    fn create_room_form(max_lifetime: i64) -> Markup {
         html! {
             form."w-full grid gap-md"
                 data-testid="create-room-form"
@@ -206,6 +244,66 @@ mod homepage {
                     button."button w-fit" id="addOption" type="button" { "ADD OPTION" }
                 }
 
+                div."grid gap-sm" {
+                    label."text-md" { "BALLOT" }
+                    select."input-text" name="ballot_type" {
+                        @for ballot_type in BallotType::ALL {
+                            option value=(ballot_type.value()) { (ballot_type.label()) }
+                        }
+                    }
+                    input."input-text" name="max_score" type="number" min="1" max="100" value="5" title="highest score for score and STAR ballots" {}
+                }
+
+                div."grid gap-sm" {
+                    label."text-md" { "COUNTING METHOD (RANKING BALLOTS)" }
+                    select."input-text" name="method" {
+                        @for method in Method::ALL.into_iter().filter(|m| m.ballot_type() == BallotType::Ranking) {
+                            option value=(method.value()) { (method.label()) }
+                        }
+                    }
+                }
+
+                div."grid gap-sm" {
+                    label."text-md" { "TIE BREAK" }
+                    select."input-text" name="tie_break" {
+                        @for tie_break in TieBreak::ALL {
+                            option value=(tie_break.value()) { (tie_break.label()) }
+                        }
+                    }
+                }
+
+                div."grid gap-sm" {
+                    label."text-md" { "SEATS" }
+                    input."input-text" name="seats" type="number" min="1" value="1" required="true" {}
+                }
+
+                div."grid gap-sm" {
+                    label."text-md" { "BALLOTS" }
+                    label."flex gap-sm text-sm" {
+                        input type="checkbox" name="partial" {}
+                        "ALLOW VOTERS TO LEAVE OPTIONS UNRANKED"
+                    }
+                    input."input-text" name="max_ranks" type="number" min="1" placeholder="rank at most (optional)" {}
+                }
+
+                div."grid gap-sm" {
+                    label."text-md" { "RESULTS VISIBLE TO" }
+                    select."input-text" name="visibility" {
+                        @for visibility in Visibility::ALL {
+                            option value=(visibility.value()) { (visibility.label()) }
+                        }
+                    }
+                    label."flex gap-sm text-sm" {
+                        input type="checkbox" name="live" {}
+                        "SHOW THE RESULTS LIVE AS VOTES COME IN"
+                    }
+                }
+
+                div."grid gap-sm" {
+                    label."text-md" { "ROOM LIFETIME (HOURS)" }
+                    input."input-text" name="lifetime" type="number" min="1" max=(max_lifetime) value="1" required="true" {}
+                }
+
                 button."button w-full" type="submit" { "CREATE ROOM" }
             }
         }
@@ -228,64 +326,169 @@ mod homepage {
 }
 
 mod rooms {
-    use std::{collections::HashMap, time::Duration};
-
     use crate::{
         events::{Broadcasters, RoomEvents},
+        files::{self, Format, LineError},
         names,
-        rejections::{self, EmptyName, EmptyOption, InternalServerError, NoOptions, NotRoomAdmin},
+        rejections::{
+            self, EmptyName, EmptyOption, InternalServerError, InvalidLifetime, InvalidMaxRanks,
+            InvalidMaxScore, InvalidMethod, InvalidSeats, NoBallots, NoOptions, NotRoomAdmin,
+            NotVoter,
+        },
+        store::{self, Db, RoomOption},
+        tally::{self, BallotType, Election, Method, Pairwise, Round, Runoff, TieBreak, Transfer},
         utils, views,
-        voters::{self, VoterPage},
-        voting::{self, ResultPage, Score, VoteAdminPage},
+        voters::{self, BallotRules, VoterPage},
+        voting::{self, ResultPage, RoundsPage, Score, VoteAdminPage},
         with_state,
     };
 
     use maud::{html, Markup};
-    use serde::Deserialize;
+    use serde::{Deserialize, Serialize};
+    use utoipa::ToSchema;
     use warp::{
-        http::{header::SET_COOKIE, Response},
-        Filter,
+        http::{header::SET_COOKIE, Response, StatusCode, Uri},
+        Filter, Reply,
     };
 
-    #[derive(Deserialize)]
-    struct CreateRoomBody {
+    #[derive(Deserialize, ToSchema)]
+    pub struct CreateRoomBody {
         name: String,
         options: Vec<String>,
+        #[serde(default)]
+        method: Method,
+        #[serde(default = "default_seats", deserialize_with = "utils::number")]
+        seats: i64,
+        #[serde(default)]
+        tie_break: TieBreak,
+        #[serde(default, deserialize_with = "utils::checkbox")]
+        partial: bool,
+        #[serde(default, deserialize_with = "utils::optional_number")]
+        max_ranks: Option<i64>,
+        #[serde(default)]
+        ballot_type: BallotType,
+        #[serde(default = "default_max_score", deserialize_with = "utils::number")]
+        max_score: i64,
+        /// Who can see the results once the vote ends.
+        #[serde(default)]
+        visibility: Visibility,
+        /// Whether the results are counted again after every ballot, for the admin and, when
+        /// the results are public, for approved voters.
+        #[serde(default, deserialize_with = "utils::checkbox")]
+        live: bool,
+        /// How long the room stays open, in hours.
+        #[serde(default = "default_lifetime", deserialize_with = "utils::number")]
+        lifetime: i64,
+    }
+
+    /// Who can see the results of a room besides its admin.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Visibility {
+        #[default]
+        Admin,
+        /// The approved voters, once the vote ends or as votes come in for live results.
+        Voters,
+        /// Anyone with the link to the results.
+        Public,
+    }
+
+    impl Visibility {
+        pub const ALL: [Visibility; 3] =
+            [Visibility::Admin, Visibility::Voters, Visibility::Public];
+
+        /// The value stored in the `rooms.visibility` column.
+        pub fn code(self) -> i64 {
+            match self {
+                Visibility::Admin => 0,
+                Visibility::Voters => 1,
+                Visibility::Public => 2,
+            }
+        }
+
+        pub fn from_code(code: i64) -> Option<Self> {
+            Self::ALL.into_iter().find(|v| v.code() == code)
+        }
+
+        pub fn value(self) -> &'static str {
+            match self {
+                Visibility::Admin => "admin",
+                Visibility::Voters => "voters",
+                Visibility::Public => "public",
+            }
+        }
+
+        pub fn label(self) -> &'static str {
+            match self {
+                Visibility::Admin => "ROOM ADMIN ONLY",
+                Visibility::Voters => "VOTERS WHEN THE VOTE ENDS",
+                Visibility::Public => "ANYONE WITH THE LINK",
+            }
+        }
+    }
+
+    fn default_seats() -> i64 {
+        1
+    }
+
+    fn default_max_score() -> i64 {
+        5
+    }
+
+    fn default_lifetime() -> i64 {
+        1
     }
 
     pub fn route(
-        conn: sqlx::Pool<sqlx::Sqlite>,
+        store: Db,
         broadcasters: Broadcasters,
+        max_lifetime: i64,
     ) -> impl warp::Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-        let create_room = with_state(conn.clone())
-            .and(with_state(broadcasters.clone()))
+        let create_room = with_state(store.clone())
+            .and(with_state(max_lifetime))
             .and(warp::path!("rooms"))
             .and(warp::post())
             .and(warp::body::json::<CreateRoomBody>())
             .and_then(create_room)
             .with(warp::trace::named("create_room"));
 
-        let get_room = with_state(conn.clone())
+        let import_page = with_state(max_lifetime)
+            .and(warp::path!("rooms" / "import"))
+            .and(warp::get())
+            .and_then(import_page)
+            .with(warp::trace::named("import_page"));
+
+        let import_ballots = with_state(store.clone())
+            .and(with_state(max_lifetime))
+            .and(warp::path!("rooms" / "import"))
+            .and(warp::post())
+            .and(warp::body::content_length_limit(files::MAX_FILE_SIZE))
+            .and(warp::body::json::<ImportBody>())
+            .and_then(import_ballots)
+            .with(warp::trace::named("import_ballots"));
+
+        let get_room = with_state(store.clone())
+            .and(with_state(broadcasters.clone()))
             .and(warp::path!("rooms" / i64))
             .and(warp::get())
             .and(warp::cookie::cookie(names::ROOM_ADMIN_COOKIE_NAME))
             .and_then(get_room)
             .with(warp::trace::named("get_room"));
 
-        let join_room_page = with_state(conn.clone())
+        let join_room_page = with_state(store.clone())
             .and(warp::path!("rooms" / i64 / "join"))
             .and(warp::get())
             .and_then(join_room_page)
             .with(warp::trace::named("join_room_page"));
 
-        let join_room = with_state(conn.clone())
+        let join_room = with_state(store.clone())
             .and(with_state(broadcasters.clone()))
             .and(warp::path!("rooms" / i64 / "join"))
             .and(warp::post())
             .and_then(join_room)
             .with(warp::trace::named("join_room"));
 
-        let start_vote = with_state(conn.clone())
+        let start_vote = with_state(store.clone())
             .and(with_state(broadcasters.clone()))
             .and(warp::path!("rooms" / i64 / "start"))
             .and(warp::put())
@@ -293,7 +496,7 @@ mod rooms {
             .and_then(start_vote)
             .with(warp::trace::named("start_vote"));
 
-        let end_vote = with_state(conn.clone())
+        let end_vote = with_state(store.clone())
             .and(with_state(broadcasters.clone()))
             .and(warp::path!("rooms" / i64 / "end"))
             .and(warp::put())
@@ -301,19 +504,99 @@ mod rooms {
             .and_then(end_vote)
             .with(warp::trace::named("start_vote"));
 
+        let results = with_state(store.clone())
+            .and(warp::path!("rooms" / i64 / "results"))
+            .and(warp::get())
+            .and(warp::cookie::optional(names::ROOM_ADMIN_COOKIE_NAME))
+            .and(warp::cookie::optional(names::VOTER_COOKIE_NAME))
+            .and_then(results)
+            .with(warp::trace::named("results"));
+
+        let round_results = with_state(store.clone())
+            .and(warp::path!("rooms" / i64 / "results" / "rounds"))
+            .and(warp::get())
+            .and(warp::cookie::optional(names::ROOM_ADMIN_COOKIE_NAME))
+            .and(warp::cookie::optional(names::VOTER_COOKIE_NAME))
+            .and_then(round_results)
+            .with(warp::trace::named("round_results"));
+
         create_room
+            .or(import_page)
+            .or(import_ballots)
             .or(get_room)
             .or(join_room_page)
             .or(join_room)
             .or(start_vote)
             .or(end_vote)
+            .or(results)
+            .or(round_results)
     }
 
     async fn create_room(
-        conn: sqlx::Pool<sqlx::Sqlite>,
-        broadcasters: Broadcasters,
+        store: Db,
+        max_lifetime: i64,
         mut body: CreateRoomBody,
     ) -> Result<impl warp::Reply, warp::Rejection> {
+        let NewRoom {
+            id: room_id,
+            admin_code,
+            expires_in,
+            ..
+        } = insert_room(store, max_lifetime, &mut body).await?;
+
+        let cookie = utils::cookie(names::ROOM_ADMIN_COOKIE_NAME, &admin_code, expires_in);
+        let resp = Response::builder()
+            .header(SET_COOKIE, cookie)
+            .header("HX-Replace-Url", names::room_page_url(room_id))
+            .body(
+                views::titled(
+                    "Admin",
+                    view(RoomPage {
+                        id: room_id,
+                        name: body.name,
+                        options: body.options,
+                        method: body.method,
+                        seats: body.seats,
+                        tie_break: body.tie_break,
+                        partial: body.partial,
+                        max_ranks: body.max_ranks,
+                        ballot_type: body.ballot_type,
+                        max_score: body.max_score,
+                        visibility: body.visibility,
+                        live: body.live,
+                        expires_in,
+                        voters: Vec::new(),
+                    }),
+                )
+                .into_string(),
+            )
+            .unwrap();
+
+        Ok(resp)
+    }
+
+    #[derive(Serialize, ToSchema)]
+    pub struct NewRoom {
+        pub id: i64,
+        pub admin_code: String,
+        /// The options of the room, whose IDs ballots refer to.
+        pub options: Vec<RoomOption>,
+        /// Seconds until the room is deleted.
+        pub expires_in: i64,
+    }
+
+    /// Validates a new room and stores it.
+    pub async fn insert_room(
+        store: Db,
+        max_lifetime: i64,
+        body: &mut CreateRoomBody,
+    ) -> Result<NewRoom, warp::Rejection> {
+        check_room(max_lifetime, body)?;
+        store_room(store, body).await
+    }
+
+    /// Validates a new room, settling any settings that don't apply to its ballot type.
+    fn check_room(max_lifetime: i64, body: &mut CreateRoomBody) -> Result<(), warp::Rejection> {
         if body.name.is_empty() {
             return Err(warp::reject::custom(EmptyName));
         }
@@ -328,167 +611,503 @@ mod rooms {
             }
         }
 
-        body.options.sort();
-        let options = serde_json::to_string(&body.options).unwrap();
-        let admin_code = utils::generate_ulid();
+        if body.seats < 1 || body.seats as usize > body.options.len() {
+            return Err(warp::reject::custom(InvalidSeats));
+        }
 
-        let room_id = sqlx::query!(
-            r#"
-        INSERT INTO rooms (name, options, admin_code)
-        VALUES ( ?1, ?2, ?3 )
-            "#,
-            body.name,
-            options,
-            admin_code
-        )
-        .execute(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while creating room: {e}");
-            warp::reject::custom(rejections::InternalServerError)
-        })?
-        .last_insert_rowid();
+        if let Some(max_ranks) = body.max_ranks {
+            if max_ranks < 1 || max_ranks as usize > body.options.len() {
+                return Err(warp::reject::custom(InvalidMaxRanks));
+            }
 
-        tokio::spawn(async move {
-            tokio::time::sleep(Duration::from_secs(3600)).await;
+            // Limiting how many options can be ranked only makes sense for truncated ballots.
+            body.partial = true;
+        }
 
-            let res = sqlx::query!(
-                r#"
-            BEGIN TRANSACTION;
+        if !(1..=100).contains(&body.max_score) {
+            return Err(warp::reject::custom(InvalidMaxScore));
+        }
 
-            DELETE FROM voters
-            WHERE room_id = ?1;
+        if !(1..=max_lifetime).contains(&body.lifetime) {
+            return Err(warp::reject::custom(InvalidLifetime));
+        }
 
-            DELETE FROM rooms
-            WHERE id = ?1;
+        // Rated ballots each have their own counting method and can't be left partial.
+        if let Some(method) = body.ballot_type.method() {
+            body.method = method;
+            body.partial = false;
+            body.max_ranks = None;
+        }
 
-            COMMIT;
-                "#,
-                room_id,
-                room_id,
-            )
-            .execute(&conn)
-            .await;
-            tracing::debug!("delete room result: {res:?}");
+        // Rated methods can't count ranked ballots.
+        if body.method.ballot_type() != body.ballot_type {
+            return Err(warp::reject::custom(InvalidMethod));
+        }
 
-            broadcasters.end_stream(room_id).await;
-        });
+        // STAR voting ends in a runoff between two finalists, so it only fills one seat.
+        if body.method == Method::Star && body.seats > 1 {
+            return Err(warp::reject::custom(InvalidSeats));
+        }
 
-        let cookie = utils::cookie(names::ROOM_ADMIN_COOKIE_NAME, &admin_code);
-        let resp = Response::builder()
-            .header(SET_COOKIE, cookie)
-            .header("HX-Replace-Url", names::room_page_url(room_id))
-            .body(
-                views::titled(
-                    "Admin",
-                    view(RoomPage {
-                        id: room_id,
-                        name: body.name,
-                        options: body.options,
-                        voters: Vec::new(),
-                    }),
-                )
-                .into_string(),
-            )
-            .unwrap();
+        Ok(())
+    }
 
-        Ok(resp)
+    async fn store_room(store: Db, body: &mut CreateRoomBody) -> Result<NewRoom, warp::Rejection> {
+        body.options.sort();
+        let admin_code = utils::generate_ulid();
+
+        let room_id = store
+            .insert_room(store::NewRoom {
+                name: body.name.clone(),
+                options: body.options.clone(),
+                admin_code: admin_code.clone(),
+                method: body.method.code(),
+                seats: body.seats,
+                tie_break: body.tie_break.code(),
+                seed: utils::generate_seed(),
+                partial: body.partial,
+                max_ranks: body.max_ranks,
+                ballot_type: body.ballot_type.code(),
+                max_score: body.max_score,
+                visibility: body.visibility.code(),
+                live: body.live,
+                lifetime: body.lifetime,
+            })
+            .await
+            .map_err(|e| {
+                tracing::error!("error while creating room: {e}");
+                warp::reject::custom(rejections::InternalServerError)
+            })?;
+
+        Ok(NewRoom {
+            id: room_id,
+            admin_code,
+            options: room_options(&store, room_id).await?,
+            expires_in: body.lifetime * 3600,
+        })
     }
 
-    async fn get_room(
-        conn: sqlx::Pool<sqlx::Sqlite>,
-        room_id: i64,
-        admin_code: String,
-    ) -> Result<impl warp::Reply, warp::Rejection> {
-        let room = sqlx::query!(
-            r#"
-        SELECT id, name, options, admin_code
-        FROM rooms
-        WHERE id = ?1 AND status = 0
-            "#,
-            room_id
-        )
-        .fetch_one(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while getting room: {e}");
-            match e {
-                sqlx::Error::RowNotFound => warp::reject::custom(rejections::RoomNotFound),
-                _ => warp::reject::custom(rejections::InternalServerError),
-            }
-        })?;
+    #[derive(Deserialize, ToSchema)]
+    pub struct ImportBody {
+        /// The name of the room. Defaults to the title of a BLT file.
+        #[serde(default)]
+        name: String,
+        #[serde(default)]
+        format: Format,
+        /// The contents of the ballot file.
+        file: String,
+        #[serde(default)]
+        method: Method,
+        /// Defaults to the seats of a BLT file, or 1.
+        #[serde(default, deserialize_with = "utils::optional_number")]
+        seats: Option<i64>,
+        #[serde(default)]
+        tie_break: TieBreak,
+        #[serde(default, deserialize_with = "utils::checkbox")]
+        partial: bool,
+        #[serde(default, deserialize_with = "utils::optional_number")]
+        max_ranks: Option<i64>,
+        /// Who can see the results besides the admin.
+        #[serde(default)]
+        visibility: Visibility,
+        /// How long the room stays open, in hours.
+        #[serde(default = "default_lifetime", deserialize_with = "utils::number")]
+        lifetime: i64,
+    }
 
-        let voters = sqlx::query!(
-            r#"
-        SELECT id, approved
-        FROM voters
-        WHERE room_id = ?1
-            "#,
-            room.id
-        )
-        .fetch_all(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while getting voters: {e}");
-            warp::reject::custom(rejections::InternalServerError)
-        })?;
+    /// A room made from a ballot file, or the lines of the file whose ballots were refused.
+    pub enum Imported {
+        Room(NewRoom, Box<ResultPage>),
+        Invalid(Vec<LineError>),
+    }
 
-        if room.admin_code != admin_code {
-            return Err(warp::reject::custom(NotRoomAdmin));
+    /// Creates an ended room holding the ballots of a file, each checked as if it had been cast
+    /// in the room, and counts them.
+    pub async fn import_room(
+        store: Db,
+        max_lifetime: i64,
+        body: ImportBody,
+    ) -> Result<Imported, warp::Rejection> {
+        let mut file = match files::read(body.format, &body.file) {
+            Ok(file) => file,
+            Err(errors) => return Ok(Imported::Invalid(errors)),
+        };
+
+        if body.method.ballot_type() != BallotType::Ranking {
+            return Err(warp::reject::custom(InvalidMethod));
         }
 
-        let page = RoomPage {
-            id: room.id,
-            name: room.name,
-            options: serde_json::from_str::<Vec<String>>(&room.options).unwrap(),
-            voters: voters
-                .into_iter()
-                .map(|r| Voter {
-                    id: r.id,
-                    approved: r.approved,
+        let mut room = CreateRoomBody {
+            name: match body.name.trim() {
+                "" => file.title.clone().unwrap_or_default(),
+                name => name.to_owned(),
+            },
+            options: file.options.clone(),
+            method: body.method,
+            seats: body.seats.or(file.seats).unwrap_or_else(default_seats),
+            tie_break: body.tie_break,
+            partial: body.partial,
+            max_ranks: body.max_ranks,
+            ballot_type: BallotType::Ranking,
+            max_score: default_max_score(),
+            visibility: body.visibility,
+            live: false,
+            lifetime: body.lifetime,
+        };
+        check_room(max_lifetime, &mut room)?;
+
+        // The room doesn't exist yet, so ballots are checked against the options numbered as
+        // in the file.
+        let rules = BallotRules {
+            options: file
+                .options
+                .iter()
+                .enumerate()
+                .map(|(idx, label)| RoomOption {
+                    id: idx as i64,
+                    label: label.clone(),
                 })
                 .collect(),
+            partial: room.partial,
+            max_ranks: room.max_ranks.map(|max| max as usize),
+            ballot_type: BallotType::Ranking,
+            max_score: room.max_score as u32,
         };
 
-        Ok(views::page("Admin", view(page)))
-    }
+        let mut errors = std::mem::take(&mut file.errors);
+        for (line, ballot) in &file.ballots {
+            let ballot = ballot
+                .iter()
+                .map(|group| group.iter().map(|idx| *idx as i64).collect())
+                .collect::<Vec<_>>();
 
-    struct RoomPage {
-        id: i64,
-        name: String,
-        options: Vec<String>,
-        voters: Vec<Voter>,
-    }
+            if let Err(e) = rules.check(&ballot) {
+                errors.push(LineError::new(*line, e));
+            }
+        }
 
-    struct Voter {
-        id: i64,
-        approved: bool,
-    }
+        // Weighted BLT lines hold many equal ballots, which are only reported once.
+        errors.sort_by_key(|error| error.line);
+        errors.dedup_by_key(|error| error.line);
+        if !errors.is_empty() {
+            return Ok(Imported::Invalid(errors));
+        }
 
-    fn view(room: RoomPage) -> Markup {
-        let voter_count = utils::format_num(room.voters.len() as i32);
-        let voter_label = utils::pluralize(room.voters.len() as i32, "voter", "voters");
+        if file.ballots.is_empty() {
+            return Err(warp::reject::custom(NoBallots));
+        }
 
-        let approved_voters_count = room.voters.iter().filter(|v| v.approved).count();
+        let new_room = store_room(store.clone(), &mut room).await?;
 
-        html! {
-            section."grid gap-lg w-800" hx-ext="sse" sse-connect=(names::room_listen_url(room.id)) {
-                h1."text-lg" { (room.name) }
+        // Options are stored sorted, and options sharing a label keep their order.
+        let mut unused = new_room.options.clone();
+        let ids = file
+            .options
+            .iter()
+            .map(|label| {
+                let idx = unused.iter().position(|o| &o.label == label).unwrap();
+                unused.remove(idx).id
+            })
+            .collect::<Vec<_>>();
 
-                div."alert" { "ROOM WILL CLOSE IN LESS THAN AN HOUR." }
+        let ballots = file
+            .ballots
+            .into_iter()
+            .map(|(_, ballot)| {
+                let ballot = ballot
+                    .into_iter()
+                    .map(|group| group.into_iter().map(|idx| ids[idx]).collect())
+                    .collect::<Vec<_>>();
+                (utils::generate_ulid(), voters::ranked_entries(&ballot))
+            })
+            .collect();
 
-                section."two-cols" {
-                    div."card card--secondary stat" hx-swap="innerHTML" sse-swap=(names::VOTER_COUNT_EVENT){
-                        p."stat__num" data-testid="voter-count" { (voter_count) }
-                        p."stat__desc" { (voter_label) " in room" }
-                    }
+        store
+            .import_ballots(new_room.id, ballots)
+            .await
+            .map_err(|e| {
+                tracing::error!("error while importing ballots: {e}");
+                warp::reject::custom(InternalServerError)
+            })?;
 
-                    div."card grid gap-lg" {
-                        h2."text-md" { "Options" }
-                        div."grid gap-sm" {
-                            @for option in room.options {
-                                span."boxed" { (option) }
-                            }
+        let stored = store.room(new_room.id).await.map_err(|e| {
+            tracing::error!("error while getting room: {e}");
+            warp::reject::custom(InternalServerError)
+        })?;
+        let page = count_votes(&store, stored).await?;
+
+        Ok(Imported::Room(new_room, Box::new(page)))
+    }
+
+    async fn import_page(max_lifetime: i64) -> Result<impl warp::Reply, warp::Rejection> {
+        Ok(views::page("Import", import_form(max_lifetime)))
+    }
+
+    async fn import_ballots(
+        store: Db,
+        max_lifetime: i64,
+        body: ImportBody,
+    ) -> Result<warp::reply::Response, warp::Rejection> {
+        let errors = match import_room(store, max_lifetime, body).await {
+            Ok(Imported::Room(room, page)) => {
+                let cookie = utils::cookie(
+                    names::ROOM_ADMIN_COOKIE_NAME,
+                    &room.admin_code,
+                    room.expires_in,
+                );
+                let resp = Response::builder()
+                    .header(SET_COOKIE, cookie)
+                    .header("HX-Replace-Url", names::results_url(room.id))
+                    .body(views::titled("Results", voting::result_page(*page, true)).into_string())
+                    .unwrap();
+
+                return Ok(resp.into_response());
+            }
+            Ok(Imported::Invalid(errors)) => html! {
+                @for error in errors {
+                    p."text-sm" { "LINE " (error.line) ": " (error.error) }
+                }
+            },
+            // Bad settings are shown next to the form too, as the file would be lost otherwise.
+            Err(e) => match rejections::reason(&e) {
+                (StatusCode::BAD_REQUEST, code) => html! { p."text-sm" { (code) } },
+                _ => return Err(e),
+            },
+        };
+
+        let resp = Response::builder()
+            .header("HX-Retarget", "#import-errors")
+            .header("HX-Reswap", "innerHTML")
+            .body(errors.into_string())
+            .unwrap();
+
+        Ok(resp.into_response())
+    }
+
+    fn import_form(max_lifetime: i64) -> Markup {
+        html! {
+            section."center" {
+                form."w-500 grid gap-md"
+                    data-testid="import-form"
+                    hx-post=(names::import_url())
+                    hx-ext="json-enc"
+                    hx-target="main"
+                    hx-swap="innerHTML" {
+                    h1."text-lg" { "IMPORT BALLOTS" }
+
+                    div."grid gap-sm" {
+                        label."text-md" { "FILE" }
+                        select."input-text" name="format" id="importFormat" {
+                            @for format in Format::ALL {
+                                option value=(format.value()) { (format.label()) }
+                            }
+                        }
+                        input."input-text" type="file" id="importFile" accept=".blt,.csv,.txt" {}
+                        textarea."input-text ballot-file" name="file" id="importText" rows="10" required="true" placeholder="or paste the ballots here" {}
+                    }
+
+                    div."grid gap-sm" {
+                        label."text-md" { "NAME" }
+                        input."input-text" name="name" placeholder="the title of the file" {}
+                    }
+
+                    div."grid gap-sm" {
+                        label."text-md" { "COUNTING METHOD" }
+                        select."input-text" name="method" {
+                            @for method in Method::ALL.into_iter().filter(|m| m.ballot_type() == BallotType::Ranking) {
+                                option value=(method.value()) { (method.label()) }
+                            }
+                        }
+                    }
+
+                    div."grid gap-sm" {
+                        label."text-md" { "TIE BREAK" }
+                        select."input-text" name="tie_break" {
+                            @for tie_break in TieBreak::ALL {
+                                option value=(tie_break.value()) { (tie_break.label()) }
+                            }
+                        }
+                    }
+
+                    div."grid gap-sm" {
+                        label."text-md" { "SEATS" }
+                        input."input-text" name="seats" type="number" min="1" placeholder="as in the file, or 1" {}
+                    }
+
+                    div."grid gap-sm" {
+                        label."text-md" { "BALLOTS" }
+                        label."flex gap-sm text-sm" {
+                            input type="checkbox" name="partial" checked {}
+                            "ALLOW BALLOTS THAT LEAVE OPTIONS UNRANKED"
+                        }
+                        input."input-text" name="max_ranks" type="number" min="1" placeholder="rank at most (optional)" {}
+                    }
+
+                    div."grid gap-sm" {
+                        label."text-md" { "RESULTS VISIBLE TO" }
+                        select."input-text" name="visibility" {
+                            @for visibility in Visibility::ALL {
+                                option value=(visibility.value()) { (visibility.label()) }
+                            }
+                        }
+                    }
+
+                    div."grid gap-sm" {
+                        label."text-md" { "ROOM LIFETIME (HOURS)" }
+                        input."input-text" name="lifetime" type="number" min="1" max=(max_lifetime) value="1" required="true" {}
+                    }
+
+                    div."grid gap-sm" id="import-errors" data-testid="import-errors" {}
+
+                    button."button w-full" type="submit" { "IMPORT BALLOTS" }
+                }
+            }
+        }
+    }
+
+    /// The options of a room, in the order they appear on ballots.
+    pub async fn room_options(
+        store: &Db,
+        room_id: i64,
+    ) -> Result<Vec<RoomOption>, warp::Rejection> {
+        store.options(room_id).await.map_err(|e| {
+            tracing::error!("error while getting options: {e}");
+            warp::reject::custom(rejections::InternalServerError)
+        })
+    }
+
+    async fn get_room(
+        store: Db,
+        broadcasters: Broadcasters,
+        room_id: i64,
+        admin_code: String,
+    ) -> Result<warp::reply::Response, warp::Rejection> {
+        let room = store.room(room_id).await.map_err(|e| {
+            tracing::error!("error while getting room: {e}");
+            match e {
+                sqlx::Error::RowNotFound => warp::reject::custom(rejections::RoomNotFound),
+                _ => warp::reject::custom(rejections::InternalServerError),
+            }
+        })?;
+
+        // Once the vote has ended, the admin page is its results.
+        if room.status == store::ENDED && room.admin_code == admin_code {
+            let url = names::results_url(room.id).parse::<Uri>().unwrap();
+            return Ok(warp::redirect::see_other(url).into_response());
+        }
+
+        let room = room
+            .in_status(store::WAITING)
+            .map_err(|_| warp::reject::custom(rejections::RoomNotFound))?;
+
+        let voters = store.voters(room.id).await.map_err(|e| {
+            tracing::error!("error while getting voters: {e}");
+            warp::reject::custom(rejections::InternalServerError)
+        })?;
+
+        if room.admin_code != admin_code {
+            return Err(warp::reject::custom(NotRoomAdmin));
+        }
+
+        let online = broadcasters.online_voters(room.id).await;
+
+        let page = RoomPage {
+            id: room.id,
+            name: room.name,
+            options: room_options(&store, room.id)
+                .await?
+                .into_iter()
+                .map(|option| option.label)
+                .collect(),
+            method: Method::from_code(room.method).unwrap_or_default(),
+            seats: room.seats,
+            tie_break: TieBreak::from_code(room.tie_break).unwrap_or_default(),
+            partial: room.partial,
+            max_ranks: room.max_ranks,
+            ballot_type: BallotType::from_code(room.ballot_type).unwrap_or_default(),
+            max_score: room.max_score,
+            visibility: Visibility::from_code(room.visibility).unwrap_or_default(),
+            live: room.live,
+            expires_in: room.expires_in,
+            voters: voters
+                .into_iter()
+                .map(|r| Voter {
+                    id: r.id,
+                    approved: r.approved,
+                    online: online.contains(&r.id),
+                })
+                .collect(),
+        };
+
+        Ok(views::page("Admin", view(page)).into_response())
+    }
+
+    struct RoomPage {
+        id: i64,
+        name: String,
+        options: Vec<String>,
+        method: Method,
+        seats: i64,
+        tie_break: TieBreak,
+        partial: bool,
+        max_ranks: Option<i64>,
+        ballot_type: BallotType,
+        max_score: i64,
+        visibility: Visibility,
+        live: bool,
+        /// Seconds until the room is deleted.
+        expires_in: i64,
+        voters: Vec<Voter>,
+    }
+
+    struct Voter {
+        id: i64,
+        approved: bool,
+        online: bool,
+    }
+
+    fn view(room: RoomPage) -> Markup {
+        let voter_count = utils::format_num(room.voters.len() as i32);
+        let voter_label = utils::pluralize(room.voters.len() as i32, "voter", "voters");
+
+        let approved_voters_count = room.voters.iter().filter(|v| v.approved).count();
+
+        html! {
+            section."grid gap-lg w-800" hx-ext="sse" sse-connect=(names::room_listen_url(room.id)) {
+                h1."text-lg" { (room.name) }
+
+                div."alert" { "ROOM WILL CLOSE IN " (utils::time_left(room.expires_in)) "." }
+
+                section."two-cols" {
+                    div."card card--secondary stat" hx-swap="innerHTML" sse-swap=(names::VOTER_COUNT_EVENT){
+                        p."stat__num" data-testid="voter-count" { (voter_count) }
+                        p."stat__desc" { (voter_label) " in room" }
+                    }
+
+                    div."card grid gap-lg" {
+                        h2."text-md" { "Options" }
+                        div."grid gap-sm" {
+                            @for option in room.options {
+                                span."boxed" { (option) }
+                            }
+                        }
+                        p."text-sm" {
+                            "BALLOT: " (room.ballot_type.label())
+                            @if matches!(room.ballot_type, BallotType::Score | BallotType::Star) { " FROM 0 TO " (room.max_score) }
+                        }
+                        p."text-sm" {
+                            "COUNTED WITH " (room.method.label())
+                            @if room.seats > 1 { " FOR " (room.seats) " SEATS" }
+                        }
+                        p."text-sm" { "TIE BREAK: " (room.tie_break.label()) }
+                        p."text-sm" { "RESULTS VISIBLE TO: " (room.visibility.label()) }
+                        @if room.live {
+                            p."text-sm" { "RESULTS ARE SHOWN LIVE AS VOTES COME IN" }
+                        }
+                        @if let Some(max_ranks) = room.max_ranks {
+                            p."text-sm" { "VOTERS MAY RANK UP TO " (max_ranks) " " (utils::pluralize(max_ranks as i32, "OPTION", "OPTIONS")) }
+                        } @else if room.partial {
+                            p."text-sm" { "VOTERS MAY LEAVE OPTIONS UNRANKED" }
                         }
                     }
                 }
@@ -520,6 +1139,8 @@ mod rooms {
                                 span { (voter.id) }
                             }
 
+                            (voting::presence(voter.id, voter.online))
+
                             @if voter.approved {
                                 button."button w-fit" disabled { "APPROVED" }
                             } @else {
@@ -532,24 +1153,15 @@ mod rooms {
         }
     }
 
-    async fn join_room_page(
-        conn: sqlx::Pool<sqlx::Sqlite>,
-        room_id: i64,
-    ) -> Result<impl warp::Reply, warp::Rejection> {
-        let room = sqlx::query!(
-            r#"
-        SELECT name
-        FROM rooms
-        WHERE id = ?1 AND status = 0
-            "#,
-            room_id
-        )
-        .fetch_one(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while getting room name: {e}");
-            warp::reject::custom(InternalServerError)
-        })?;
+    async fn join_room_page(store: Db, room_id: i64) -> Result<impl warp::Reply, warp::Rejection> {
+        let room = store
+            .room(room_id)
+            .await
+            .and_then(|room| room.in_status(store::WAITING))
+            .map_err(|e| {
+                tracing::error!("error while getting room name: {e}");
+                warp::reject::custom(InternalServerError)
+            })?;
 
         Ok(views::page(
             "Join Room",
@@ -565,54 +1177,73 @@ mod rooms {
     }
 
     async fn join_room(
-        conn: sqlx::Pool<sqlx::Sqlite>,
+        store: Db,
         broadcasters: Broadcasters,
         room_id: i64,
     ) -> Result<impl warp::Reply, warp::Rejection> {
-        let room_name = sqlx::query!(
-            r#"
-        SELECT name
-        FROM rooms
-        WHERE id = ?1
-            "#,
-            room_id
-        )
-        .fetch_one(&conn)
-        .await
-        .map_err(|e| {
+        let voter = add_voter(store, broadcasters, room_id).await?;
+
+        let page = views::titled(
+            "Voter",
+            voters::view(VoterPage {
+                id: voter.id,
+                room_id,
+                room_name: voter.room_name,
+                voter_count: voter.voter_count,
+                approved: false,
+            }),
+        );
+
+        let cookie = utils::cookie(
+            names::VOTER_COOKIE_NAME,
+            &voter.voter_code,
+            voter.expires_in,
+        );
+        let resp = Response::builder()
+            .header(SET_COOKIE, cookie)
+            .header("HX-Replace-Url", names::voter_page_url(voter.id))
+            .body(page.into_string())
+            .unwrap();
+
+        Ok(resp)
+    }
+
+    #[derive(Serialize, ToSchema)]
+    pub struct NewVoter {
+        pub id: i64,
+        pub voter_code: String,
+        pub room_name: String,
+        /// The options of the room, whose IDs ballots refer to.
+        pub options: Vec<RoomOption>,
+        pub voter_count: i32,
+        /// Seconds until the room is deleted.
+        pub expires_in: i64,
+    }
+
+    /// Adds a voter, still waiting to be approved, to a room.
+    pub async fn add_voter(
+        store: Db,
+        broadcasters: Broadcasters,
+        room_id: i64,
+    ) -> Result<NewVoter, warp::Rejection> {
+        let room = store.room(room_id).await.map_err(|e| {
             tracing::error!("error while getting room: {e}");
             warp::reject::custom(InternalServerError)
-        })?
-        .name;
+        })?;
 
         let voter_code = utils::generate_ulid();
-        let voter_id = sqlx::query!(
-            r#"
-        INSERT INTO voters (voter_code, room_id)
-        VALUES (?1, ?2)
-            "#,
-            voter_code,
-            room_id
-        )
-        .execute(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while creating new voter: {e}");
-            warp::reject::custom(InternalServerError)
-        })?
-        .last_insert_rowid();
+        let voter_id = store
+            .insert_voter(room_id, voter_code.clone())
+            .await
+            .map_err(|e| {
+                tracing::error!("error while creating new voter: {e}");
+                warp::reject::custom(InternalServerError)
+            })?;
 
-        let voter_count = sqlx::query!(
-            "SELECT count(id) as count FROM voters WHERE room_id = ?1",
-            room_id
-        )
-        .fetch_one(&conn)
-        .await
-        .map_err(|e| {
+        let voter_count = store.voter_count(room_id).await.map_err(|e| {
             tracing::error!("error while getting voters count: {e}");
             warp::reject::custom(InternalServerError)
-        })?
-        .count;
+        })?;
 
         tokio::spawn(async move {
             broadcasters
@@ -623,926 +1254,9972 @@ mod rooms {
                 .await;
         });
 
-        let page = views::titled(
-            "Voter",
-            voters::view(VoterPage {
-                id: voter_id,
-                room_id,
-                room_name,
-                voter_count,
-                approved: false,
-            }),
-        );
-
-        let cookie = utils::cookie(names::VOTER_COOKIE_NAME, &voter_code);
-        let resp = Response::builder()
-            .header(SET_COOKIE, cookie)
-            .header("HX-Replace-Url", names::voter_page_url(voter_id))
-            .body(page.into_string())
-            .unwrap();
-
-        Ok(resp)
+        Ok(NewVoter {
+            id: voter_id,
+            voter_code,
+            room_name: room.name,
+            options: room_options(&store, room_id).await?,
+            voter_count,
+            expires_in: room.expires_in,
+        })
     }
 
     async fn start_vote(
-        conn: sqlx::Pool<sqlx::Sqlite>,
+        store: Db,
         broadcasters: Broadcasters,
         room_id: i64,
         admin_code: String,
     ) -> Result<impl warp::Reply, warp::Rejection> {
-        let room = sqlx::query!(
-            r#"
-        SELECT admin_code, name, options
-        FROM rooms
-        WHERE id = ?1 AND status = 0
-            "#,
-            room_id
-        )
-        .fetch_one(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while getting room: {e}");
-            warp::reject::custom(InternalServerError)
-        })?;
+        let page = begin_vote(store, broadcasters, room_id, admin_code).await?;
+
+        Ok(views::titled("Vote Started", voting::admin_page(page)))
+    }
+
+    /// Starts the vote in a waiting room and sends the ballot to its voters.
+    pub async fn begin_vote(
+        store: Db,
+        broadcasters: Broadcasters,
+        room_id: i64,
+        admin_code: String,
+    ) -> Result<VoteAdminPage, warp::Rejection> {
+        let room = store
+            .room(room_id)
+            .await
+            .and_then(|room| room.in_status(store::WAITING))
+            .map_err(|e| {
+                tracing::error!("error while getting room: {e}");
+                warp::reject::custom(InternalServerError)
+            })?;
 
         if admin_code != room.admin_code {
             return Err(warp::reject::custom(NotRoomAdmin));
         }
 
-        sqlx::query!(
-            r#"
-        UPDATE rooms
-        SET status = 1
-        WHERE id = ?1
-            "#,
-            room_id
-        )
-        .execute(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while setting room status to `started`: {e}");
-            warp::reject::custom(InternalServerError)
-        })?;
+        store
+            .set_status(room_id, store::STARTED)
+            .await
+            .map_err(|e| {
+                tracing::error!("error while setting room status to `started`: {e}");
+                warp::reject::custom(InternalServerError)
+            })?;
 
-        let voters = sqlx::query!(
-            r#"
-        SELECT id, options
-        FROM voters
-        WHERE voters.room_id = ?1 AND voters.approved = TRUE
-            "#,
-            room_id
-        )
-        .fetch_all(&conn)
-        .await
-        .map_err(|e| {
+        let voters = store.voters(room_id).await.map_err(|e| {
             tracing::error!("error while getting voters: {e}");
             warp::reject::custom(InternalServerError)
         })?;
 
-        let options = serde_json::from_str(&room.options).unwrap();
+        let rules = BallotRules {
+            options: room_options(&store, room_id).await?,
+            partial: room.partial,
+            max_ranks: room.max_ranks.map(|n| n as usize),
+            ballot_type: BallotType::from_code(room.ballot_type).unwrap_or_default(),
+            max_score: room.max_score as u32,
+        };
+        let online = broadcasters.online_voters(room_id).await;
         tokio::spawn(async move {
             broadcasters
-                .send_event(room_id, RoomEvents::VoteStarted(options))
+                .send_event(room_id, RoomEvents::VoteStarted(rules))
                 .await;
         });
 
-        let page = voting::admin_page(VoteAdminPage {
+        Ok(VoteAdminPage {
             room_id,
             room_name: room.name,
+            expires_in: room.expires_in,
             recorded_votes: 0,
+            live: room.live,
             approved_voters: voters
                 .into_iter()
+                .filter(|v| v.approved)
                 .map(|v| voting::Voter {
                     id: v.id,
-                    voted: v.options.map(|_| true).unwrap_or_default(),
+                    voted: v.voted,
+                    online: online.contains(&v.id),
                 })
                 .collect(),
-        });
-
-        Ok(views::titled("Vote Started", page))
+        })
     }
 
     async fn end_vote(
-        conn: sqlx::Pool<sqlx::Sqlite>,
+        store: Db,
         broadcasters: Broadcasters,
         room_id: i64,
         admin_code: String,
     ) -> Result<impl warp::Reply, warp::Rejection> {
-        let room = sqlx::query!(
-            r#"
-        SELECT admin_code, name, options
-        FROM rooms
-        WHERE id = ?1 AND status = 1
-            "#,
-            room_id
-        )
-        .fetch_one(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while getting room: {e}");
-            warp::reject::custom(InternalServerError)
-        })?;
+        let page = finish_vote(store, broadcasters, room_id, admin_code).await?;
 
-        if admin_code != room.admin_code {
-            return Err(warp::reject::custom(NotRoomAdmin));
-        }
+        let resp = Response::builder()
+            .header("HX-Replace-Url", names::results_url(room_id))
+            .body(views::titled("Vote Ended", voting::result_page(page, true)).into_string())
+            .unwrap();
 
-        sqlx::query!(
-            r#"
-        UPDATE rooms
-        SET status = 2
-        WHERE id = ?1
-            "#,
-            room_id
-        )
-        .execute(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while setting room status to `ended`: {e}");
-            warp::reject::custom(InternalServerError)
-        })?;
+        Ok(resp)
+    }
 
-        let votes = sqlx::query!(
-            r#"
-        SELECT options
-        FROM voters
-        WHERE voters.room_id = ?1 AND voters.approved = TRUE AND options NOT NULL
-            "#,
-            room_id
-        )
-        .fetch_all(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while getting voters: {e}");
+    /// Ends the vote in a started room and counts it.
+    pub async fn finish_vote(
+        store: Db,
+        broadcasters: Broadcasters,
+        room_id: i64,
+        admin_code: String,
+    ) -> Result<ResultPage, warp::Rejection> {
+        let room = store
+            .room(room_id)
+            .await
+            .and_then(|room| room.in_status(store::STARTED))
+            .map_err(|e| {
+                tracing::error!("error while getting room: {e}");
+                warp::reject::custom(InternalServerError)
+            })?;
+
+        if admin_code != room.admin_code {
+            return Err(warp::reject::custom(NotRoomAdmin));
+        }
+
+        store.set_status(room_id, store::ENDED).await.map_err(|e| {
+            tracing::error!("error while setting room status to `ended`: {e}");
             warp::reject::custom(InternalServerError)
         })?;
 
-        let scores = votes
-            .into_iter()
-            .map(|r| r.options.unwrap())
-            .map(|r| serde_json::from_str::<Vec<String>>(&r).unwrap())
-            .fold(HashMap::<String, usize>::new(), |map, options| {
-                let options_len = options.len();
-                options
-                    .into_iter()
-                    .enumerate()
-                    .fold(map, |mut map, (idx, choice)| {
-                        let curr_score = options_len - idx;
-                        map.entry(choice)
-                            .and_modify(|score| *score += curr_score)
-                            .or_insert(curr_score);
-                        map
-                    })
-            });
-
-        let mut scores = scores.into_iter().collect::<Vec<_>>();
-        scores.sort_by_key(|(_, score)| *score);
-        scores.reverse();
+        let visibility = Visibility::from_code(room.visibility).unwrap_or_default();
+        let page = count_votes(&store, room).await;
 
+        // Voters who may see the results get them along with the end of the vote.
+        let results = match &page {
+            Ok(page) if visibility != Visibility::Admin => Some(Box::new(page.clone())),
+            _ => None,
+        };
         tokio::spawn(async move {
             broadcasters
-                .send_event(room_id, RoomEvents::VoteEnded)
+                .send_event(room_id, RoomEvents::VoteEnded(results))
                 .await;
             broadcasters.end_stream(room_id).await;
         });
 
-        let page = voting::result_page(ResultPage {
-            room_name: room.name,
-            scores: scores
-                .into_iter()
-                .map(|(option, score)| Score { option, score })
-                .collect(),
-        });
-
-        Ok(views::titled("Vote Ended", page))
+        page
     }
-}
 
-mod voters {
-    use maud::{html, Markup};
-    use serde::Deserialize;
-    use warp::Filter;
+    /// The results of an ended room, for its admin and for whoever else the room lets see them.
+    pub async fn visible_results(
+        store: Db,
+        room_id: i64,
+        admin_code: Option<String>,
+        voter_code: Option<String>,
+    ) -> Result<ResultPage, warp::Rejection> {
+        let room = store
+            .room(room_id)
+            .await
+            .and_then(|room| room.in_status(store::ENDED))
+            .map_err(|e| {
+                tracing::error!("error while getting room: {e}");
+                match e {
+                    sqlx::Error::RowNotFound => warp::reject::custom(rejections::RoomNotFound),
+                    _ => warp::reject::custom(InternalServerError),
+                }
+            })?;
 
-    use crate::{
-        events::{Broadcasters, RoomEvents},
-        names,
-        rejections::{InternalServerError, NotRoomAdmin, NotVoter, UnknownOptions, VoterNotFound},
-        utils, views, with_state,
-    };
+        if admin_code.as_deref() != Some(room.admin_code.as_str()) {
+            match Visibility::from_code(room.visibility).unwrap_or_default() {
+                Visibility::Admin => return Err(warp::reject::custom(NotRoomAdmin)),
+                Visibility::Voters => {
+                    let voter = match voter_code {
+                        Some(voter_code) => store
+                            .voter_by_code(room_id, voter_code)
+                            .await
+                            .map_err(|e| {
+                                tracing::error!("error while getting voter: {e}");
+                                warp::reject::custom(InternalServerError)
+                            })?,
+                        None => None,
+                    };
+                    if !voter.is_some_and(|v| v.approved) {
+                        return Err(warp::reject::custom(NotVoter));
+                    }
+                }
+                Visibility::Public => {}
+            }
+        }
 
-    #[derive(Deserialize)]
-    struct VoteBody {
-        options: Vec<String>,
+        count_votes(&store, room).await
     }
 
-    pub fn route(
-        conn: sqlx::Pool<sqlx::Sqlite>,
-        broadcasters: Broadcasters,
-    ) -> impl warp::Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-        let get_voter = with_state(conn.clone())
-            .and(warp::path!("voters" / i64))
-            .and(warp::get())
-            .and(warp::cookie::cookie(names::VOTER_COOKIE_NAME))
-            .and_then(get_voter)
-            .with(warp::trace::named("get_voter"));
+    /// The results of a room whose vote has ended. The votes are counted the first time they
+    /// are asked for, which is when the vote ends, and the results are kept as they are until
+    /// the room is deleted.
+    pub async fn count_votes(store: &Db, room: store::Room) -> Result<ResultPage, warp::Rejection> {
+        let room_id = room.id;
+        let stored = store.result(room_id).await.map_err(|e| {
+            tracing::error!("error while getting results: {e}");
+            warp::reject::custom(InternalServerError)
+        })?;
+        if let Some(result) = stored {
+            return Ok(kept_page(room, result));
+        }
 
-        let approve_voter = with_state(conn.clone())
-            .and(with_state(broadcasters.clone()))
-            .and(warp::path!("voters" / i64 / "approve"))
-            .and(warp::put())
-            .and(warp::cookie::cookie(names::ROOM_ADMIN_COOKIE_NAME))
-            .and_then(approve_voter)
-            .with(warp::trace::named("approve_voter"));
+        let page = tally_votes(store, room).await?;
+        store
+            .insert_result(room_id, kept_result(&page))
+            .await
+            .map_err(|e| {
+                tracing::error!("error while saving results: {e}");
+                warp::reject::custom(InternalServerError)
+            })?;
 
-        let vote = with_state(conn.clone())
-            .and(with_state(broadcasters.clone()))
-            .and(warp::path!("voters" / i64 / "vote"))
-            .and(warp::post())
-            .and(warp::cookie::cookie(names::VOTER_COOKIE_NAME))
-            .and(warp::body::json::<VoteBody>())
-            .and_then(vote)
-            .with(warp::trace::named("vote"));
+        Ok(page)
+    }
 
-        get_voter.or(approve_voter).or(vote)
+    /// The rows kept for counted results.
+    fn kept_result(page: &ResultPage) -> store::CountedResult {
+        let counts = |counts: &[usize]| counts.iter().map(|&n| n as i64).collect();
+
+        store::CountedResult {
+            method: page.method.code(),
+            seats: page.seats as i64,
+            quota: page.quota,
+            tie_break: page.tie_break.code(),
+            seed: page.seed as i64,
+            partial: page.partial,
+            max_score: page.max_score as i64,
+            voters: page.voters as i64,
+            scores: page
+                .scores
+                .iter()
+                .map(|score| store::ResultScore {
+                    label: score.option.clone(),
+                    score: score.score,
+                    elected: score.elected,
+                    tied: score.tied,
+                })
+                .collect(),
+            ties: page.ties.clone(),
+            pairwise: page
+                .pairwise
+                .options
+                .iter()
+                .zip(&page.pairwise.prefs)
+                .map(|(option, prefs)| (option.clone(), counts(prefs)))
+                .collect(),
+            rounds: page
+                .rounds
+                .iter()
+                .map(|round| store::ResultRound {
+                    tallies: round.tallies.clone(),
+                    elected: round.elected.clone(),
+                    eliminated: round.eliminated.clone(),
+                    tied: round.tied.clone(),
+                    exhausted: round.exhausted,
+                    transfers: round
+                        .transfers
+                        .iter()
+                        .map(|t| (t.from.clone(), t.to.clone(), t.votes))
+                        .collect(),
+                })
+                .collect(),
+            runoff: page.runoff.as_ref().map(|runoff| store::ResultRunoff {
+                finalists: runoff
+                    .finalists
+                    .clone()
+                    .map(|(option, voters)| (option, voters as i64)),
+                no_preference: runoff.no_preference as i64,
+            }),
+            profiles: page
+                .profiles
+                .iter()
+                .map(|(option, grades)| (option.clone(), counts(grades)))
+                .collect(),
+        }
     }
 
-    async fn get_voter(
-        conn: sqlx::Pool<sqlx::Sqlite>,
-        voter_id: i64,
-        voter_code: String,
-    ) -> Result<impl warp::Reply, warp::Rejection> {
-        let voter = sqlx::query!(
-            r#"
-        SELECT voter_code, approved, room_id
-        FROM voters
-        WHERE id = ?1
-            "#,
-            voter_id
-        )
-        .fetch_one(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while getting voter: {e}");
-            match e {
-                sqlx::Error::RowNotFound => warp::reject::custom(VoterNotFound),
-                _ => warp::reject::custom(InternalServerError),
-            }
-        })?;
+    /// The results of a room as they were kept when its votes were counted.
+    fn kept_page(room: store::Room, result: store::CountedResult) -> ResultPage {
+        let counts = |counts: Vec<i64>| counts.into_iter().map(|n| n as usize).collect();
+        let (options, prefs) = result
+            .pairwise
+            .into_iter()
+            .map(|(option, prefs)| (option, counts(prefs)))
+            .unzip();
 
-        if voter_code != voter.voter_code {
-            return Err(warp::reject::custom(NotVoter));
+        ResultPage {
+            room_id: room.id,
+            room_name: room.name,
+            method: Method::from_code(result.method).unwrap_or_default(),
+            seats: result.seats as usize,
+            quota: result.quota,
+            tie_break: TieBreak::from_code(result.tie_break).unwrap_or_default(),
+            seed: result.seed as u64,
+            partial: result.partial,
+            max_score: result.max_score as u32,
+            voters: result.voters as usize,
+            ties: result.ties,
+            pairwise: Pairwise { options, prefs },
+            scores: result
+                .scores
+                .into_iter()
+                .map(|score| Score {
+                    option: score.label,
+                    score: score.score,
+                    elected: score.elected,
+                    tied: score.tied,
+                })
+                .collect(),
+            rounds: result
+                .rounds
+                .into_iter()
+                .map(|round| Round {
+                    tallies: round.tallies,
+                    elected: round.elected,
+                    eliminated: round.eliminated,
+                    exhausted: round.exhausted,
+                    transfers: round
+                        .transfers
+                        .into_iter()
+                        .map(|(from, to, votes)| Transfer { from, to, votes })
+                        .collect(),
+                    tied: round.tied,
+                })
+                .collect(),
+            runoff: result.runoff.map(|runoff| Runoff {
+                finalists: runoff
+                    .finalists
+                    .map(|(option, voters)| (option, voters as usize)),
+                no_preference: runoff.no_preference as usize,
+            }),
+            profiles: result
+                .profiles
+                .into_iter()
+                .map(|(option, grades)| (option, counts(grades)))
+                .collect(),
         }
+    }
 
-        let room_name = sqlx::query!(
-            r#"
-        SELECT name
-        FROM rooms
-        WHERE id = ?1 AND status = 0
-            "#,
-            voter.room_id
-        )
-        .fetch_one(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while getting room: {e}");
+    /// Counts the ballots of a room as they are now.
+    pub async fn tally_votes(store: &Db, room: store::Room) -> Result<ResultPage, warp::Rejection> {
+        let room_id = room.id;
+        let ballot_type = BallotType::from_code(room.ballot_type).unwrap_or_default();
+        let names = voters::option_names(&room_options(store, room_id).await?);
+        let stored = store.ballots(room_id).await.map_err(|e| {
+            tracing::error!("error while getting ballots: {e}");
             warp::reject::custom(InternalServerError)
-        })?
-        .name;
+        })?;
+        let (ballots, ratings) = voters::decode_votes(ballot_type, &names, stored);
+        let election = Election {
+            options: names.into_iter().map(|(_, name)| name).collect(),
+            ballots,
+            ratings,
+            seats: room.seats as usize,
+            tie_break: TieBreak::from_code(room.tie_break).unwrap_or_default(),
+            seed: room.seed as u64,
+        };
+        let method = Method::from_code(room.method).unwrap_or_default();
+        let outcome = method.tally().tally(&election);
+        let pairwise = Pairwise::new(&election);
+        let profiles = match method {
+            Method::MajorityJudgment => tally::merit_profiles(&election),
+            _ => Vec::new(),
+        };
 
-        let voter_count = sqlx::query!(
-            r#"
-        SELECT count(id) as count
-        FROM voters
-        WHERE room_id = ?1
-            "#,
-            voter.room_id
-        )
-        .fetch_one(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while getting voter count: {e}");
-            warp::reject::custom(InternalServerError)
-        })?
-        .count;
+        Ok(ResultPage {
+            room_id,
+            room_name: room.name,
+            method,
+            seats: election.seats,
+            quota: outcome.quota,
+            tie_break: election.tie_break,
+            seed: election.seed,
+            partial: room.partial,
+            max_score: room.max_score as u32,
+            voters: election.ballots.len(),
+            pairwise,
+            scores: outcome
+                .scores
+                .into_iter()
+                .map(|(option, score)| Score {
+                    elected: outcome.elected.contains(&option),
+                    tied: outcome.ties.iter().any(|t| t.contains(&option)),
+                    option,
+                    score,
+                })
+                .collect(),
+            rounds: outcome.rounds,
+            ties: outcome.ties,
+            runoff: outcome.runoff,
+            profiles,
+        })
+    }
+
+    async fn results(
+        store: Db,
+        room_id: i64,
+        admin_code: Option<String>,
+        voter_code: Option<String>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let admin = match &admin_code {
+            Some(admin_code) => store
+                .room(room_id)
+                .await
+                .is_ok_and(|room| &room.admin_code == admin_code),
+            None => false,
+        };
+        let page = visible_results(store, room_id, admin_code, voter_code).await?;
+
+        Ok(views::page("Results", voting::result_page(page, admin)))
+    }
+
+    async fn round_results(
+        store: Db,
+        room_id: i64,
+        admin_code: Option<String>,
+        voter_code: Option<String>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let page = visible_results(store, room_id, admin_code, voter_code).await?;
 
         Ok(views::page(
-            "Voter",
-            view(VoterPage {
-                id: voter_id,
-                room_id: voter.room_id,
-                room_name,
-                voter_count,
-                approved: voter.approved,
+            "Rounds",
+            voting::rounds_page(RoundsPage {
+                room_name: page.room_name,
+                method: page.method,
+                rounds: page.rounds,
             }),
         ))
     }
 
-    pub struct VoterPage {
-        pub id: i64,
-        pub room_id: i64,
-        pub room_name: String,
-        pub voter_count: i32,
-        pub approved: bool,
-    }
+    #[cfg(test)]
+    mod tests {
+        use std::time::Duration;
 
-    pub fn view(voter: VoterPage) -> Markup {
-        let voter_count = utils::format_num(voter.voter_count);
-        let voter_label = utils::pluralize(voter.voter_count, "voter", "voters");
+        use serde_json::{json, Value};
+        use warp::http::StatusCode;
 
-        html! {
-            section."grid gap-lg w-800" hx-ext="sse" sse-connect=(names::room_listen_url(voter.room_id)) {
-                h1."text-lg" { (voter.room_name) }
+        use super::Visibility;
+        use crate::{events::Broadcasters, names, store::BallotEntry, tests};
 
-                section."two-cols" {
-                    div."card card--secondary stat" hx-swap="innerHTML" sse-swap=(names::VOTER_COUNT_EVENT) {
-                        p."stat__num" data-testid="voter-count" { (voter_count) }
-                        p."stat__desc" { (voter_label) " in room" }
-                    }
+        #[tokio::test]
+        async fn rooms_close_after_their_lifetime() {
+            let app = tests::app(tests::store().await, Broadcasters::new());
 
-                    div."card grid gap-lg" {
-                        h2."text-md" { "YOUR VOTER ID" }
-                        span."code" { (voter.id) }
-                        @if voter.approved {
-                            div."alert" { "VOTER HAS BEEN APPROVED." }
-                        } @else {
-                            div."alert" hx-swap="outerHTML" sse-swap=(names::voter_approved_event(voter.id)) {
-                                "WAITING TO BE APPROVED."
-                            }
-                        }
-                    }
-                }
+            let resp = warp::test::request()
+                .method("POST")
+                .path(&names::rooms_url())
+                .json(&json!({"name": "Retro", "options": ["Keep", "Drop"], "lifetime": "30"}))
+                .reply(&app)
+                .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert!(tests::text(&resp).contains("ROOM WILL CLOSE IN LESS THAN 30 HOURS."));
+            let cookie = resp.headers()["set-cookie"].to_str().unwrap();
+            assert!(cookie.contains("; Max-Age=108000;"));
+
+            // The admin page works out the time left from when the room expires.
+            let admin_code = cookie.split(';').next().unwrap();
+            let room_url = resp.headers()["hx-replace-url"].to_str().unwrap();
+            let resp = warp::test::request()
+                .path(room_url)
+                .header("cookie", admin_code)
+                .reply(&app)
+                .await;
+            assert!(tests::text(&resp).contains("ROOM WILL CLOSE IN LESS THAN 30 HOURS."));
+
+            for lifetime in [0, tests::MAX_LIFETIME + 1] {
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&names::rooms_url())
+                    .json(&json!({"name": "Retro", "options": ["Keep"], "lifetime": lifetime}))
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+                assert!(tests::text(&resp).contains("INVALID_LIFETIME"));
+            }
+        }
 
-                div hx-swap="innerHTML" sse-swap=(names::VOTE_STARTED_EVENT) {
-                    div."alert" { "VOTES WILL START SHORTLY." }
-                }
+        #[tokio::test]
+        async fn rated_methods_only_count_rated_ballots() {
+            let app = tests::app(tests::store().await, Broadcasters::new());
+
+            for method in ["approval", "score", "star", "majority-judgment"] {
+                let body = json!({
+                    "name": "Lunch",
+                    "options": ["Pasta", "Pizza"],
+                    "method": method,
+                    "ballot_type": "ranking",
+                });
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/v1/rooms")
+                    .json(&body)
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{method}");
+                assert_eq!(tests::json(&resp), json!({"error": "INVALID_METHOD"}));
+            }
 
-                div hx-swap="innerHTML" sse-swap=(names::VOTE_ENDED_EVENT) { }
+            // Rated ballots still pick their own method.
+            let body = json!({
+                "name": "Lunch",
+                "options": ["Pasta", "Pizza"],
+                "method": "borda",
+                "ballot_type": "score",
+            });
+            let resp = warp::test::request()
+                .method("POST")
+                .path("/api/v1/rooms")
+                .json(&body)
+                .reply(&app)
+                .await;
+            assert_eq!(resp.status(), StatusCode::CREATED);
+        }
+
+        #[tokio::test]
+        async fn results_are_kept_as_they_were_counted() {
+            for store in tests::stores().await {
+                let app = tests::app(store.clone(), Broadcasters::new());
+                let room = tests::room(
+                    &app,
+                    json!({"name": "Lunch", "options": ["Pasta", "Pizza"]}),
+                    1,
+                )
+                .await;
+                let (pasta, pizza) = (room.options[0], room.options[1]);
+                let (voter_id, voter_code) = &room.voters[0];
+                let admin_cookie = format!("{}={}", names::ROOM_ADMIN_COOKIE_NAME, room.admin_code);
+
+                // Nothing is shown before the vote ends.
+                let results = names::results_url(room.id);
+                let resp = warp::test::request()
+                    .path(&results)
+                    .header("cookie", &admin_cookie)
+                    .reply(&app)
+                    .await;
+                assert!(tests::text(&resp).contains("ROOM_NOT_FOUND"));
+
+                warp::test::request()
+                    .method("PUT")
+                    .path(&names::start_vote_url(room.id))
+                    .header("cookie", &admin_cookie)
+                    .reply(&app)
+                    .await;
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&format!("/api/v1/voters/{voter_id}/vote"))
+                    .header("authorization", format!("Bearer {voter_code}"))
+                    .json(&json!({"options": [pizza, pasta]}))
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+                let resp = warp::test::request()
+                    .method("PUT")
+                    .path(&names::end_vote_url(room.id))
+                    .header("cookie", &admin_cookie)
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::OK);
+                let ended = tests::text(&resp);
+
+                // Changing the ballots afterwards would change a new count, but not the results.
+                let flipped = [(pasta, 0), (pizza, 1)].map(|(option_id, rank)| BallotEntry {
+                    option_id,
+                    rank,
+                    score: None,
+                });
+                store
+                    .record_vote(*voter_id, flipped.to_vec())
+                    .await
+                    .unwrap();
+                let room_row = store.room(room.id).await.unwrap();
+                let recount = super::tally_votes(&store, room_row).await.unwrap();
+                assert_eq!(recount.scores[0].option, "Pasta");
+
+                let resp = warp::test::request()
+                    .path(&results)
+                    .header("cookie", &admin_cookie)
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::OK);
+                let scores = |page: &str| {
+                    let start = page.find("data-testid=\"result-scores\"").unwrap();
+                    page[start..start + page[start..].find("</section>").unwrap()].to_owned()
+                };
+                assert_eq!(scores(&tests::text(&resp)), scores(&ended));
+
+                let resp = warp::test::request()
+                    .path(&format!("/api/v1/rooms/{}/results", room.id))
+                    .header("authorization", format!("Bearer {}", room.admin_code))
+                    .reply(&app)
+                    .await;
+                let page = tests::json(&resp);
+                assert_eq!(page["scores"][0]["option"], "Pizza");
+                assert_eq!(page["voters"], 1);
             }
         }
-    }
 
-    async fn approve_voter(
-        conn: sqlx::Pool<sqlx::Sqlite>,
-        broadcasters: Broadcasters,
-        voter_id: i64,
-        admin_code: String,
-    ) -> Result<impl warp::Reply, warp::Rejection> {
-        let room = sqlx::query!(
-            r#"
-        SELECT id, admin_code
-        FROM rooms
-        WHERE id = (SELECT room_id FROM voters WHERE id = ?1)
-            "#,
-            voter_id
-        )
-        .fetch_one(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while getting voter: {e}");
-            warp::reject::custom(InternalServerError)
-        })?;
+        #[tokio::test]
+        async fn results_are_shown_to_whoever_the_room_allows() {
+            for visibility in Visibility::ALL {
+                let app = tests::app(tests::store().await, Broadcasters::new());
+                let body = json!({
+                    "name": "Lunch",
+                    "options": ["Pasta", "</script><script>alert(1)</script>"],
+                    "visibility": visibility.value(),
+                });
+                let room = tests::room(&app, body, 1).await;
+                let (voter_id, voter_code) = &room.voters[0];
+                let admin = format!("Bearer {}", room.admin_code);
+
+                // Someone who joined but was never approved.
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&format!("/api/v1/rooms/{}/join", room.id))
+                    .reply(&app)
+                    .await;
+                let waiting_code = tests::json(&resp)["voter_code"]
+                    .as_str()
+                    .unwrap()
+                    .to_owned();
+
+                warp::test::request()
+                    .method("PUT")
+                    .path(&format!("/api/v1/rooms/{}/start", room.id))
+                    .header("authorization", &admin)
+                    .reply(&app)
+                    .await;
+                warp::test::request()
+                    .method("POST")
+                    .path(&format!("/api/v1/voters/{voter_id}/vote"))
+                    .header("authorization", format!("Bearer {voter_code}"))
+                    .json(&json!({"options": [room.options[1], room.options[0]]}))
+                    .reply(&app)
+                    .await;
 
-        if admin_code != room.admin_code {
-            return Err(warp::reject::custom(NotRoomAdmin));
+                let mut sockets = Vec::new();
+                for (code, format) in [
+                    (voter_code, "html"),
+                    (&waiting_code, "html"),
+                    (&waiting_code, "json"),
+                ] {
+                    let mut socket = warp::test::ws()
+                        .path(&format!(
+                            "/rooms/{}/ws?token={code}&format={format}",
+                            room.id
+                        ))
+                        .handshake(app.clone())
+                        .await
+                        .unwrap();
+                    // A reply to an action means the socket is following the room's events.
+                    socket.send_text("{}").await;
+                    socket.recv().await.unwrap();
+                    sockets.push(socket);
+                }
+
+                let resp = warp::test::request()
+                    .method("PUT")
+                    .path(&format!("/api/v1/rooms/{}/end", room.id))
+                    .header("authorization", &admin)
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::OK);
+
+                // Only approved voters get the results along with the end of the vote.
+                let mut ended = Vec::new();
+                for socket in &mut sockets {
+                    ended.push(loop {
+                        let message = tokio::time::timeout(Duration::from_secs(5), socket.recv())
+                            .await
+                            .unwrap()
+                            .unwrap();
+                        let message: Value =
+                            serde_json::from_str(message.to_str().unwrap()).unwrap();
+                        if message["event"] == names::VOTE_ENDED_EVENT
+                            || message["type"] == "vote-ended"
+                        {
+                            break message;
+                        }
+                    });
+                }
+                let [approved, waiting, waiting_json] = <[Value; 3]>::try_from(ended).unwrap();
+                for html in [&approved, &waiting] {
+                    assert!(html["data"].as_str().unwrap().contains("VOTES HAVE ENDED."));
+                }
+                let pushed = |html: &Value| {
+                    html["data"]
+                        .as_str()
+                        .unwrap()
+                        .contains("data-testid=\"result-scores\"")
+                };
+                assert_eq!(
+                    pushed(&approved),
+                    visibility != Visibility::Admin,
+                    "{visibility:?}"
+                );
+                assert!(!pushed(&waiting), "{visibility:?}");
+                assert!(waiting_json.get("results").is_none(), "{visibility:?}");
+
+                let results = names::results_url(room.id);
+                for (cookie, allowed) in [
+                    (
+                        format!("{}={voter_code}", names::VOTER_COOKIE_NAME),
+                        visibility != Visibility::Admin,
+                    ),
+                    (
+                        format!("{}={waiting_code}", names::VOTER_COOKIE_NAME),
+                        visibility == Visibility::Public,
+                    ),
+                    (String::new(), visibility == Visibility::Public),
+                ] {
+                    let resp = warp::test::request()
+                        .path(&results)
+                        .header("cookie", &cookie)
+                        .reply(&app)
+                        .await;
+                    assert_eq!(
+                        resp.status() == StatusCode::OK,
+                        allowed,
+                        "{visibility:?} {cookie}"
+                    );
+                    // Labels can't break out of the chart's script.
+                    assert!(!tests::text(&resp).contains("<script>alert(1)"));
+                }
+            }
         }
 
-        sqlx::query!(
-            r#"UPDATE voters SET approved = true WHERE id = ?1"#,
-            voter_id
-        )
-        .execute(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while approving voter: {e}");
+        #[tokio::test]
+        async fn imported_files_become_ended_rooms() {
+            let app = tests::app(tests::store().await, Broadcasters::new());
+            let blt =
+                "3 1\n2 1 2 3 0\n1 2 1 0\n1 3 0\n0\n\"Pasta\"\n\"Pizza\"\n\"Tacos\"\n\"Lunch\"\n";
+            let import = |body: Value| {
+                let app = app.clone();
+                async move {
+                    warp::test::request()
+                        .method("POST")
+                        .path("/api/v1/rooms/import")
+                        .json(&body)
+                        .reply(&app)
+                        .await
+                }
+            };
+
+            // Ballots follow the same rules as ballots cast in the room.
+            for (partial, max_ranks, lines) in [
+                (
+                    "",
+                    "",
+                    json!([
+                        {"line": 3, "error": "UNKNOWN_OPTIONS"},
+                        {"line": 4, "error": "UNKNOWN_OPTIONS"},
+                    ]),
+                ),
+                ("on", "2", json!([{"line": 2, "error": "TOO_MANY_RANKS"}])),
+            ] {
+                let body = json!({"file": blt, "partial": partial, "max_ranks": max_ranks});
+                let resp = import(body).await;
+                assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+                assert_eq!(
+                    tests::json(&resp),
+                    json!({"error": "INVALID_BALLOTS", "lines": lines})
+                );
+            }
+
+            let resp = import(json!({"file": blt, "partial": "on"})).await;
+            assert_eq!(resp.status(), StatusCode::CREATED);
+            let room = tests::json(&resp);
+            let resp = warp::test::request()
+                .path(&format!("/api/v1/rooms/{}/results", room["id"]))
+                .header(
+                    "authorization",
+                    format!("Bearer {}", room["admin_code"].as_str().unwrap()),
+                )
+                .reply(&app)
+                .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let results = tests::json(&resp);
+            assert_eq!(results["room_name"], "Lunch");
+            assert_eq!(results["voters"], 4);
+            assert_eq!(results["scores"].as_array().unwrap().len(), 3);
+
+            // The form shows line errors next to itself, and the results once imported.
+            let csv = |file: &str| {
+                let app = app.clone();
+                let body = json!({"name": "Dinner", "format": "csv", "file": file});
+                async move {
+                    warp::test::request()
+                        .method("POST")
+                        .path(&names::import_url())
+                        .json(&body)
+                        .reply(&app)
+                        .await
+                }
+            };
+            let resp = csv("Pasta,Pizza\n1,2\n1,\n").await;
+            assert_eq!(resp.headers()["hx-retarget"], "#import-errors");
+            assert!(tests::text(&resp).contains("LINE 3: UNKNOWN_OPTIONS"));
+
+            let resp = csv("Pasta,Pizza\n1,2\n2,1\n1,2\n").await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert!(tests::text(&resp).contains("Dinner"));
+            let cookie = resp.headers()["set-cookie"].to_str().unwrap();
+            let admin_cookie = cookie.split(';').next().unwrap().to_owned();
+            let results = resp.headers()["hx-replace-url"]
+                .to_str()
+                .unwrap()
+                .to_owned();
+            let resp = warp::test::request()
+                .path(&results)
+                .header("cookie", admin_cookie)
+                .reply(&app)
+                .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert!(tests::text(&resp).contains("Dinner"));
+        }
+    }
+}
+
+mod voters {
+    use maud::{html, Markup};
+    use serde::{Deserialize, Serialize};
+    use utoipa::ToSchema;
+    use warp::{http::Uri, Filter, Reply};
+
+    use crate::{
+        events::{Broadcasters, RoomEvents},
+        names,
+        rejections::{
+            EmptyBallot, InternalServerError, InvalidScore, NotRoomAdmin, NotVoter, TooManyRanks,
+            UnknownOptions, VoterNotFound,
+        },
+        rooms::{self, Visibility},
+        store::{self, BallotEntry, Db, RoomOption},
+        tally::{self, Ballot, BallotType, Ratings},
+        utils, views, with_state,
+    };
+
+    #[derive(Deserialize, ToSchema)]
+    pub struct VoteBody {
+        /// The IDs of the options, best first.
+        #[serde(default)]
+        options: Ranking,
+        /// Whether each option in a flat ranking is ranked equal to the one above it, as sent
+        /// by the voting form.
+        #[serde(default, deserialize_with = "utils::one_or_many")]
+        tied: Vec<String>,
+        /// The IDs of the options approved on an approval ballot.
+        #[serde(default, deserialize_with = "utils::numbers")]
+        approved: Vec<i64>,
+        /// The score of each option in `options`, on score and STAR ballots.
+        #[serde(default, deserialize_with = "utils::numbers")]
+        scores: Vec<u32>,
+    }
+
+    /// A ballot as sent by a voter, either as a strict order or as groups of equal options.
+    #[derive(Deserialize, ToSchema)]
+    #[serde(untagged)]
+    enum Ranking {
+        One(#[serde(deserialize_with = "utils::number")] i64),
+        Order(#[serde(deserialize_with = "utils::numbers")] Vec<i64>),
+        Groups(Vec<Vec<i64>>),
+    }
+
+    impl Default for Ranking {
+        fn default() -> Self {
+            Ranking::Order(Vec::new())
+        }
+    }
+
+    impl Ranking {
+        /// The options in the order they were sent, ignoring equal rankings.
+        fn order(self) -> Vec<i64> {
+            match self {
+                Ranking::One(option) => vec![option],
+                Ranking::Order(order) => order,
+                Ranking::Groups(groups) => groups.concat(),
+            }
+        }
+    }
+
+    impl VoteBody {
+        /// The ranking sent, as groups of equal option IDs, best first.
+        fn ballot(self) -> Vec<Vec<i64>> {
+            let mut ballot = match self.options {
+                Ranking::One(option) => vec![vec![option]],
+                Ranking::Groups(groups) => groups,
+                Ranking::Order(order) => {
+                    let mut groups = Vec::<Vec<i64>>::new();
+                    for (idx, option) in order.into_iter().enumerate() {
+                        let tied = self.tied.get(idx).is_some_and(|t| t == "true");
+                        match groups.last_mut() {
+                            Some(group) if tied => group.push(option),
+                            _ => groups.push(vec![option]),
+                        }
+                    }
+                    groups
+                }
+            };
+
+            ballot.retain(|group| !group.is_empty());
+            ballot
+        }
+    }
+
+    /// The names the tally knows the options of a room by, in ballot order. The tally tells
+    /// options apart by name, so options sharing a label are numbered, as in "Pizza (2)".
+    pub fn option_names(options: &[RoomOption]) -> Vec<(i64, String)> {
+        let mut names = Vec::<(i64, String)>::with_capacity(options.len());
+
+        for option in options {
+            let mut name = option.label.clone();
+            let mut n = 1;
+            while names.iter().any(|(_, other)| *other == name) {
+                n += 1;
+                name = format!("{} ({n})", option.label);
+            }
+
+            names.push((option.id, name));
+        }
+
+        names
+    }
+
+    /// Decodes the stored ballots of a room into rankings and, for rated ballots, ratings, naming
+    /// each option as in [`option_names`].
+    pub fn decode_votes(
+        ballot_type: BallotType,
+        names: &[(i64, String)],
+        ballots: Vec<Vec<BallotEntry>>,
+    ) -> (Vec<Ballot>, Vec<Ratings>) {
+        let name = |option_id: i64| {
+            names
+                .iter()
+                .find(|(id, _)| *id == option_id)
+                .map(|(_, name)| name.clone())
+        };
+
+        match ballot_type {
+            BallotType::Ranking => {
+                let ballots = ballots
+                    .into_iter()
+                    .map(|entries| {
+                        let mut ballot = Ballot::new();
+                        let mut last_rank = None;
+                        for entry in entries {
+                            let Some(option) = name(entry.option_id) else {
+                                continue;
+                            };
+                            match ballot.last_mut() {
+                                Some(group) if last_rank == Some(entry.rank) => group.push(option),
+                                _ => ballot.push(vec![option]),
+                            }
+                            last_rank = Some(entry.rank);
+                        }
+                        ballot
+                    })
+                    .collect();
+
+                (ballots, Vec::new())
+            }
+            _ => {
+                let ratings = ballots
+                    .into_iter()
+                    .map(|entries| {
+                        entries
+                            .into_iter()
+                            .filter_map(|entry| {
+                                Some((name(entry.option_id)?, entry.score.unwrap_or(0) as u32))
+                            })
+                            .collect::<Ratings>()
+                    })
+                    .collect::<Vec<_>>();
+
+                (ratings.iter().map(tally::ranking).collect(), ratings)
+            }
+        }
+    }
+
+    /// What a room accepts as a ballot.
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct BallotRules {
+        pub options: Vec<RoomOption>,
+        /// Whether voters may leave options unranked.
+        pub partial: bool,
+        /// The most options a voter may rank, if limited.
+        pub max_ranks: Option<usize>,
+        pub ballot_type: BallotType,
+        /// The highest score on score and STAR ballots.
+        pub max_score: u32,
+    }
+
+    impl BallotRules {
+        fn has_option(&self, option_id: i64) -> bool {
+            self.options.iter().any(|option| option.id == option_id)
+        }
+
+        /// The IDs of every option, sorted.
+        fn option_ids(&self) -> Vec<i64> {
+            let mut ids = self.options.iter().map(|o| o.id).collect::<Vec<_>>();
+            ids.sort();
+            ids
+        }
+
+        /// Checks a ranking, given as groups of equal option IDs.
+        pub fn check(&self, ballot: &[Vec<i64>]) -> Result<(), warp::Rejection> {
+            let ballot = ballot.concat();
+
+            if !self.partial {
+                let mut sorted = ballot.to_vec();
+                sorted.sort();
+
+                if sorted != self.option_ids() {
+                    return Err(warp::reject::custom(UnknownOptions));
+                }
+
+                return Ok(());
+            }
+
+            if ballot.is_empty() {
+                return Err(warp::reject::custom(EmptyBallot));
+            }
+
+            for (idx, choice) in ballot.iter().enumerate() {
+                if !self.has_option(*choice) || ballot[..idx].contains(choice) {
+                    return Err(warp::reject::custom(UnknownOptions));
+                }
+            }
+
+            if self.max_ranks.is_some_and(|max| ballot.len() > max) {
+                return Err(warp::reject::custom(TooManyRanks));
+            }
+
+            Ok(())
+        }
+
+        /// Checks that every option is rated exactly once and within the allowed scores.
+        pub fn check_ratings(&self, ratings: &[(i64, u32)]) -> Result<(), warp::Rejection> {
+            let max = match self.ballot_type {
+                BallotType::Approval => 1,
+                BallotType::MajorityJudgment => tally::GRADES.len() as u32 - 1,
+                _ => self.max_score,
+            };
+
+            let mut options = ratings.iter().map(|(o, _)| *o).collect::<Vec<_>>();
+            options.sort();
+
+            if options != self.option_ids() {
+                return Err(warp::reject::custom(UnknownOptions));
+            }
+
+            if ratings.iter().any(|(_, score)| *score > max) {
+                return Err(warp::reject::custom(InvalidScore));
+            }
+
+            Ok(())
+        }
+
+        /// Validates a vote against the room's ballot type, returning the entries to store.
+        fn accept(&self, body: VoteBody) -> Result<Vec<BallotEntry>, warp::Rejection> {
+            let ratings =
+                match self.ballot_type {
+                    BallotType::Ranking => {
+                        let ballot = body.ballot();
+                        self.check(&ballot)?;
+                        return Ok(ranked_entries(&ballot));
+                    }
+                    BallotType::Approval => {
+                        if body.approved.is_empty() {
+                            return Err(warp::reject::custom(EmptyBallot));
+                        }
+
+                        if body.approved.iter().enumerate().any(|(idx, o)| {
+                            !self.has_option(*o) || body.approved[..idx].contains(o)
+                        }) {
+                            return Err(warp::reject::custom(UnknownOptions));
+                        }
+
+                        self.options
+                            .iter()
+                            .map(|o| (o.id, body.approved.contains(&o.id) as u32))
+                            .collect::<Vec<_>>()
+                    }
+                    BallotType::Score | BallotType::Star | BallotType::MajorityJudgment => {
+                        let options = body.options.order();
+                        if options.len() != body.scores.len() {
+                            return Err(warp::reject::custom(InvalidScore));
+                        }
+
+                        options.into_iter().zip(body.scores).collect()
+                    }
+                };
+
+            self.check_ratings(&ratings)?;
+            Ok(rated_entries(&ratings))
+        }
+    }
+
+    /// The entries of a checked ranking, one rank per group of equal options.
+    pub fn ranked_entries(ballot: &[Vec<i64>]) -> Vec<BallotEntry> {
+        ballot
+            .iter()
+            .enumerate()
+            .flat_map(|(rank, group)| {
+                group.iter().map(move |option_id| BallotEntry {
+                    option_id: *option_id,
+                    rank: rank as i64,
+                    score: None,
+                })
+            })
+            .collect()
+    }
+
+    /// The entries of checked ratings, ranking options by score with equal scores ranked equal.
+    pub fn rated_entries(ratings: &[(i64, u32)]) -> Vec<BallotEntry> {
+        let mut scores = ratings.iter().map(|(_, score)| *score).collect::<Vec<_>>();
+        scores.sort_unstable_by(|a, b| b.cmp(a));
+        scores.dedup();
+
+        ratings
+            .iter()
+            .map(|(option_id, score)| BallotEntry {
+                option_id: *option_id,
+                rank: scores.iter().position(|s| s == score).unwrap_or_default() as i64,
+                score: Some(*score as i64),
+            })
+            .collect()
+    }
+
+    pub fn route(
+        store: Db,
+        broadcasters: Broadcasters,
+    ) -> impl warp::Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let get_voter = with_state(store.clone())
+            .and(warp::path!("voters" / i64))
+            .and(warp::get())
+            .and(warp::cookie::cookie(names::VOTER_COOKIE_NAME))
+            .and_then(get_voter)
+            .with(warp::trace::named("get_voter"));
+
+        let approve_voter = with_state(store.clone())
+            .and(with_state(broadcasters.clone()))
+            .and(warp::path!("voters" / i64 / "approve"))
+            .and(warp::put())
+            .and(warp::cookie::cookie(names::ROOM_ADMIN_COOKIE_NAME))
+            .and_then(approve_voter)
+            .with(warp::trace::named("approve_voter"));
+
+        let vote = with_state(store.clone())
+            .and(with_state(broadcasters.clone()))
+            .and(warp::path!("voters" / i64 / "vote"))
+            .and(warp::post())
+            .and(warp::cookie::cookie(names::VOTER_COOKIE_NAME))
+            .and(warp::body::json::<VoteBody>())
+            .and_then(vote)
+            .with(warp::trace::named("vote"));
+
+        get_voter.or(approve_voter).or(vote)
+    }
+
+    async fn get_voter(
+        store: Db,
+        voter_id: i64,
+        voter_code: String,
+    ) -> Result<warp::reply::Response, warp::Rejection> {
+        let voter = store.voter(voter_id).await.map_err(|e| {
+            tracing::error!("error while getting voter: {e}");
+            match e {
+                sqlx::Error::RowNotFound => warp::reject::custom(VoterNotFound),
+                _ => warp::reject::custom(InternalServerError),
+            }
+        })?;
+
+        if voter_code != voter.voter_code {
+            return Err(warp::reject::custom(NotVoter));
+        }
+
+        let room = store.room(voter.room_id).await.map_err(|e| {
+            tracing::error!("error while getting room: {e}");
+            warp::reject::custom(InternalServerError)
+        })?;
+
+        // Once the vote has ended, the voter page is its results when voters may see them.
+        let visibility = Visibility::from_code(room.visibility).unwrap_or_default();
+        if room.status == store::ENDED && voter.approved && visibility != Visibility::Admin {
+            let url = names::results_url(room.id).parse::<Uri>().unwrap();
+            return Ok(warp::redirect::see_other(url).into_response());
+        }
+
+        let room_name = room
+            .in_status(store::WAITING)
+            .map_err(|e| {
+                tracing::error!("error while getting room: {e}");
+                warp::reject::custom(InternalServerError)
+            })?
+            .name;
+
+        let voter_count = store.voter_count(voter.room_id).await.map_err(|e| {
+            tracing::error!("error while getting voter count: {e}");
             warp::reject::custom(InternalServerError)
         })?;
 
-        tokio::spawn(async move {
-            broadcasters
-                .send_event(room.id, RoomEvents::VoteStartable(room.id))
-                .await;
-            broadcasters
-                .send_event(room.id, RoomEvents::VoterApproved(voter_id))
-                .await;
-        });
+        Ok(views::page(
+            "Voter",
+            view(VoterPage {
+                id: voter_id,
+                room_id: voter.room_id,
+                room_name,
+                voter_count,
+                approved: voter.approved,
+            }),
+        )
+        .into_response())
+    }
+
+    pub struct VoterPage {
+        pub id: i64,
+        pub room_id: i64,
+        pub room_name: String,
+        pub voter_count: i32,
+        pub approved: bool,
+    }
+
+    pub fn view(voter: VoterPage) -> Markup {
+        let voter_count = utils::format_num(voter.voter_count);
+        let voter_label = utils::pluralize(voter.voter_count, "voter", "voters");
+
+        html! {
+            section."grid gap-lg w-800" hx-ext="sse" sse-connect=(names::room_listen_url(voter.room_id)) {
+                h1."text-lg" { (voter.room_name) }
+
+                section."two-cols" {
+                    div."card card--secondary stat" hx-swap="innerHTML" sse-swap=(names::VOTER_COUNT_EVENT) {
+                        p."stat__num" data-testid="voter-count" { (voter_count) }
+                        p."stat__desc" { (voter_label) " in room" }
+                    }
+
+                    div."card grid gap-lg" {
+                        h2."text-md" { "YOUR VOTER ID" }
+                        span."code" { (voter.id) }
+                        @if voter.approved {
+                            div."alert" { "VOTER HAS BEEN APPROVED." }
+                        } @else {
+                            div."alert" hx-swap="outerHTML" sse-swap=(names::voter_approved_event(voter.id)) {
+                                "WAITING TO BE APPROVED."
+                            }
+                        }
+                    }
+                }
+
+                div hx-swap="innerHTML" sse-swap=(names::VOTE_STARTED_EVENT) {
+                    div."alert" { "VOTES WILL START SHORTLY." }
+                }
+
+                div hx-swap="innerHTML" sse-swap=(names::LIVE_RESULTS_EVENT) { }
+
+                div hx-swap="innerHTML" sse-swap=(names::VOTE_ENDED_EVENT) { }
+            }
+        }
+    }
+
+    /// An option on the voting form. Cards that start out unranked only get their input
+    /// names once they are dragged into the ranking.
+    fn ballot_card(option: &RoomOption, ranked: bool) -> Markup {
+        html! {
+            div."card flex gap-sm" {
+                span."strech" { (option.label) }
+                button."button w-fit tie" type="button" { "=" }
+                @if ranked {
+                    input type="hidden" name="options" value=(option.id) {}
+                    input."tied" type="hidden" name="tied" value="false" {}
+                } @else {
+                    input type="hidden" data-name="options" value=(option.id) {}
+                    input."tied" type="hidden" data-name="tied" value="false" {}
+                }
+            }
+        }
+    }
+
+    pub fn ballot_form(voter_id: i64, rules: &BallotRules) -> Markup {
+        html! {
+            form."grid gap-md sortable" hx-ext="json-enc" hx-post=(names::vote_url(voter_id)) hx-swap="outerHTML" {
+                h2."text-lg" { "START VOTING" }
+
+                @if rules.ballot_type == BallotType::Approval {
+                    p."text-sm" { "(CHECK EVERY OPTION YOU APPROVE OF)" }
+
+                    div."grid gap-md" {
+                        @for option in &rules.options {
+                            label."card flex gap-sm" {
+                                input type="checkbox" name="approved" value=(option.id) {}
+                                span."strech" { (option.label) }
+                            }
+                        }
+                    }
+                } @else if rules.ballot_type == BallotType::MajorityJudgment {
+                    p."text-sm" { "(GIVE EACH OPTION A GRADE)" }
+
+                    div."grid gap-md" {
+                        @for option in &rules.options {
+                            div."card flex gap-sm" {
+                                span."strech" { (option.label) }
+                                input type="hidden" name="options" value=(option.id) {}
+                                select."input-text w-fit" name="scores" required="true" {
+                                    option value="" disabled selected { "GRADE" }
+                                    @for (grade, label) in tally::GRADES.iter().enumerate().rev() {
+                                        option value=(grade) { (label) }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                } @else if rules.ballot_type != BallotType::Ranking {
+                    p."text-sm" {
+                        "(SCORE EACH OPTION FROM 0 TO " (rules.max_score) ", HIGHER IS BETTER"
+                        @if rules.ballot_type == BallotType::Star { ". THE TWO HIGHEST SCORING OPTIONS GO TO AN AUTOMATIC RUNOFF" }
+                        ")"
+                    }
+
+                    div."grid gap-md" {
+                        @for option in &rules.options {
+                            div."card flex gap-sm" {
+                                span."strech" { (option.label) }
+                                input type="hidden" name="options" value=(option.id) {}
+                                select."input-text w-fit" name="scores" {
+                                    @for score in 0..=rules.max_score {
+                                        option value=(score) { (score) }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                } @else if rules.partial {
+                    p."text-sm" {
+                        "(DRAG THE OPTIONS YOU WANT TO RANK INTO YOUR RANKING, BEST FIRST"
+                        @if let Some(max) = rules.max_ranks { ". YOU CAN RANK UP TO " (max) " " (utils::pluralize(max as i32, "OPTION", "OPTIONS")) }
+                        ")"
+                    }
+
+                    h3."text-sm" { "YOUR RANKING" }
+                    div."grid gap-md sortable ballot" data-group="ballot" data-ranked="true" data-max=[rules.max_ranks] {}
+
+                    h3."text-sm" { "UNRANKED" }
+                    div."grid gap-md sortable ballot" data-group="ballot" {
+                        @for option in &rules.options {
+                            (ballot_card(option, false))
+                        }
+                    }
+                } @else {
+                    p."text-sm" { "(REORDER THE OPTIONS BY DRAGGING AND DROPPING THEM)" }
+
+                    div."grid gap-md sortable" {
+                        @for option in &rules.options {
+                            (ballot_card(option, true))
+                        }
+                    }
+                }
+
+                @if rules.ballot_type == BallotType::Ranking {
+                    p."text-sm" { "(PRESS = TO RANK AN OPTION EQUAL TO THE ONE ABOVE IT)" }
+                }
+
+                button."button align-left" type="submit" { "SUBMIT VOTE" }
+            }
+        }
+    }
+
+    async fn approve_voter(
+        store: Db,
+        broadcasters: Broadcasters,
+        voter_id: i64,
+        admin_code: String,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        approve(store, broadcasters, voter_id, admin_code).await?;
+
+        Ok(html! {
+            button."button w-fit" disabled { "APPROVED" }
+        })
+    }
+
+    /// Approves a voter on behalf of the admin of their room.
+    pub async fn approve(
+        store: Db,
+        broadcasters: Broadcasters,
+        voter_id: i64,
+        admin_code: String,
+    ) -> Result<(), warp::Rejection> {
+        let room = async {
+            let voter = store.voter(voter_id).await?;
+            store.room(voter.room_id).await
+        }
+        .await
+        .map_err(|e| {
+            tracing::error!("error while getting voter: {e}");
+            warp::reject::custom(InternalServerError)
+        })?;
+
+        if admin_code != room.admin_code {
+            return Err(warp::reject::custom(NotRoomAdmin));
+        }
+
+        store.approve_voter(voter_id).await.map_err(|e| {
+            tracing::error!("error while approving voter: {e}");
+            warp::reject::custom(InternalServerError)
+        })?;
+
+        tokio::spawn(async move {
+            broadcasters
+                .send_event(room.id, RoomEvents::VoteStartable(room.id))
+                .await;
+            broadcasters
+                .send_event(room.id, RoomEvents::VoterApproved(voter_id))
+                .await;
+        });
+
+        Ok(())
+    }
+
+    async fn vote(
+        store: Db,
+        broadcasters: Broadcasters,
+        voter_id: i64,
+        voter_code: String,
+        body: VoteBody,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        cast_vote(store, broadcasters, voter_id, voter_code, body).await?;
+
+        Ok(html! {
+            h2."text-md" { "THANKS FOR VOTING!" }
+        })
+    }
+
+    /// Validates and records the ballot of an approved voter.
+    pub async fn cast_vote(
+        store: Db,
+        broadcasters: Broadcasters,
+        voter_id: i64,
+        voter_code: String,
+        body: VoteBody,
+    ) -> Result<(), warp::Rejection> {
+        let voter = store.voter(voter_id).await.map_err(|e| {
+            tracing::error!("error while getting voter: {e}");
+            warp::reject::custom(InternalServerError)
+        })?;
+
+        if voter_code != voter.voter_code {
+            return Err(warp::reject::custom(NotVoter));
+        }
+
+        let room = store
+            .room(voter.room_id)
+            .await
+            .and_then(|room| room.in_status(store::STARTED))
+            .map_err(|e| {
+                tracing::error!("error while getting room: {e}");
+                warp::reject::custom(InternalServerError)
+            })?;
+
+        let rules = BallotRules {
+            options: rooms::room_options(&store, voter.room_id).await?,
+            partial: room.partial,
+            max_ranks: room.max_ranks.map(|n| n as usize),
+            ballot_type: BallotType::from_code(room.ballot_type).unwrap_or_default(),
+            max_score: room.max_score as u32,
+        };
+        let entries = rules.accept(body)?;
+
+        if let Err(e) = store.record_vote(voter_id, entries).await {
+            tracing::error!("error while storing vote options: {e}");
+        }
+
+        let visibility = Visibility::from_code(room.visibility).unwrap_or_default();
+        let live = room.live;
+        tokio::spawn(async move {
+            broadcasters
+                .send_event(voter.room_id, RoomEvents::NewVote(voter_id))
+                .await;
+
+            if live {
+                if let Ok(page) = rooms::tally_votes(&store, room).await {
+                    // Only rooms whose results are public show voters the standings mid-vote.
+                    let shown = visibility == Visibility::Public;
+                    broadcasters
+                        .send_event(
+                            voter.room_id,
+                            RoomEvents::LiveResults(Box::new(page), shown),
+                        )
+                        .await;
+                }
+            }
+
+            if let Ok(votes) = store.vote_count(voter.room_id).await {
+                broadcasters
+                    .send_event(voter.room_id, RoomEvents::NewVoteCount(votes))
+                    .await;
+
+                broadcasters
+                    .send_event(voter.room_id, RoomEvents::VoteEndable(voter.room_id))
+                    .await;
+            }
+        });
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::time::Duration;
+
+        use serde_json::{json, Value};
+
+        use super::{decode_votes, option_names, ranked_entries, BallotRules, VoteBody};
+        use crate::{
+            events::Broadcasters, names, rejections, rooms::Visibility, store::RoomOption,
+            tally::BallotType, tests,
+        };
+
+        fn rules(ballot_type: BallotType, partial: bool, max_ranks: Option<usize>) -> BallotRules {
+            BallotRules {
+                options: (1..=3)
+                    .map(|id| RoomOption {
+                        id,
+                        label: format!("option {id}"),
+                    })
+                    .collect(),
+                partial,
+                max_ranks,
+                ballot_type,
+                max_score: 5,
+            }
+        }
+
+        /// Opens a room's WebSocket with a code, once it follows the room's events.
+        async fn socket<F>(app: &F, room_id: i64, query: &str) -> warp::test::WsClient
+        where
+            F: warp::Filter + Clone + Send + Sync + 'static,
+            F::Extract: warp::Reply,
+        {
+            let mut socket = warp::test::ws()
+                .path(&format!("/rooms/{room_id}/ws?{query}"))
+                .handshake(app.clone())
+                .await
+                .unwrap();
+            // A reply to an action means the socket is following the room's events.
+            socket.send_text("{}").await;
+            socket.recv().await.unwrap();
+            socket
+        }
+
+        /// The messages of a WebSocket up to and including the first that is `done`.
+        async fn read_until(
+            socket: &mut warp::test::WsClient,
+            done: impl Fn(&Value) -> bool,
+        ) -> Vec<Value> {
+            let mut messages = Vec::new();
+            loop {
+                let message = tokio::time::timeout(Duration::from_secs(5), socket.recv())
+                    .await
+                    .unwrap()
+                    .unwrap();
+                let message: Value = serde_json::from_str(message.to_str().unwrap()).unwrap();
+                let last = done(&message);
+                messages.push(message);
+                if last {
+                    return messages;
+                }
+            }
+        }
+
+        #[tokio::test]
+        async fn live_rooms_push_the_standings_after_each_ballot() {
+            for (live, visibility) in [
+                (true, Visibility::Public),
+                (true, Visibility::Voters),
+                (true, Visibility::Admin),
+                (false, Visibility::Public),
+            ] {
+                let app = tests::app(tests::store().await, Broadcasters::new());
+                let body = json!({
+                    "name": "Lunch",
+                    "options": ["Pasta", "Pizza"],
+                    "visibility": visibility.value(),
+                    "live": if live { "on" } else { "" },
+                });
+                let room = tests::room(&app, body, 1).await;
+                let (voter_id, voter_code) = &room.voters[0];
+                let admin = format!("Bearer {}", room.admin_code);
+
+                warp::test::request()
+                    .method("PUT")
+                    .path(&format!("/api/v1/rooms/{}/start", room.id))
+                    .header("authorization", &admin)
+                    .reply(&app)
+                    .await;
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&format!("/api/v1/rooms/{}/join", room.id))
+                    .reply(&app)
+                    .await;
+                let waiting_code = tests::json(&resp)["voter_code"]
+                    .as_str()
+                    .unwrap()
+                    .to_owned();
+
+                let admin_code = &room.admin_code;
+                let mut admin_socket =
+                    socket(&app, room.id, &format!("format=json&token={admin_code}")).await;
+                let mut voter_socket = socket(&app, room.id, &format!("token={voter_code}")).await;
+                let mut waiting_socket =
+                    socket(&app, room.id, &format!("token={waiting_code}")).await;
+
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&format!("/api/v1/voters/{voter_id}/vote"))
+                    .header("authorization", format!("Bearer {voter_code}"))
+                    .json(&json!({"options": [room.options[1], room.options[0]]}))
+                    .reply(&app)
+                    .await;
+                assert!(resp.status().is_success());
+
+                // The standings are counted again before the new vote count is sent.
+                let events = read_until(&mut admin_socket, |m| m["type"] == "vote-count").await;
+                let standings = events.iter().find(|m| m["type"] == "live-results");
+                assert_eq!(standings.is_some(), live, "{visibility:?}");
+                if let Some(standings) = standings {
+                    assert_eq!(standings["results"]["voters"], 1);
+                    assert_eq!(standings["results"]["scores"][0]["option"], "Pizza");
+                }
+
+                warp::test::request()
+                    .method("PUT")
+                    .path(&format!("/api/v1/rooms/{}/end", room.id))
+                    .header("authorization", &admin)
+                    .reply(&app)
+                    .await;
+                // Voters only see the standings of rooms whose results are public, and only once
+                // they are approved.
+                let events =
+                    read_until(&mut voter_socket, |m| m["event"] == names::VOTE_ENDED_EVENT).await;
+                let standings = events
+                    .iter()
+                    .find(|m| m["event"] == names::LIVE_RESULTS_EVENT);
+                assert_eq!(
+                    standings.is_some(),
+                    live && visibility == Visibility::Public,
+                    "{visibility:?}"
+                );
+                if let Some(standings) = standings {
+                    assert!(standings["data"].as_str().unwrap().contains("LIVE RESULTS"));
+                }
+                let events = read_until(&mut waiting_socket, |m| {
+                    m["event"] == names::VOTE_ENDED_EVENT
+                })
+                .await;
+                assert!(!events
+                    .iter()
+                    .any(|m| m["event"] == names::LIVE_RESULTS_EVENT));
+            }
+        }
+
+        /// The error code a ballot is refused with, if it is.
+        fn code<T>(result: Result<T, warp::Rejection>) -> Option<&'static str> {
+            result.err().map(|e| rejections::reason(&e).1)
+        }
+
+        #[test]
+        fn full_ballots_rank_every_option() {
+            let rules = rules(BallotType::Ranking, false, None);
+
+            assert_eq!(code(rules.check(&[vec![2], vec![1], vec![3]])), None);
+            for ballot in [
+                vec![vec![2], vec![1]],
+                vec![vec![2], vec![1], vec![1]],
+                vec![vec![2], vec![1], vec![3], vec![4]],
+                vec![],
+            ] {
+                assert_eq!(code(rules.check(&ballot)), Some("UNKNOWN_OPTIONS"));
+            }
+        }
+
+        #[test]
+        fn partial_ballots_rank_up_to_the_limit() {
+            let unlimited = rules(BallotType::Ranking, true, None);
+            assert_eq!(code(unlimited.check(&[vec![3]])), None);
+            assert_eq!(code(unlimited.check(&[vec![3], vec![1], vec![2]])), None);
+
+            let rules = rules(BallotType::Ranking, true, Some(2));
+            assert_eq!(code(rules.check(&[vec![3], vec![1]])), None);
+            assert_eq!(code(rules.check(&[])), Some("EMPTY_BALLOT"));
+            assert_eq!(
+                code(rules.check(&[vec![3], vec![1], vec![2]])),
+                Some("TOO_MANY_RANKS")
+            );
+            assert_eq!(
+                code(rules.check(&[vec![3], vec![3]])),
+                Some("UNKNOWN_OPTIONS")
+            );
+            assert_eq!(code(rules.check(&[vec![9]])), Some("UNKNOWN_OPTIONS"));
+        }
+
+        #[test]
+        fn equal_rankings_are_grouped() {
+            // The voting form sends a flat order with a flag on options tied to the one above.
+            let body = serde_json::from_str::<VoteBody>(
+                r#"{"options": ["2", "1", "3"], "tied": ["false", "true", "false"]}"#,
+            )
+            .unwrap();
+            assert_eq!(body.ballot(), [vec![2, 1], vec![3]]);
+
+            let body = serde_json::from_str::<VoteBody>(r#"{"options": [[3], [], [1, 2]]}"#);
+            assert_eq!(body.unwrap().ballot(), [vec![3], vec![1, 2]]);
+
+            let body = serde_json::from_str::<VoteBody>(r#"{"options": "2"}"#);
+            assert_eq!(body.unwrap().ballot(), [vec![2]]);
+
+            // An option can't be ranked twice, even in different groups.
+            let rules = rules(BallotType::Ranking, true, None);
+            assert_eq!(code(rules.check(&[vec![2, 1], vec![3]])), None);
+            assert_eq!(
+                code(rules.check(&[vec![2, 1], vec![1]])),
+                Some("UNKNOWN_OPTIONS")
+            );
+        }
+
+        #[test]
+        fn equal_rankings_are_stored_with_one_rank() {
+            let entries = ranked_entries(&[vec![2, 1], vec![3]]);
+            let ranks = entries
+                .iter()
+                .map(|entry| (entry.option_id, entry.rank))
+                .collect::<Vec<_>>();
+            assert_eq!(ranks, [(2, 0), (1, 0), (3, 1)]);
+
+            let names = option_names(&rules(BallotType::Ranking, true, None).options);
+            let (ballots, ratings) = decode_votes(BallotType::Ranking, &names, vec![entries]);
+            assert_eq!(
+                ballots,
+                [vec![
+                    vec!["option 2".to_owned(), "option 1".to_owned()],
+                    vec!["option 3".to_owned()]
+                ]]
+            );
+            assert!(ratings.is_empty());
+        }
+
+        /// A stored entry as `(option, rank, score)`.
+        type Entry = (i64, i64, Option<i64>);
+
+        /// The entries a vote would be stored as, or why it's refused.
+        fn accept(rules: &BallotRules, body: &str) -> Result<Vec<Entry>, &'static str> {
+            let body = serde_json::from_str::<VoteBody>(body).unwrap();
+            rules
+                .accept(body)
+                .map(|entries| {
+                    entries
+                        .into_iter()
+                        .map(|entry| (entry.option_id, entry.rank, entry.score))
+                        .collect()
+                })
+                .map_err(|e| rejections::reason(&e).1)
+        }
+
+        #[test]
+        fn approval_ballots_approve_known_options() {
+            let rules = rules(BallotType::Approval, false, None);
+
+            assert_eq!(
+                accept(&rules, r#"{"approved": ["3", "1"]}"#),
+                Ok(vec![(1, 0, Some(1)), (2, 1, Some(0)), (3, 0, Some(1))])
+            );
+            assert_eq!(accept(&rules, r#"{"approved": []}"#), Err("EMPTY_BALLOT"));
+            assert_eq!(accept(&rules, r#"{}"#), Err("EMPTY_BALLOT"));
+            assert_eq!(
+                accept(&rules, r#"{"approved": [1, 1]}"#),
+                Err("UNKNOWN_OPTIONS")
+            );
+            assert_eq!(
+                accept(&rules, r#"{"approved": [4]}"#),
+                Err("UNKNOWN_OPTIONS")
+            );
+        }
+
+        #[test]
+        fn score_ballots_rate_every_option_within_bounds() {
+            for ballot_type in [BallotType::Score, BallotType::Star] {
+                let rules = rules(ballot_type, false, None);
+
+                assert_eq!(
+                    accept(&rules, r#"{"options": [2, 1, 3], "scores": [5, 0, 5]}"#),
+                    Ok(vec![(2, 0, Some(5)), (1, 1, Some(0)), (3, 0, Some(5))])
+                );
+                assert_eq!(
+                    accept(&rules, r#"{"options": [1, 2, 3], "scores": [6, 0, 0]}"#),
+                    Err("INVALID_SCORE")
+                );
+                assert_eq!(
+                    accept(&rules, r#"{"options": [1, 2, 3], "scores": [1, 2]}"#),
+                    Err("INVALID_SCORE")
+                );
+                assert_eq!(
+                    accept(&rules, r#"{"options": [1, 2], "scores": [1, 2]}"#),
+                    Err("UNKNOWN_OPTIONS")
+                );
+                assert_eq!(
+                    accept(&rules, r#"{"options": [1, 1, 2], "scores": [1, 2, 3]}"#),
+                    Err("UNKNOWN_OPTIONS")
+                );
+            }
+
+            // Approvals are scores of at most 1, whatever the room's maximum score.
+            let approval = rules(BallotType::Approval, false, None);
+            assert_eq!(
+                code(approval.check_ratings(&[(1, 1), (2, 0), (3, 1)])),
+                None
+            );
+            assert_eq!(
+                code(approval.check_ratings(&[(1, 2), (2, 0), (3, 1)])),
+                Some("INVALID_SCORE")
+            );
+        }
+
+        #[test]
+        fn majority_judgment_ballots_grade_every_option() {
+            // Grades index `GRADES`, whatever the room's maximum score.
+            let mut rules = rules(BallotType::MajorityJudgment, false, None);
+            rules.max_score = 10;
+
+            assert_eq!(
+                accept(&rules, r#"{"options": [1, 2, 3], "scores": [5, 0, 3]}"#),
+                Ok(vec![(1, 0, Some(5)), (2, 2, Some(0)), (3, 1, Some(3))])
+            );
+            assert_eq!(
+                accept(&rules, r#"{"options": [1, 2, 3], "scores": [6, 0, 3]}"#),
+                Err("INVALID_SCORE")
+            );
+            assert_eq!(
+                accept(&rules, r#"{"options": [1, 3], "scores": [5, 3]}"#),
+                Err("UNKNOWN_OPTIONS")
+            );
+        }
+    }
+}
+
+mod api {
+    use serde::Serialize;
+    use utoipa::ToSchema;
+    use utoipa::{
+        openapi::{
+            security::{Http, HttpAuthScheme, SecurityScheme},
+            HttpMethod,
+        },
+        Modify, OpenApi,
+    };
+    use warp::{filters::BoxedFilter, http::StatusCode, Filter, Reply};
+
+    use crate::{
+        events::Broadcasters,
+        files::{self, LineError},
+        rejections::{self, ApiError, InvalidBallots, MissingToken},
+        rooms::{self, CreateRoomBody, ImportBody, Imported, NewRoom, NewVoter},
+        store::Db,
+        voters::{self, VoteBody},
+        voting::{ResultPage, VoteAdminPage},
+        with_state,
+    };
+
+    /// The OpenAPI document of the API, served at `/api/openapi.json`.
+    #[derive(OpenApi)]
+    #[openapi(
+        info(title = "ordo", description = "Create rooms and run ranked votes."),
+        paths(
+            create_room,
+            import_room,
+            join_room,
+            approve_voter,
+            start_vote,
+            vote,
+            end_vote,
+            results
+        ),
+        components(schemas(ApiError)),
+        modifiers(&BearerToken)
+    )]
+    pub struct ApiDoc;
+
+    struct BearerToken;
+
+    impl Modify for BearerToken {
+        fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+            if let Some(components) = openapi.components.as_mut() {
+                components.add_security_scheme(
+                    "token",
+                    SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+                );
+            }
+        }
+    }
+
+    /// A route of the API, with the path and methods its `#[utoipa::path]` documents.
+    struct Endpoint {
+        /// Read by the tests, which check them against the served routes and [`ApiDoc`].
+        #[cfg_attr(not(test), allow(dead_code))]
+        path: String,
+        #[cfg_attr(not(test), allow(dead_code))]
+        methods: Vec<HttpMethod>,
+        filter: BoxedFilter<(Box<dyn Reply>,)>,
+    }
+
+    impl Endpoint {
+        /// The route served by `filter` and documented by `P`, the type `#[utoipa::path]`
+        /// generates for its handler.
+        fn new<P: utoipa::Path>(
+            filter: impl Filter<Extract = (impl Reply + 'static,), Error = warp::Rejection>
+                + Clone
+                + Send
+                + Sync
+                + 'static,
+        ) -> Self {
+            Self {
+                path: P::path(),
+                methods: P::methods(),
+                filter: filter
+                    .map(|reply| Box::new(reply) as Box<dyn Reply>)
+                    .boxed(),
+            }
+        }
+    }
+
+    /// A JSON API under `/api/v1` for scripts and other clients, with the same rules as the
+    /// HTML endpoints. Admin and voter codes are sent as `Authorization: Bearer <code>`
+    /// instead of cookies, and errors are replied as `{"error": "<CODE>"}`.
+    pub fn route(
+        store: Db,
+        broadcasters: Broadcasters,
+        max_lifetime: i64,
+    ) -> impl warp::Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let openapi = warp::path!("api" / "openapi.json")
+            .and(warp::get())
+            .map(|| warp::reply::json(&ApiDoc::openapi()))
+            .with(warp::trace::named("api_openapi"));
+
+        let none = warp::any()
+            .and_then(|| async { Err::<Box<dyn Reply>, _>(warp::reject::not_found()) })
+            .boxed();
+        let routes = endpoints(store, broadcasters, max_lifetime)
+            .into_iter()
+            .fold(none, |routes, endpoint| {
+                routes.or(endpoint.filter).unify().boxed()
+            })
+            .recover(rejections::handle_api_rejection);
+
+        openapi.or(warp::path!("api" / "v1" / ..).and(routes))
+    }
+
+    /// Every route of the API, relative to `/api/v1`.
+    fn endpoints(store: Db, broadcasters: Broadcasters, max_lifetime: i64) -> Vec<Endpoint> {
+        let create_room = with_state(store.clone())
+            .and(with_state(max_lifetime))
+            .and(warp::path!("rooms"))
+            .and(warp::post())
+            .and(warp::body::json::<CreateRoomBody>())
+            .and_then(create_room)
+            .with(warp::trace::named("api_create_room"));
+
+        let import_room = with_state(store.clone())
+            .and(with_state(max_lifetime))
+            .and(warp::path!("rooms" / "import"))
+            .and(warp::post())
+            .and(warp::body::content_length_limit(files::MAX_FILE_SIZE))
+            .and(warp::body::json::<ImportBody>())
+            .and_then(import_room)
+            .with(warp::trace::named("api_import_room"));
+
+        let join_room = with_state(store.clone())
+            .and(with_state(broadcasters.clone()))
+            .and(warp::path!("rooms" / i64 / "join"))
+            .and(warp::post())
+            .and_then(join_room)
+            .with(warp::trace::named("api_join_room"));
+
+        let approve_voter = with_state(store.clone())
+            .and(with_state(broadcasters.clone()))
+            .and(warp::path!("voters" / i64 / "approve"))
+            .and(warp::put())
+            .and(token())
+            .and_then(approve_voter)
+            .with(warp::trace::named("api_approve_voter"));
+
+        let start_vote = with_state(store.clone())
+            .and(with_state(broadcasters.clone()))
+            .and(warp::path!("rooms" / i64 / "start"))
+            .and(warp::put())
+            .and(token())
+            .and_then(start_vote)
+            .with(warp::trace::named("api_start_vote"));
+
+        let vote = with_state(store.clone())
+            .and(with_state(broadcasters.clone()))
+            .and(warp::path!("voters" / i64 / "vote"))
+            .and(warp::post())
+            .and(token())
+            .and(warp::body::json::<VoteBody>())
+            .and_then(vote)
+            .with(warp::trace::named("api_vote"));
+
+        let end_vote = with_state(store.clone())
+            .and(with_state(broadcasters))
+            .and(warp::path!("rooms" / i64 / "end"))
+            .and(warp::put())
+            .and(token())
+            .and_then(end_vote)
+            .with(warp::trace::named("api_end_vote"));
+
+        let results = with_state(store)
+            .and(warp::path!("rooms" / i64 / "results"))
+            .and(warp::get())
+            .and(optional_token())
+            .and_then(results)
+            .with(warp::trace::named("api_results"));
+
+        vec![
+            Endpoint::new::<__path_create_room>(create_room),
+            Endpoint::new::<__path_import_room>(import_room),
+            Endpoint::new::<__path_join_room>(join_room),
+            Endpoint::new::<__path_approve_voter>(approve_voter),
+            Endpoint::new::<__path_start_vote>(start_vote),
+            Endpoint::new::<__path_vote>(vote),
+            Endpoint::new::<__path_end_vote>(end_vote),
+            Endpoint::new::<__path_results>(results),
+        ]
+    }
+
+    /// The admin or voter code sent as a bearer token.
+    fn token() -> impl warp::Filter<Extract = (String,), Error = warp::Rejection> + Clone {
+        warp::header::optional::<String>("authorization").and_then(
+            |header: Option<String>| async move {
+                header
+                    .as_deref()
+                    .and_then(|h| h.strip_prefix("Bearer "))
+                    .map(|code| code.trim().to_owned())
+                    .filter(|code| !code.is_empty())
+                    .ok_or_else(|| warp::reject::custom(MissingToken))
+            },
+        )
+    }
+
+    /// Like [`token`], for requests that anyone can make.
+    fn optional_token(
+    ) -> impl warp::Filter<Extract = (Option<String>,), Error = warp::Rejection> + Clone {
+        warp::header::optional::<String>("authorization").map(|header: Option<String>| {
+            header
+                .as_deref()
+                .and_then(|h| h.strip_prefix("Bearer "))
+                .map(|code| code.trim().to_owned())
+                .filter(|code| !code.is_empty())
+        })
+    }
+
+    /// Creates a room.
+    #[utoipa::path(
+        post,
+        path = "/api/v1/rooms",
+        request_body = CreateRoomBody,
+        responses(
+            (status = 201, description = "The new room and its admin code", body = NewRoom),
+            (status = 400, description = "The room is invalid", body = ApiError),
+        )
+    )]
+    async fn create_room(
+        store: Db,
+        max_lifetime: i64,
+        mut body: CreateRoomBody,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let room = rooms::insert_room(store, max_lifetime, &mut body).await?;
+
+        Ok(warp::reply::with_status(
+            warp::reply::json(&room),
+            StatusCode::CREATED,
+        ))
+    }
+
+    /// The body of the reply when some ballots of an imported file are refused.
+    #[derive(Serialize, ToSchema)]
+    struct InvalidBallotsError {
+        /// Always `INVALID_BALLOTS`.
+        #[schema(value_type = String)]
+        error: &'static str,
+        lines: Vec<LineError>,
+    }
+
+    /// Creates an ended room from a BLT or CSV ballot file and counts its ballots. The results
+    /// are then read with the admin code as the token.
+    #[utoipa::path(
+        post,
+        path = "/api/v1/rooms/import",
+        request_body = ImportBody,
+        responses(
+            (status = 201, description = "The new room and its admin code", body = NewRoom),
+            (status = 400, description = "Some lines of the file are invalid", body = InvalidBallotsError),
+        )
+    )]
+    async fn import_room(
+        store: Db,
+        max_lifetime: i64,
+        body: ImportBody,
+    ) -> Result<warp::reply::Response, warp::Rejection> {
+        let reply = match rooms::import_room(store, max_lifetime, body).await? {
+            Imported::Room(room, _) => {
+                warp::reply::with_status(warp::reply::json(&room), StatusCode::CREATED)
+            }
+            Imported::Invalid(lines) => warp::reply::with_status(
+                warp::reply::json(&InvalidBallotsError {
+                    error: rejections::reason(&warp::reject::custom(InvalidBallots)).1,
+                    lines,
+                }),
+                StatusCode::BAD_REQUEST,
+            ),
+        };
+
+        Ok(reply.into_response())
+    }
+
+    /// Joins a room as a voter waiting to be approved.
+    #[utoipa::path(
+        post,
+        path = "/api/v1/rooms/{room_id}/join",
+        params(("room_id" = i64, Path)),
+        responses(
+            (status = 201, description = "The new voter and its voter code", body = NewVoter),
+            (status = 400, description = "The room does not exist", body = ApiError),
+        )
+    )]
+    async fn join_room(
+        store: Db,
+        broadcasters: Broadcasters,
+        room_id: i64,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let voter = rooms::add_voter(store, broadcasters, room_id).await?;
+
+        Ok(warp::reply::with_status(
+            warp::reply::json(&voter),
+            StatusCode::CREATED,
+        ))
+    }
+
+    /// Lets a voter take part in the vote.
+    #[utoipa::path(
+        put,
+        path = "/api/v1/voters/{voter_id}/approve",
+        params(("voter_id" = i64, Path)),
+        security(("token" = [])),
+        responses(
+            (status = 204, description = "The voter is approved"),
+            (status = 400, description = "The voter does not exist", body = ApiError),
+            (status = 401, description = "The token is not the room's admin code", body = ApiError),
+        )
+    )]
+    async fn approve_voter(
+        store: Db,
+        broadcasters: Broadcasters,
+        voter_id: i64,
+        admin_code: String,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        voters::approve(store, broadcasters, voter_id, admin_code).await?;
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    /// Starts the vote.
+    #[utoipa::path(
+        put,
+        path = "/api/v1/rooms/{room_id}/start",
+        params(("room_id" = i64, Path)),
+        security(("token" = [])),
+        responses(
+            (status = 200, description = "The approved voters", body = VoteAdminPage),
+            (status = 400, description = "The room does not exist or has started", body = ApiError),
+            (status = 401, description = "The token is not the room's admin code", body = ApiError),
+        )
+    )]
+    async fn start_vote(
+        store: Db,
+        broadcasters: Broadcasters,
+        room_id: i64,
+        admin_code: String,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let page = rooms::begin_vote(store, broadcasters, room_id, admin_code).await?;
+
+        Ok(warp::reply::json(&page))
+    }
+
+    /// Casts or replaces a voter's ballot.
+    #[utoipa::path(
+        post,
+        path = "/api/v1/voters/{voter_id}/vote",
+        params(("voter_id" = i64, Path)),
+        request_body = VoteBody,
+        security(("token" = [])),
+        responses(
+            (status = 204, description = "The ballot is recorded"),
+            (status = 400, description = "The ballot breaks the room's rules", body = ApiError),
+            (status = 401, description = "The token is not the voter's code", body = ApiError),
+        )
+    )]
+    async fn vote(
+        store: Db,
+        broadcasters: Broadcasters,
+        voter_id: i64,
+        voter_code: String,
+        body: VoteBody,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        voters::cast_vote(store, broadcasters, voter_id, voter_code, body).await?;
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    /// Ends the vote and counts it.
+    #[utoipa::path(
+        put,
+        path = "/api/v1/rooms/{room_id}/end",
+        params(("room_id" = i64, Path)),
+        security(("token" = [])),
+        responses(
+            (status = 200, description = "The results", body = ResultPage),
+            (status = 400, description = "The room does not exist or has not started", body = ApiError),
+            (status = 401, description = "The token is not the room's admin code", body = ApiError),
+        )
+    )]
+    async fn end_vote(
+        store: Db,
+        broadcasters: Broadcasters,
+        room_id: i64,
+        admin_code: String,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let page = rooms::finish_vote(store, broadcasters, room_id, admin_code).await?;
+
+        Ok(warp::reply::json(&page))
+    }
+
+    /// The results of an ended room, as they were counted when its vote ended. Voters can
+    /// send their code when the room shows them the results, and public results need no token.
+    #[utoipa::path(
+        get,
+        path = "/api/v1/rooms/{room_id}/results",
+        params(("room_id" = i64, Path)),
+        security((), ("token" = [])),
+        responses(
+            (status = 200, description = "The results", body = ResultPage),
+            (status = 400, description = "The room does not exist or has not ended", body = ApiError),
+            (status = 401, description = "The room doesn't show its results to the token's owner", body = ApiError),
+        )
+    )]
+    async fn results(
+        store: Db,
+        room_id: i64,
+        token: Option<String>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let page = rooms::visible_results(store, room_id, token.clone(), token).await?;
+
+        Ok(warp::reply::json(&page))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use serde_json::json;
+        use utoipa::{openapi::HttpMethod, OpenApi};
+        use warp::{http::StatusCode, reject::MethodNotAllowed};
+
+        use super::ApiDoc;
+        use crate::{events::Broadcasters, rejections, tests};
+
+        #[tokio::test]
+        async fn rooms_are_run_through_the_api() {
+            for store in tests::stores().await {
+                let app = tests::app(store, Broadcasters::new());
+
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/v1/rooms")
+                    .json(&json!({"name": "Lunch", "options": ["Pasta", "Pizza"]}))
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::CREATED);
+                let room = tests::json(&resp);
+                let room_id = room["id"].as_i64().unwrap();
+                let admin = format!("Bearer {}", room["admin_code"].as_str().unwrap());
+                let pasta = room["options"][0]["id"].as_i64().unwrap();
+                let pizza = room["options"][1]["id"].as_i64().unwrap();
+
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&format!("/api/v1/rooms/{room_id}/join"))
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::CREATED);
+                let voter = tests::json(&resp);
+                let voter_id = voter["id"].as_i64().unwrap();
+                let voter_code = format!("Bearer {}", voter["voter_code"].as_str().unwrap());
+
+                // Errors carry the same codes and statuses as the HTML endpoints.
+                let approve = format!("/api/v1/voters/{voter_id}/approve");
+                for (token, status, code) in [
+                    (None, StatusCode::UNAUTHORIZED, "MISSING_TOKEN"),
+                    (
+                        Some(&voter_code),
+                        StatusCode::UNAUTHORIZED,
+                        "NOT_ROOM_ADMIN",
+                    ),
+                ] {
+                    let mut req = warp::test::request().method("PUT").path(&approve);
+                    if let Some(token) = token {
+                        req = req.header("authorization", token);
+                    }
+                    let resp = req.reply(&app).await;
+                    assert_eq!(resp.status(), status);
+                    assert_eq!(tests::json(&resp), json!({"error": code}));
+                }
+
+                let resp = warp::test::request()
+                    .method("PUT")
+                    .path(&approve)
+                    .header("authorization", &admin)
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+                let resp = warp::test::request()
+                    .method("PUT")
+                    .path(&format!("/api/v1/rooms/{room_id}/start"))
+                    .header("authorization", &admin)
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::OK);
+                assert_eq!(tests::json(&resp)["approved_voters"][0]["id"], voter_id);
+
+                let vote = format!("/api/v1/voters/{voter_id}/vote");
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&vote)
+                    .header("authorization", &voter_code)
+                    .json(&json!({"options": [pizza, pasta + pizza]}))
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+                assert_eq!(tests::json(&resp), json!({"error": "UNKNOWN_OPTIONS"}));
+
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&vote)
+                    .header("authorization", &voter_code)
+                    .json(&json!({"options": [pizza, pasta]}))
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+                let resp = warp::test::request()
+                    .method("PUT")
+                    .path(&format!("/api/v1/rooms/{room_id}/end"))
+                    .header("authorization", &admin)
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::OK);
+                let ended = tests::json(&resp);
+                assert_eq!(ended["voters"], 1);
+                assert_eq!(ended["scores"][0]["option"], "Pizza");
+                assert_eq!(ended["scores"][0]["elected"], true);
+
+                // The results can be read again once counted.
+                let results = format!("/api/v1/rooms/{room_id}/results");
+                let resp = warp::test::request()
+                    .path(&results)
+                    .header("authorization", &admin)
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::OK);
+                assert_eq!(tests::json(&resp), ended);
+
+                let resp = warp::test::request().path(&results).reply(&app).await;
+                assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+                assert_eq!(tests::json(&resp), json!({"error": "NOT_ROOM_ADMIN"}));
+            }
+        }
+
+        /// Each endpoint serves the path and methods its schema documents, and [`ApiDoc`]
+        /// documents every endpoint and nothing else.
+        #[tokio::test]
+        async fn every_route_has_a_schema() {
+            let endpoints = super::endpoints(
+                tests::store().await,
+                Broadcasters::new(),
+                tests::MAX_LIFETIME,
+            );
+            let doc = ApiDoc::openapi();
+
+            for endpoint in &endpoints {
+                // Path parameters are all IDs.
+                let path = endpoint
+                    .path
+                    .strip_prefix("/api/v1")
+                    .unwrap()
+                    .split('/')
+                    .map(|segment| {
+                        if segment.starts_with('{') {
+                            "1"
+                        } else {
+                            segment
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/");
+
+                for method in &endpoint.methods {
+                    let name = serde_json::to_value(method).unwrap();
+                    let name = name.as_str().unwrap().to_uppercase();
+                    assert!(
+                        doc.paths
+                            .get_path_operation(&endpoint.path, method.clone())
+                            .is_some(),
+                        "no schema for {name} {}",
+                        endpoint.path
+                    );
+
+                    let reply = warp::test::request()
+                        .method(&name)
+                        .path(&path)
+                        .filter(&endpoint.filter)
+                        .await;
+                    if let Err(rejection) = reply {
+                        assert!(
+                            !rejection.is_not_found()
+                                && rejection.find::<MethodNotAllowed>().is_none(),
+                            "{name} {path} isn't served: {rejection:?}"
+                        );
+                    }
+                }
+            }
+
+            for (path, item) in &doc.paths.paths {
+                for (method, operation) in [
+                    (HttpMethod::Get, &item.get),
+                    (HttpMethod::Post, &item.post),
+                    (HttpMethod::Put, &item.put),
+                    (HttpMethod::Delete, &item.delete),
+                ] {
+                    let served = endpoints
+                        .iter()
+                        .any(|e| e.path == *path && e.methods.contains(&method));
+                    assert_eq!(operation.is_some(), served, "{path}");
+                }
+            }
+        }
+
+        #[tokio::test]
+        async fn the_served_document_describes_every_error_code() {
+            let app = tests::app(tests::store().await, Broadcasters::new());
+
+            let resp = warp::test::request()
+                .path("/api/openapi.json")
+                .reply(&app)
+                .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let doc = tests::json(&resp);
+            let schemas = &doc["components"]["schemas"];
+
+            let codes = schemas["ApiError"]["properties"]["error"]["enum"]
+                .as_array()
+                .unwrap();
+            assert!(rejections::error_codes().eq(codes.iter().map(|c| c.as_str().unwrap())));
+
+            // Every body type a route refers to is part of the document.
+            let doc = doc.to_string();
+            for (idx, _) in doc.match_indices("#/components/schemas/") {
+                let name = &doc[idx + "#/components/schemas/".len()..];
+                let name = &name[..name.find('"').unwrap()];
+                assert!(schemas.get(name).is_some(), "no schema named {name}");
+            }
+            for name in ["CreateRoomBody", "VoteBody", "ResultPage"] {
+                assert!(schemas.get(name).is_some(), "no schema named {name}");
+            }
+        }
+    }
+}
+
+mod files {
+    use std::{borrow::Cow, str::FromStr};
+
+    use serde::{Deserialize, Serialize};
+    use utoipa::ToSchema;
+    use warp::{
+        http::{
+            header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+            Response,
+        },
+        Filter,
+    };
+
+    use crate::{
+        names,
+        rejections::{
+            self, InternalServerError, InvalidLine, NotRoomAdmin, TooManyBallots, UnknownOptions,
+        },
+        rooms,
+        store::{self, Db},
+        tally::{Ballot, BallotType, Ratings},
+        voters,
+        voting::ResultPage,
+        with_state,
+    };
+
+    /// A file the admin of an ended room can download.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Export {
+        ResultsCsv,
+        ResultsJson,
+        BallotsCsv,
+        BallotsJson,
+        BallotsBlt,
+    }
+
+    impl Export {
+        pub const ALL: [Export; 5] = [
+            Export::ResultsCsv,
+            Export::ResultsJson,
+            Export::BallotsCsv,
+            Export::BallotsJson,
+            Export::BallotsBlt,
+        ];
+
+        pub fn file_name(self) -> &'static str {
+            match self {
+                Export::ResultsCsv => "results.csv",
+                Export::ResultsJson => "results.json",
+                Export::BallotsCsv => "ballots.csv",
+                Export::BallotsJson => "ballots.json",
+                Export::BallotsBlt => "ballots.blt",
+            }
+        }
+
+        pub fn label(self) -> &'static str {
+            match self {
+                Export::ResultsCsv => "RESULTS (CSV)",
+                Export::ResultsJson => "RESULTS (JSON)",
+                Export::BallotsCsv => "BALLOTS (CSV)",
+                Export::BallotsJson => "BALLOTS (JSON)",
+                Export::BallotsBlt => "BALLOTS (BLT)",
+            }
+        }
+
+        fn content_type(self) -> &'static str {
+            match self {
+                Export::ResultsCsv | Export::BallotsCsv => "text/csv; charset=utf-8",
+                Export::ResultsJson | Export::BallotsJson => "application/json",
+                Export::BallotsBlt => "text/plain; charset=utf-8",
+            }
+        }
+    }
+
+    impl FromStr for Export {
+        type Err = ();
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            Self::ALL
+                .into_iter()
+                .find(|export| export.file_name() == s)
+                .ok_or(())
+        }
+    }
+
+    /// The ballots of a room without their voters. They are sorted by what they say, so their
+    /// order can't be matched to the order voters joined or voted in.
+    #[derive(Serialize)]
+    pub struct AnonymousBallots {
+        pub room_name: String,
+        pub ballot_type: BallotType,
+        pub seats: i64,
+        /// The names of the options, made unique as on the results page.
+        pub options: Vec<String>,
+        /// Groups of equal options, best first. For rated ballots, the ranking implied by the
+        /// ratings.
+        pub ballots: Vec<Ballot>,
+        /// The score each ballot gave each option, in the same order as `ballots`. Empty unless
+        /// the room uses rated ballots.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        pub ratings: Vec<Ratings>,
+    }
+
+    pub fn route(
+        store: Db,
+    ) -> impl warp::Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        with_state(store)
+            .and(warp::path!("rooms" / i64 / "export" / Export))
+            .and(warp::get())
+            .and(warp::cookie::cookie(names::ROOM_ADMIN_COOKIE_NAME))
+            .and_then(export)
+            .with(warp::trace::named("export"))
+    }
+
+    async fn export(
+        store: Db,
+        room_id: i64,
+        export: Export,
+        admin_code: String,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let room = store
+            .room(room_id)
+            .await
+            .and_then(|room| room.in_status(store::ENDED))
+            .map_err(|e| {
+                tracing::error!("error while getting room: {e}");
+                match e {
+                    sqlx::Error::RowNotFound => warp::reject::custom(rejections::RoomNotFound),
+                    _ => warp::reject::custom(InternalServerError),
+                }
+            })?;
+
+        if admin_code != room.admin_code {
+            return Err(warp::reject::custom(NotRoomAdmin));
+        }
+
+        let body = match export {
+            Export::ResultsCsv | Export::ResultsJson => {
+                let page = rooms::count_votes(&store, room).await?;
+                match export {
+                    Export::ResultsCsv => results_csv(&page),
+                    _ => pretty_json(&page)?,
+                }
+            }
+            Export::BallotsCsv | Export::BallotsJson | Export::BallotsBlt => {
+                let ballots = anonymous_ballots(&store, room).await?;
+                match export {
+                    Export::BallotsCsv => ballots_csv(&ballots),
+                    Export::BallotsJson => pretty_json(&ballots)?,
+                    _ => ballots_blt(&ballots),
+                }
+            }
+        };
+
+        let resp = Response::builder()
+            .header(CONTENT_TYPE, export.content_type())
+            .header(
+                CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"ordo-{room_id}-{}\"",
+                    export.file_name()
+                ),
+            )
+            .body(body)
+            .map_err(|e| {
+                tracing::error!("error while building export response: {e}");
+                warp::reject::custom(InternalServerError)
+            })?;
+
+        Ok(resp)
+    }
+
+    /// Writes a JSON export, indented for people reading it.
+    fn pretty_json(value: &impl Serialize) -> Result<String, warp::Rejection> {
+        serde_json::to_string_pretty(value).map_err(|e| {
+            tracing::error!("error while writing export: {e}");
+            warp::reject::custom(InternalServerError)
+        })
+    }
+
+    async fn anonymous_ballots(
+        store: &Db,
+        room: store::Room,
+    ) -> Result<AnonymousBallots, warp::Rejection> {
+        let ballot_type = BallotType::from_code(room.ballot_type).unwrap_or_default();
+        let names = voters::option_names(&rooms::room_options(store, room.id).await?);
+        let stored = store.ballots(room.id).await.map_err(|e| {
+            tracing::error!("error while getting ballots: {e}");
+            warp::reject::custom(InternalServerError)
+        })?;
+        let (mut ballots, mut ratings) = voters::decode_votes(ballot_type, &names, stored);
+
+        if ratings.is_empty() {
+            ballots.sort();
+        } else {
+            let mut rated = ballots.into_iter().zip(ratings).collect::<Vec<_>>();
+            rated.sort_by(|(_, a), (_, b)| a.cmp(b));
+            (ballots, ratings) = rated.into_iter().unzip();
+        }
+
+        Ok(AnonymousBallots {
+            room_name: room.name,
+            ballot_type,
+            seats: room.seats,
+            options: names.into_iter().map(|(_, name)| name).collect(),
+            ballots,
+            ratings,
+        })
+    }
+
+    /// The first characters that make spreadsheets run a field as a formula, along with the
+    /// `'` put in front of those fields, so that [`csv_name`] can take it off again.
+    const FORMULA_STARTS: [char; 5] = ['=', '+', '-', '@', '\''];
+
+    /// Quotes a CSV field when it holds a separator, a quote or a line break. Fields that
+    /// spreadsheets would run as a formula get a `'` in front.
+    fn csv_field(field: &str) -> Cow<'_, str> {
+        let field = if field.starts_with(FORMULA_STARTS) {
+            Cow::Owned(format!("'{field}"))
+        } else {
+            Cow::Borrowed(field)
+        };
+
+        if field.contains([',', '"', '\n', '\r']) {
+            Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+        } else {
+            field
+        }
+    }
+
+    /// One line per option, best first.
+    fn results_csv(page: &ResultPage) -> String {
+        let mut csv = String::from("option,score,elected,tied\n");
+        for score in &page.scores {
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                csv_field(&score.option),
+                score.score,
+                score.elected,
+                score.tied
+            ));
+        }
+        csv
+    }
+
+    /// A column per option and a line per ballot, holding the rank given to the option,
+    /// starting at 1, or its score on rated ballots. Unranked options are left empty.
+    fn ballots_csv(ballots: &AnonymousBallots) -> String {
+        let mut csv = ballots
+            .options
+            .iter()
+            .map(|option| csv_field(option))
+            .collect::<Vec<_>>()
+            .join(",");
+        csv.push('\n');
+
+        for (idx, ballot) in ballots.ballots.iter().enumerate() {
+            let cells = ballots
+                .options
+                .iter()
+                .map(|option| match ballots.ratings.get(idx) {
+                    Some(ratings) => ratings
+                        .get(option)
+                        .map(|score| score.to_string())
+                        .unwrap_or_default(),
+                    None => ballot
+                        .iter()
+                        .position(|group| group.contains(option))
+                        .map(|rank| (rank + 1).to_string())
+                        .unwrap_or_default(),
+                })
+                .collect::<Vec<_>>();
+            csv.push_str(&cells.join(","));
+            csv.push('\n');
+        }
+
+        csv
+    }
+
+    /// The ballots in the BLT format read by most election counting software: the number of
+    /// options and seats, then each distinct ranking with how many ballots gave it, options
+    /// numbered from 1 and equal options joined by `=`, then the option names and the title,
+    /// quoted, with backslashes, quotes and line breaks escaped by a `\`.
+    fn ballots_blt(ballots: &AnonymousBallots) -> String {
+        let quoted = |name: &str| {
+            let escaped = name
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r");
+            format!("\"{escaped}\"")
+        };
+        let number = |option: &String| {
+            ballots
+                .options
+                .iter()
+                .position(|o| o == option)
+                .map(|idx| idx + 1)
+                .unwrap_or_default()
+        };
+
+        let mut blt = format!("{} {}\n", ballots.options.len(), ballots.seats);
+
+        // Equal ballots are next to each other once sorted.
+        let mut sorted = ballots.ballots.clone();
+        sorted.sort();
+        for group in sorted.chunk_by(|a, b| a == b) {
+            blt.push_str(&group.len().to_string());
+            for equals in &group[0] {
+                let numbers = equals
+                    .iter()
+                    .map(|option| number(option).to_string())
+                    .collect::<Vec<_>>();
+                blt.push(' ');
+                blt.push_str(&numbers.join("="));
+            }
+            blt.push_str(" 0\n");
+        }
+
+        blt.push_str("0\n");
+        for option in &ballots.options {
+            blt.push_str(&quoted(option));
+            blt.push('\n');
+        }
+        blt.push_str(&quoted(&ballots.room_name));
+        blt.push('\n');
+
+        blt
+    }
+
+    /// The format of an imported ballot file.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ToSchema)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Format {
+        /// The ballot file format of counting programs such as OpenSTV, which is also the
+        /// format ballots are downloaded in.
+        #[default]
+        Blt,
+        /// A column per option, named on the first line, and a line per ballot holding the
+        /// rank given to each option, starting at 1. Unranked options are left empty.
+        Csv,
+    }
+
+    impl Format {
+        pub const ALL: [Format; 2] = [Format::Blt, Format::Csv];
+
+        pub fn value(self) -> &'static str {
+            match self {
+                Format::Blt => "blt",
+                Format::Csv => "csv",
+            }
+        }
+
+        pub fn label(self) -> &'static str {
+            match self {
+                Format::Blt => "BLT",
+                Format::Csv => "CSV",
+            }
+        }
+    }
+
+    /// The largest ballot file that can be imported, in bytes.
+    pub const MAX_FILE_SIZE: u64 = 2 * 1024 * 1024;
+
+    /// The most ballots a file can hold, counting each weighted BLT ballot as many times.
+    const MAX_IMPORTED_BALLOTS: usize = 10_000;
+
+    /// The ballots of an imported file.
+    #[derive(Debug, PartialEq)]
+    pub struct BallotFile {
+        pub options: Vec<String>,
+        /// The number of seats, when the file says.
+        pub seats: Option<i64>,
+        /// The title of the election, when the file has one.
+        pub title: Option<String>,
+        /// Each ballot with the line it was read from, as groups of equal option indexes, best
+        /// first.
+        pub ballots: Vec<(usize, Vec<Vec<usize>>)>,
+        /// The ballot lines that couldn't be read, which are left out of `ballots`.
+        pub errors: Vec<LineError>,
+    }
+
+    /// Why a line of an imported file was refused, as one of the error codes.
+    #[derive(Debug, PartialEq, Serialize, ToSchema)]
+    pub struct LineError {
+        /// The line number, starting at 1.
+        pub line: usize,
+        #[schema(value_type = String)]
+        pub error: &'static str,
+    }
+
+    impl LineError {
+        pub fn new(line: usize, rejection: warp::Rejection) -> Self {
+            Self {
+                line,
+                error: rejections::reason(&rejection).1,
+            }
+        }
+    }
+
+    /// Reads the options and ballots of a file, or why it can't be read at all.
+    pub fn read(format: Format, file: &str) -> Result<BallotFile, Vec<LineError>> {
+        let ballot_file = match format {
+            Format::Blt => read_blt(file)?,
+            Format::Csv => read_csv(file)?,
+        };
+
+        if let Some((line, _)) = ballot_file.ballots.get(MAX_IMPORTED_BALLOTS) {
+            return Err(vec![LineError::new(
+                *line,
+                warp::reject::custom(TooManyBallots),
+            )]);
+        }
+
+        Ok(ballot_file)
+    }
+
+    /// The numbered lines of a file that aren't blank.
+    fn lines(file: &str) -> impl Iterator<Item = (usize, &str)> {
+        file.lines()
+            .enumerate()
+            .map(|(idx, line)| (idx + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty())
+    }
+
+    /// The numbered records of a CSV file that aren't blank, keeping together the lines of a
+    /// quoted field that holds line breaks. Records are numbered by their first line.
+    fn csv_lines(file: &str) -> Vec<(usize, String)> {
+        let mut records: Vec<(usize, String)> = Vec::new();
+        let mut quoted = false;
+        for (idx, line) in file.lines().enumerate() {
+            match records.last_mut() {
+                Some((_, record)) if quoted => {
+                    record.push('\n');
+                    record.push_str(line);
+                }
+                _ => records.push((idx + 1, line.to_owned())),
+            }
+            quoted ^= line.matches('"').count() % 2 == 1;
+        }
+        records
+            .into_iter()
+            .map(|(line, record)| (line, record.trim().to_owned()))
+            .filter(|(_, record)| !record.is_empty())
+            .collect()
+    }
+
+    fn invalid_line(line: usize) -> LineError {
+        LineError::new(line, warp::reject::custom(InvalidLine))
+    }
+
+    /// Reads a BLT file: the number of options and seats, optionally the withdrawn options as
+    /// negative numbers, a line per ballot starting with its weight and ending with 0, a line
+    /// with 0, then the option names and the title in quotes.
+    fn read_blt(file: &str) -> Result<BallotFile, Vec<LineError>> {
+        let mut lines = lines(file).peekable();
+
+        let (header_line, header) = lines.next().ok_or_else(|| vec![invalid_line(1)])?;
+        let (count, seats) = match header
+            .split_whitespace()
+            .map(str::parse::<usize>)
+            .collect::<Vec<_>>()[..]
+        {
+            [Ok(count), Ok(seats)] if count > 0 => (count, seats),
+            _ => return Err(vec![invalid_line(header_line)]),
+        };
+
+        let mut withdrawn = Vec::new();
+        if let Some((line, text)) = lines.next_if(|(_, text)| text.starts_with('-')) {
+            for number in text.split_whitespace() {
+                match number.parse::<isize>() {
+                    Ok(number) if (1..=count as isize).contains(&-number) => {
+                        withdrawn.push(-number as usize - 1)
+                    }
+                    _ => return Err(vec![invalid_line(line)]),
+                }
+            }
+        }
+
+        // Options keep their order, without the withdrawn ones.
+        let index = |number: usize| {
+            let option = number - 1;
+            (!withdrawn.contains(&option))
+                .then(|| option - withdrawn.iter().filter(|w| **w < option).count())
+        };
+
+        let mut ballots = Vec::new();
+        let mut errors = Vec::new();
+        let mut ended = false;
+        for (line, text) in lines.by_ref() {
+            if text == "0" {
+                ended = true;
+                break;
+            }
+
+            let tokens = text.split_whitespace().collect::<Vec<_>>();
+            let (Some((weight, ranks)), Some(&"0")) = (tokens.split_first(), tokens.last()) else {
+                errors.push(invalid_line(line));
+                continue;
+            };
+            let Ok(weight) = weight.parse::<usize>() else {
+                errors.push(invalid_line(line));
+                continue;
+            };
+
+            let mut ballot = Vec::new();
+            for rank in &ranks[..ranks.len() - 1] {
+                let numbers = rank.split('=').map(str::parse::<usize>).collect::<Vec<_>>();
+                if numbers.iter().any(Result::is_err) {
+                    errors.push(invalid_line(line));
+                    break;
+                }
+                if !numbers.iter().flatten().all(|n| (1..=count).contains(n)) {
+                    errors.push(LineError::new(line, warp::reject::custom(UnknownOptions)));
+                    break;
+                }
+
+                let group = numbers
+                    .into_iter()
+                    .flatten()
+                    .filter_map(index)
+                    .collect::<Vec<_>>();
+                if !group.is_empty() {
+                    ballot.push(group);
+                }
+            }
+
+            if errors.last().is_some_and(|e| e.line == line) {
+                continue;
+            }
+            if weight > MAX_IMPORTED_BALLOTS - ballots.len() {
+                return Err(vec![LineError::new(
+                    line,
+                    warp::reject::custom(TooManyBallots),
+                )]);
+            }
+            ballots.extend(std::iter::repeat_n((line, ballot), weight));
+        }
+
+        // The names can't be found without the line ending the ballots.
+        if !ended {
+            let last = file.lines().count().max(1);
+            if errors.last().is_none_or(|e| e.line != last) {
+                errors.push(invalid_line(last));
+            }
+            return Err(errors);
+        }
+
+        let mut options = Vec::new();
+        for number in 0..count {
+            let Some((_, name)) = lines.next() else {
+                return Err(vec![invalid_line(file.lines().count().max(1))]);
+            };
+            if !withdrawn.contains(&number) {
+                options.push(blt_name(name));
+            }
+        }
+
+        Ok(BallotFile {
+            options,
+            seats: Some(seats as i64),
+            title: lines.next().map(|(_, title)| blt_name(title)),
+            ballots,
+            errors,
+        })
+    }
+
+    /// A name of a BLT file without its quotes, undoing the `\` escapes of [`ballots_blt`].
+    fn blt_name(line: &str) -> String {
+        let line = line.strip_prefix('"').unwrap_or(line);
+        let line = line.strip_suffix('"').unwrap_or(line);
+
+        let mut name = String::new();
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            let unescaped = match (c, chars.peek()) {
+                ('\\', Some('\\')) => '\\',
+                ('\\', Some('"')) => '"',
+                ('\\', Some('n')) => '\n',
+                ('\\', Some('r')) => '\r',
+                // Other backslashes are kept, as files from elsewhere don't escape names.
+                _ => {
+                    name.push(c);
+                    continue;
+                }
+            };
+            chars.next();
+            name.push(unescaped);
+        }
+        name
+    }
+
+    /// Splits a CSV line into its fields, or `None` when a quote isn't closed.
+    fn csv_record(line: &str) -> Option<Vec<String>> {
+        let mut fields = vec![String::new()];
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            let field = fields.last_mut().unwrap();
+            match (c, quoted) {
+                ('"', true) if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                ('"', _) => quoted = !quoted,
+                (',', false) => fields.push(String::new()),
+                _ => field.push(c),
+            }
+        }
+
+        (!quoted).then_some(fields)
+    }
+
+    /// An option name of a CSV file, without the `'` [`csv_field`] puts in front of names
+    /// spreadsheets would run as a formula.
+    fn csv_name(field: &str) -> String {
+        match field.strip_prefix('\'') {
+            Some(name) if name.starts_with(FORMULA_STARTS) => name.to_owned(),
+            _ => field.to_owned(),
+        }
+    }
+
+    /// Reads a CSV file in the format of [`Format::Csv`], which is also the format ballots of
+    /// ranking rooms are downloaded in.
+    fn read_csv(file: &str) -> Result<BallotFile, Vec<LineError>> {
+        let mut lines = csv_lines(file).into_iter();
+
+        let (header_line, header) = lines.next().ok_or_else(|| vec![invalid_line(1)])?;
+        let options = csv_record(&header)
+            .ok_or_else(|| vec![invalid_line(header_line)])?
+            .into_iter()
+            .map(|name| csv_name(name.trim()))
+            .collect::<Vec<_>>();
+
+        let mut ballots = Vec::new();
+        let mut errors = Vec::new();
+        for (line, text) in lines {
+            let ranks = csv_record(&text)
+                .filter(|fields| fields.len() == options.len())
+                .and_then(|fields| {
+                    fields
+                        .iter()
+                        .map(|field| match field.trim() {
+                            "" => Ok(None),
+                            rank => rank.parse::<usize>().map(Some),
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                        .ok()
+                });
+            let Some(ranks) = ranks else {
+                errors.push(invalid_line(line));
+                continue;
+            };
+
+            let mut distinct = ranks.iter().flatten().copied().collect::<Vec<_>>();
+            distinct.sort_unstable();
+            distinct.dedup();
+
+            let ballot = distinct
+                .into_iter()
+                .map(|rank| {
+                    ranks
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, r)| **r == Some(rank))
+                        .map(|(option, _)| option)
+                        .collect()
+                })
+                .collect();
+            ballots.push((line, ballot));
+        }
+
+        Ok(BallotFile {
+            options,
+            seats: None,
+            title: None,
+            ballots,
+            errors,
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use serde_json::json;
+        use warp::http::{
+            header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+            StatusCode,
+        };
+
+        use super::{
+            ballots_blt, ballots_csv, csv_field, read, AnonymousBallots, Export, Format, LineError,
+        };
+        use crate::{events::Broadcasters, names, tally::BallotType, tests};
+
+        fn error(line: usize, error: &'static str) -> LineError {
+            LineError { line, error }
+        }
+
+        #[tokio::test]
+        async fn admins_download_ended_rooms() {
+            let app = tests::app(tests::store().await, Broadcasters::new());
+            let body = json!({"name": "Lunch", "options": ["Pasta", "Pizza", "Tacos"]});
+            let room = tests::room(&app, body, 3).await;
+            let [pasta, pizza, tacos] = room.options[..] else {
+                unreachable!()
+            };
+            let admin = format!("Bearer {}", room.admin_code);
+            let admin_cookie = format!("{}={}", names::ROOM_ADMIN_COOKIE_NAME, room.admin_code);
+            let download = |export: Export, cookie: String| {
+                let app = app.clone();
+                async move {
+                    warp::test::request()
+                        .path(&names::export_url(room.id, export))
+                        .header("cookie", cookie)
+                        .reply(&app)
+                        .await
+                }
+            };
+
+            warp::test::request()
+                .method("PUT")
+                .path(&format!("/api/v1/rooms/{}/start", room.id))
+                .header("authorization", &admin)
+                .reply(&app)
+                .await;
+            let ballots = [
+                [tacos, pasta, pizza],
+                [pasta, pizza, tacos],
+                [pizza, tacos, pasta],
+            ];
+            for ((voter_id, voter_code), ballot) in room.voters.iter().zip(ballots) {
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&format!("/api/v1/voters/{voter_id}/vote"))
+                    .header("authorization", format!("Bearer {voter_code}"))
+                    .json(&json!({ "options": ballot }))
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+            }
+
+            // Nothing can be downloaded while voting.
+            let resp = download(Export::BallotsCsv, admin_cookie.clone()).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+            let resp = warp::test::request()
+                .method("PUT")
+                .path(&format!("/api/v1/rooms/{}/end", room.id))
+                .header("authorization", &admin)
+                .reply(&app)
+                .await;
+            let results = tests::json(&resp);
+
+            let voter_cookie = format!("{}={}", names::VOTER_COOKIE_NAME, room.voters[0].1);
+            for cookie in [
+                voter_cookie,
+                format!("{}=nope", names::ROOM_ADMIN_COOKIE_NAME),
+            ] {
+                for export in Export::ALL {
+                    let resp = download(export, cookie.clone()).await;
+                    assert_ne!(resp.status(), StatusCode::OK, "{export:?} {cookie}");
+                }
+            }
+
+            for export in Export::ALL {
+                let resp = download(export, admin_cookie.clone()).await;
+                assert_eq!(resp.status(), StatusCode::OK);
+                assert_eq!(resp.headers()[CONTENT_TYPE], export.content_type());
+                let disposition = format!(
+                    "attachment; filename=\"ordo-{}-{}\"",
+                    room.id,
+                    export.file_name()
+                );
+                assert_eq!(resp.headers()[CONTENT_DISPOSITION], disposition.as_str());
+
+                // Ballots are sorted by what they say rather than kept in the voters' order.
+                let text = tests::text(&resp);
+                match export {
+                    Export::ResultsCsv => {
+                        let winner = results["scores"][0]["option"].as_str().unwrap();
+                        assert!(text.starts_with(&format!("option,score,elected,tied\n{winner},")));
+                        assert_eq!(text.lines().count(), 4);
+                    }
+                    Export::ResultsJson => {
+                        let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+                        assert_eq!(json, results);
+                    }
+                    Export::BallotsCsv => {
+                        assert_eq!(text, "Pasta,Pizza,Tacos\n1,2,3\n3,1,2\n2,3,1\n");
+                    }
+                    Export::BallotsJson => {
+                        let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+                        assert_eq!(
+                            json["ballots"],
+                            json!([
+                                [["Pasta"], ["Pizza"], ["Tacos"]],
+                                [["Pizza"], ["Tacos"], ["Pasta"]],
+                                [["Tacos"], ["Pasta"], ["Pizza"]],
+                            ])
+                        );
+                        assert!(!text.contains("voter"));
+                    }
+                    Export::BallotsBlt => {
+                        assert_eq!(
+                            text,
+                            "3 1\n1 1 2 3 0\n1 2 3 1 0\n1 3 1 2 0\n0\n\"Pasta\"\n\"Pizza\"\n\"Tacos\"\n\"Lunch\"\n"
+                        );
+                    }
+                }
+            }
+        }
+
+        #[tokio::test]
+        async fn downloaded_ballots_import_back() {
+            let app = tests::app(tests::store().await, Broadcasters::new());
+            let names = [
+                "=SUM(A1)",
+                "'quoted",
+                "Pizza \"thin\"",
+                "Pasta, al dente",
+                "Tacos\\al pastor",
+                "Soup\nof the day",
+            ];
+            let body = json!({"name": "Lunch \"at noon\"", "options": names});
+            let room = tests::room(&app, body, 2).await;
+            let admin = format!("Bearer {}", room.admin_code);
+            let admin_cookie = format!("{}={}", names::ROOM_ADMIN_COOKIE_NAME, room.admin_code);
+
+            warp::test::request()
+                .method("PUT")
+                .path(&format!("/api/v1/rooms/{}/start", room.id))
+                .header("authorization", &admin)
+                .reply(&app)
+                .await;
+            for (voter_id, voter_code) in &room.voters {
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&format!("/api/v1/voters/{voter_id}/vote"))
+                    .header("authorization", format!("Bearer {voter_code}"))
+                    .json(&json!({ "options": room.options }))
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+            }
+            warp::test::request()
+                .method("PUT")
+                .path(&format!("/api/v1/rooms/{}/end", room.id))
+                .header("authorization", &admin)
+                .reply(&app)
+                .await;
+
+            for (export, format) in [(Export::BallotsCsv, "csv"), (Export::BallotsBlt, "blt")] {
+                let resp = warp::test::request()
+                    .path(&names::export_url(room.id, export))
+                    .header("cookie", &admin_cookie)
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::OK);
+
+                let body = json!({"file": tests::text(&resp), "format": format, "name": "Copy"});
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/v1/rooms/import")
+                    .json(&body)
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::CREATED, "{format}");
+                let imported = tests::json(&resp);
+                let resp = warp::test::request()
+                    .path(&format!("/api/v1/rooms/{}/results", imported["id"]))
+                    .header(
+                        "authorization",
+                        format!("Bearer {}", imported["admin_code"].as_str().unwrap()),
+                    )
+                    .reply(&app)
+                    .await;
+                let results = tests::json(&resp);
+                assert_eq!(results["voters"], 2, "{format}");
+                let mut options = results["scores"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|score| score["option"].as_str().unwrap())
+                    .collect::<Vec<_>>();
+                options.sort_unstable();
+                let mut names = names.to_vec();
+                names.sort_unstable();
+                assert_eq!(options, names, "{format}");
+            }
+        }
+
+        #[test]
+        fn csv_fields_are_not_formulas() {
+            assert_eq!(csv_field("Pasta"), "Pasta");
+            assert_eq!(csv_field("Pasta, al dente"), "\"Pasta, al dente\"");
+            for formula in ["=1+1", "+1", "-1", "@SUM(A1)", "'=1+1"] {
+                assert_eq!(csv_field(formula), format!("'{formula}"));
+            }
+            assert_eq!(csv_field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+        }
+
+        #[test]
+        fn reads_blt() {
+            let file = "4 2\n-3\n2 1=2 4 0\n\n1 3 0\n1 4 2 1 0\n0\n\"A\"\n\"B\"\n\"C\"\n\"D\"\n\"Lunch\"\n";
+            let read = read(Format::Blt, file).unwrap();
+
+            // C is withdrawn, so D is the third option.
+            assert_eq!(read.options, ["A", "B", "D"]);
+            assert_eq!(
+                (read.seats, read.title.as_deref()),
+                (Some(2), Some("Lunch"))
+            );
+            assert_eq!(
+                read.ballots,
+                [
+                    (3, vec![vec![0, 1], vec![2]]),
+                    (3, vec![vec![0, 1], vec![2]]),
+                    (5, vec![]),
+                    (6, vec![vec![2], vec![1], vec![0]]),
+                ]
+            );
+            assert!(read.errors.is_empty());
+        }
+
+        #[test]
+        fn reports_blt_lines() {
+            let file = "2 1\n1 1 3 0\n1 1 x 0\n1 1\n1 2 1 0\n0\n\"A\"\n\"B\"\n";
+            let read = read(Format::Blt, file).unwrap();
+
+            assert_eq!(read.ballots, [(5, vec![vec![1], vec![0]])]);
+            assert_eq!(
+                read.errors,
+                [
+                    error(2, "UNKNOWN_OPTIONS"),
+                    error(3, "INVALID_LINE"),
+                    error(4, "INVALID_LINE"),
+                ]
+            );
+
+            assert_eq!(
+                super::read(Format::Blt, "2 1\n1 1 0\n").unwrap_err(),
+                [error(2, "INVALID_LINE")]
+            );
+            assert_eq!(
+                super::read(Format::Blt, "2 1\n20000 1 0\n0\n\"A\"\n\"B\"\n").unwrap_err(),
+                [error(2, "TOO_MANY_BALLOTS")]
+            );
+            let huge = format!("2 1\n1 1 0\n{} 2 0\n0\n\"A\"\n\"B\"\n", usize::MAX);
+            assert_eq!(
+                super::read(Format::Blt, &huge).unwrap_err(),
+                [error(3, "TOO_MANY_BALLOTS")]
+            );
+        }
+
+        #[test]
+        fn reads_csv() {
+            let file = "A,\"B, \"\"the best\"\"\",C\n1,2,\n2,1,1\n1,x,2\n1,2\n";
+            let read = read(Format::Csv, file).unwrap();
+
+            assert_eq!(read.options, ["A", "B, \"the best\"", "C"]);
+            assert_eq!(
+                read.ballots,
+                [(2, vec![vec![0], vec![1]]), (3, vec![vec![1, 2], vec![0]]),]
+            );
+            assert_eq!(
+                read.errors,
+                [error(4, "INVALID_LINE"), error(5, "INVALID_LINE")]
+            );
+        }
+
+        /// Downloaded ballots read back as they were.
+        #[test]
+        fn reads_downloaded_ballots() {
+            let options = ["=Pasta", "Pizza, \"thin\"", "Tacos\\al\npastor"].map(str::to_owned);
+            let ballots = AnonymousBallots {
+                room_name: "Lunch".to_owned(),
+                ballot_type: BallotType::Ranking,
+                seats: 1,
+                options: options.to_vec(),
+                ballots: vec![
+                    vec![vec![options[1].clone()], vec![options[0].clone()]],
+                    vec![vec![options[0].clone(), options[2].clone()]],
+                    vec![vec![options[1].clone()], vec![options[0].clone()]],
+                ],
+                ratings: Vec::new(),
+            };
+            let expected = [
+                vec![vec![1], vec![0]],
+                vec![vec![0, 2]],
+                vec![vec![1], vec![0]],
+            ];
+
+            let csv = read(Format::Csv, &ballots_csv(&ballots)).unwrap();
+            assert_eq!(csv.options, options);
+            let csv = csv.ballots.into_iter().map(|(_, b)| b).collect::<Vec<_>>();
+            assert_eq!(csv, expected);
+
+            let blt = read(Format::Blt, &ballots_blt(&ballots)).unwrap();
+            assert_eq!(blt.options, options);
+            assert_eq!(blt.title.as_deref(), Some("Lunch"));
+            let mut blt = blt.ballots.into_iter().map(|(_, b)| b).collect::<Vec<_>>();
+            let mut expected = expected.to_vec();
+            blt.sort();
+            expected.sort();
+            assert_eq!(blt, expected);
+        }
+    }
+}
+
+mod voting {
+    use maud::{html, Markup, PreEscaped};
+    use serde::{Deserialize, Serialize};
+    use utoipa::ToSchema;
+
+    use crate::{
+        files::Export,
+        names,
+        tally::{self, BallotType, Method, Pairwise, Round, Runoff, TieBreak},
+        utils,
+    };
+
+    #[derive(Serialize, ToSchema)]
+    pub struct VoteAdminPage {
+        pub room_id: i64,
+        pub room_name: String,
+        /// Seconds until the room is deleted.
+        pub expires_in: i64,
+        pub recorded_votes: i32,
+        /// Whether the results are counted again after every ballot.
+        pub live: bool,
+        pub approved_voters: Vec<Voter>,
+    }
+
+    #[derive(Serialize, ToSchema)]
+    pub struct Voter {
+        pub id: i64,
+        pub voted: bool,
+        /// Whether the voter has the room open.
+        pub online: bool,
+    }
+
+    /// Whether a voter has the room open, kept up to date by presence events.
+    pub fn presence(voter_id: i64, online: bool) -> Markup {
+        html! {
+            span."boxed presence" .presence--online[online]
+                data-testid="presence"
+                sse-swap=(names::presence_event(voter_id))
+                hx-swap="outerHTML" {
+                @if online { "ONLINE" } @else { "OFFLINE" }
+            }
+        }
+    }
+
+    pub fn admin_page(page: VoteAdminPage) -> Markup {
+        let approved_count = utils::format_num(page.approved_voters.len() as i32);
+        let approved_label = utils::pluralize(page.approved_voters.len() as i32, "voter", "voters");
+
+        let recorded_votes = utils::format_num(page.recorded_votes);
+        let recorded_votes_label = utils::pluralize(page.recorded_votes, "vote", "votes");
+
+        html! {
+            section."grid gap-lg w-800" hx-ext="sse" sse-connect=(names::room_listen_url(page.room_id)) {
+                h1."text-lg" { (page.room_name) }
+
+                div."alert" { "ROOM WILL CLOSE IN " (utils::time_left(page.expires_in)) "." }
+
+                section."two-cols" {
+                    div."card card--secondary stat" {
+                        p."stat__num" { (approved_count) }
+                        p."stat__desc" { "approved " (approved_label) }
+                    }
+
+                    div."card stat" hx-swap="innerHTML" sse-swap=(names::VOTE_COUNT_EVENT) {
+                        p."stat__num" data-testid="votes-count" { (recorded_votes) }
+                        p."stat__desc" { "recorded " (recorded_votes_label) }
+                    }
+                }
+
+                @if page.recorded_votes > 0 {
+                    button."button text-lg align-left"
+                        hx-put=(names::end_vote_url(page.room_id))
+                        hx-target="main"
+                        hx-swap="innerHTML" { "END VOTE" }
+                } @else {
+                    button."button text-lg align-left"
+                        disabled
+                        sse-swap=(names::VOTE_ENDABLE_EVENT)
+                        hx-swap="outerHTML" { "AT LEAST ONE RECORDED VOTE REQUIRED TO BE ABLE TO END VOTES." }
+                }
+
+                @if page.live {
+                    section hx-swap="innerHTML" sse-swap=(names::LIVE_RESULTS_EVENT) {
+                        div."alert" { "LIVE RESULTS WILL SHOW UP AS VOTES COME IN." }
+                    }
+                }
+
+                section."grid gap-md" {
+                    h2."text-md" { "APPROVED VOTERS" }
+
+                    @for voter in page.approved_voters {
+                        div."flex gap-md" {
+                            span."strech code" {
+                                span { "VOTER ID" }
+                                span { (voter.id) }
+                            }
+
+                            (presence(voter.id, voter.online))
+
+                            @if voter.voted {
+                                span."boxed" { "VOTED" }
+                            } @else {
+                                span."boxed" sse-swap=(names::vote_event(voter.id)) hx-swap="outerHTML" { "WAITING" }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+    pub struct ResultPage {
+        pub room_id: i64,
+        pub room_name: String,
+        pub method: Method,
+        pub seats: usize,
+        pub quota: Option<f64>,
+        pub tie_break: TieBreak,
+        pub seed: u64,
+        pub partial: bool,
+        /// The highest score on score and STAR ballots.
+        pub max_score: u32,
+        /// The number of ballots counted.
+        pub voters: usize,
+        pub ties: Vec<Vec<String>>,
+        pub pairwise: Pairwise,
+        pub scores: Vec<Score>,
+        pub rounds: Vec<Round>,
+        pub runoff: Option<Runoff>,
+        /// How many voters gave each option each grade, for Majority Judgment.
+        pub profiles: Vec<(String, Vec<usize>)>,
+    }
+
+    #[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+    pub struct Score {
+        pub option: String,
+        pub score: f64,
+        pub elected: bool,
+        pub tied: bool,
+    }
+
+    /// The score of each option, best first.
+    fn scores(page: &ResultPage) -> Markup {
+        html! {
+            section."grid gap-sm" data-testid="result-scores" {
+                div."big-small gap-sm" {
+                    p."code text-sm" { "OPTION" }
+                    p."code text-sm" { (page.method.score_label()) }
+                }
+
+                @for score in &page.scores {
+                    div."big-small gap-sm" {
+                        div."card flex gap-md" {
+                            p."text-sm strech" { (score.option) }
+                            @if score.tied {
+                                span."boxed text-sm" { "TIED" }
+                            }
+                            @if page.seats > 1 && score.elected {
+                                span."boxed text-sm" { "ELECTED" }
+                            }
+                        }
+
+                        div."card card--secondary" {
+                            @if page.method == Method::MajorityJudgment {
+                                p."text-sm" { (tally::GRADES[score.score as usize]) }
+                            } @else {
+                                p."text-sm" { (utils::format_score(score.score)) }
+                            }
+                            @match page.method.ballot_type() {
+                                BallotType::Approval if page.voters > 0 => p."text-sm" {
+                                    (utils::format_score(score.score * 100.0 / page.voters as f64)) "%"
+                                },
+                                BallotType::Score | BallotType::Star if page.voters > 0 => p."text-sm" {
+                                    "AVG " (utils::format_score(score.score / page.voters as f64))
+                                },
+                                _ => {},
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The standings of a live room, counted from the ballots recorded so far.
+    pub fn live_results(page: &ResultPage) -> Markup {
+        let ballots = utils::pluralize(page.voters as i32, "BALLOT", "BALLOTS");
+
+        html! {
+            section."grid gap-md" data-testid="live-results" {
+                h2."text-md" { "LIVE RESULTS" }
+                p."text-sm" { "COUNTED FROM " (page.voters) " " (ballots) " SO FAR." }
+                (scores(page))
+            }
+        }
+    }
+
+    /// The results of a room, with links to download them for its `admin`.
+    pub fn result_page(page: ResultPage, admin: bool) -> Markup {
+        // Labels are user input, so they go in as JSON strings, with `<` escaped so that none
+        // of them can close the script.
+        let labels = page
+            .scores
+            .iter()
+            .map(|Score { option, .. }| option)
+            .collect::<Vec<_>>();
+        let labels = serde_json::to_string(&labels)
+            .unwrap_or_else(|_| "[]".to_owned())
+            .replace('<', "\\u003c");
+        let data = page
+            .scores
+            .iter()
+            .map(|s| s.score.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let score_label = page.method.score_label();
+        let chart_js = format!(
+            r#"
+        <script>
+            const canvas = document.querySelector('canvas');
+
+            const data = {{
+              labels: {labels},
+              datasets: [{{
+                label: '{score_label}',
+                data: [{data}],
+                borderWidth: 1
+              }}]
+            }};
+
+            const config = {{
+              type: 'bar',
+              data: data,
+              options: {{
+                scales: {{
+                  y: {{
+                    beginAtZero: true
+                  }}
+                }}
+              }},
+            }};
+
+            new Chart(canvas, config);
+        </script>
+            "#
+        );
+
+        html! {
+            section."grid gap-lg w-800" {
+                h1."text-lg" { "RESULTS FOR \"" (page.room_name) "\"" }
+
+                div."alert" {
+                    "COUNTED WITH " (page.method.label())
+                    @if page.seats > 1 { " FOR " (page.seats) " SEATS" }
+                    @if let Some(quota) = page.quota { ", WITH A QUOTA OF " (utils::format_score(quota)) " VOTES" }
+                    @if matches!(page.method.ballot_type(), BallotType::Score | BallotType::Star) { ", FROM SCORES OF 0 TO " (page.max_score) }
+                    "."
+                }
+
+                @if page.partial {
+                    div."alert" { (page.method.unranked_rule()) }
+                }
+
+                div."alert" data-testid="tie-break" {
+                    @match page.tie_break {
+                        TieBreak::LeaveTied => "TIES ARE LEFT UNRESOLVED.",
+                        TieBreak::Random => { "TIES ARE BROKEN BY " (page.tie_break.label()) " FROM SEED " (page.seed) "." },
+                        _ => { "TIES ARE BROKEN BY " (page.tie_break.label()) "." },
+                    }
+                }
+
+                @for tie in &page.ties {
+                    div."alert" data-testid="tie" {
+                        "TIE BETWEEN " (tie.join(", "))
+                        @if page.tie_break == TieBreak::LeaveTied { " (LEFT UNRESOLVED)." } @else { " (BROKEN BY " (page.tie_break.label()) ")." }
+                    }
+                }
+
+                (scores(&page))
+
+                @if let Some(runoff) = &page.runoff {
+                    (automatic_runoff(runoff))
+                }
+
+                @if page.method == Method::MajorityJudgment {
+                    (merit_profiles(&page.profiles))
+                } @else {
+                    canvas."card card--secondary" {}
+
+                    (PreEscaped(chart_js))
+                }
+
+                @if !page.rounds.is_empty() {
+                    (rounds(&page.rounds))
+
+                    a."button w-fit" href=(names::round_results_url(page.room_id)) target="_blank" {
+                        "VIEW ROUND BY ROUND BREAKDOWN"
+                    }
+                }
+
+                (pairwise_matrix(&page.pairwise))
+
+                @if admin {
+                    section."grid gap-sm" data-testid="exports" {
+                        h2."text-md" { "DOWNLOAD" }
+                        div."flex gap-sm downloads" {
+                            @for export in Export::ALL {
+                                a."button w-fit text-sm" href=(names::export_url(page.room_id, export)) download { (export.label()) }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// One bar per option, split into the share of voters giving it each grade, best grade
+    /// first. The line in the middle marks the majority grade.
+    fn merit_profiles(profiles: &[(String, Vec<usize>)]) -> Markup {
+        html! {
+            section."grid gap-md" data-testid="merit-profiles" {
+                h2."text-md" { "MERIT PROFILES" }
+
+                div."flex gap-sm merit__legend" {
+                    @for (grade, label) in tally::GRADES.iter().enumerate().rev() {
+                        span."text-sm merit__key" {
+                            span class=(format!("merit__swatch merit__grade--{grade}")) {}
+                            (label)
+                        }
+                    }
+                }
+
+                @for (option, counts) in profiles {
+                    @let total = counts.iter().sum::<usize>().max(1) as f64;
+
+                    div."grid gap-sm" {
+                        p."text-sm" { (option) }
+                        div."merit" {
+                            @for (grade, count) in counts.iter().enumerate().rev().filter(|(_, c)| **c > 0) {
+                                @let share = *count as f64 * 100.0 / total;
+                                div class=(format!("merit__grade merit__grade--{grade}"))
+                                    style=(format!("width: {share:.2}%"))
+                                    title=(format!("{}: {count}", tally::GRADES[grade])) {}
+                            }
+                            div."merit__median" {}
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn automatic_runoff(runoff: &Runoff) -> Markup {
+        let [(winner, won), (runner_up, lost)] = &runoff.finalists;
+
+        html! {
+            section."grid gap-md" data-testid="runoff" {
+                h2."text-md" { "AUTOMATIC RUNOFF" }
+                p."text-sm" { "THE TWO HIGHEST SCORING OPTIONS FACE OFF. EACH VOTER SUPPORTS THE ONE THEY SCORED HIGHER." }
+
+                div."card grid gap-sm" {
+                    div."big-small gap-sm" {
+                        span."boxed text-sm" { (winner) }
+                        span."boxed text-sm" { (won) }
+                    }
+                    div."big-small gap-sm" {
+                        span."boxed text-sm" { (runner_up) }
+                        span."boxed text-sm" { (lost) }
+                    }
+                    @if runoff.no_preference > 0 {
+                        p."text-sm" { "NO PREFERENCE: " (runoff.no_preference) }
+                    }
+                    @if won == lost {
+                        p."text-sm" { "THE RUNOFF WAS TIED, SO THE OPTION WITH THE HIGHER TOTAL SCORE WINS." }
+                    }
+                    p."text-sm bold" { "ELECTED: " (winner) }
+                }
+            }
+        }
+    }
+
+    fn rounds(rounds: &[Round]) -> Markup {
+        html! {
+            section."grid gap-md" {
+                h2."text-md" { "ROUNDS" }
+
+                @for (idx, round) in rounds.iter().enumerate() {
+                    div."card grid gap-sm" data-testid="round" {
+                        h3."text-sm bold" { "ROUND " (idx + 1) }
+
+                        @for (option, votes) in &round.tallies {
+                            div."big-small gap-sm" {
+                                span."boxed text-sm" { (option) }
+                                span."boxed text-sm" { (utils::format_score(*votes)) }
+                            }
+                        }
+
+                        @if round.exhausted > 0.0 {
+                            p."text-sm" { "EXHAUSTED: " (utils::format_score(round.exhausted)) }
+                        }
+                        @if !round.elected.is_empty() {
+                            p."text-sm bold" { "ELECTED: " (round.elected.join(", ")) }
+                        }
+                        @if !round.tied.is_empty() {
+                            p."text-sm" { "TIED FOR FEWEST VOTES: " (round.tied.join(", ")) }
+                        }
+                        @if !round.eliminated.is_empty() {
+                            p."text-sm bold" { "ELIMINATED: " (round.eliminated.join(", ")) }
+                        }
+                        @for transfer in &round.transfers {
+                            p."text-sm" {
+                                (utils::format_score(transfer.votes)) " "
+                                (utils::pluralize(transfer.votes.ceil() as i32, "VOTE", "VOTES"))
+                                " FROM " (transfer.from) " WENT TO "
+                                (transfer.to.as_deref().unwrap_or("NO ONE (EXHAUSTED)"))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub struct RoundsPage {
+        pub room_name: String,
+        pub method: Method,
+        pub rounds: Vec<Round>,
+    }
+
+    pub fn rounds_page(page: RoundsPage) -> Markup {
+        html! {
+            section."grid gap-lg w-800" {
+                h1."text-lg" { "ROUNDS FOR \"" (page.room_name) "\"" }
+
+                @if page.rounds.is_empty() {
+                    div."alert" { (page.method.label()) " IS NOT COUNTED IN ROUNDS." }
+                } @else {
+                    div."card card--secondary sankey" {
+                        (transfer_chart(&page.rounds))
+                    }
+
+                    (rounds(&page.rounds))
+                }
+            }
+        }
+    }
+
+    /// A Sankey-style diagram with a column of vote totals per round and bands showing
+    /// how votes stayed with or moved between options from one round to the next.
+    fn transfer_chart(rounds: &[Round]) -> Markup {
+        const HEIGHT: f64 = 360.0;
+        const MARGIN: f64 = 20.0;
+        const PADDING: f64 = 12.0;
+        const NODE_WIDTH: f64 = 14.0;
+        const COLUMN_GAP: f64 = 170.0;
+        const LABEL_WIDTH: f64 = 140.0;
+
+        // Each column holds the options in the count that round, then the exhausted votes.
+        let columns = rounds
+            .iter()
+            .map(|round| {
+                let mut nodes = round
+                    .tallies
+                    .iter()
+                    .map(|(option, votes)| (Some(option.as_str()), *votes))
+                    .collect::<Vec<_>>();
+                if round.exhausted > 0.0 {
+                    nodes.push((None, round.exhausted));
+                }
+                nodes
+            })
+            .collect::<Vec<_>>();
+
+        let max_total = columns
+            .iter()
+            .map(|nodes| nodes.iter().map(|(_, votes)| votes).sum::<f64>())
+            .fold(0.0, f64::max);
+        let max_nodes = columns.iter().map(Vec::len).max().unwrap_or_default();
+
+        if max_total <= 0.0 {
+            return html! {};
+        }
+
+        let scale = (HEIGHT - PADDING * max_nodes.saturating_sub(1) as f64) / max_total;
+        let width = COLUMN_GAP * (columns.len() - 1) as f64 + NODE_WIDTH + LABEL_WIDTH;
+
+        let positions = columns
+            .iter()
+            .map(|nodes| {
+                let mut y = MARGIN;
+                nodes
+                    .iter()
+                    .map(|(_, votes)| {
+                        let top = y;
+                        y += votes * scale + PADDING;
+                        top
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut links = Vec::new();
+        for (col, round) in rounds.iter().enumerate().take(rounds.len() - 1) {
+            let (from, to) = (&columns[col], &columns[col + 1]);
+            let mut from_offsets = vec![0.0; from.len()];
+            let mut to_offsets = vec![0.0; to.len()];
+
+            // Votes that stay where they are, followed by the votes that moved this round.
+            let flows = from
+                .iter()
+                .filter(|(key, _)| to.iter().any(|(k, _)| k == key))
+                .map(|(key, votes)| (*key, *key, *votes))
+                .chain(
+                    round
+                        .transfers
+                        .iter()
+                        .map(|t| (Some(t.from.as_str()), t.to.as_deref(), t.votes)),
+                );
+
+            for (source, target, votes) in flows {
+                let (Some(si), Some(ti)) = (
+                    from.iter().position(|(k, _)| *k == source),
+                    to.iter().position(|(k, _)| *k == target),
+                ) else {
+                    continue;
+                };
+
+                let height = votes * scale;
+                let x0 = COLUMN_GAP * col as f64 + NODE_WIDTH;
+                let x1 = COLUMN_GAP * (col + 1) as f64;
+                let xm = (x0 + x1) / 2.0;
+                let y0 = positions[col][si] + from_offsets[si];
+                let y1 = positions[col + 1][ti] + to_offsets[ti];
+                from_offsets[si] += height;
+                to_offsets[ti] += height;
+
+                let class = if source == target {
+                    "sankey__link"
+                } else {
+                    "sankey__link sankey__link--transfer"
+                };
+                let path = format!(
+                    "M{x0:.1},{y0:.1} C{xm:.1},{y0:.1} {xm:.1},{y1:.1} {x1:.1},{y1:.1} \
+                     L{x1:.1},{y1b:.1} C{xm:.1},{y1b:.1} {xm:.1},{y0b:.1} {x0:.1},{y0b:.1} Z",
+                    y0b = y0 + height,
+                    y1b = y1 + height,
+                );
+                links.push((class, path));
+            }
+        }
+
+        html! {
+            svg."w-full" viewBox=(format!("0 0 {width:.0} {:.0}", HEIGHT + MARGIN * 2.0)) role="img" {
+                @for (class, path) in &links {
+                    path class=(class) d=(path) {}
+                }
+
+                @for (col, nodes) in columns.iter().enumerate() {
+                    @for (idx, (key, votes)) in nodes.iter().enumerate() {
+                        @let x = COLUMN_GAP * col as f64;
+                        @let y = positions[col][idx];
+                        @let height = (votes * scale).max(1.0);
+                        @let class = match key {
+                            Some(option) if rounds[col].elected.iter().any(|o| o == option) => "sankey__node sankey__node--elected",
+                            Some(option) if rounds[col].eliminated.iter().any(|o| o == option) => "sankey__node sankey__node--eliminated",
+                            Some(_) => "sankey__node",
+                            None => "sankey__node sankey__node--exhausted",
+                        };
+
+                        rect class=(class) x=(x) y=(format!("{y:.1}")) width=(NODE_WIDTH) height=(format!("{height:.1}")) {}
+                        text."sankey__label" x=(x + NODE_WIDTH + 4.0) y=(format!("{:.1}", y + height / 2.0)) {
+                            (key.unwrap_or("EXHAUSTED")) " (" (utils::format_score(*votes)) ")"
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn pairwise_matrix(pairwise: &Pairwise) -> Markup {
+        let cycles = pairwise.cycles();
+
+        html! {
+            section."grid gap-md" {
+                h2."text-md" { "HEAD TO HEAD" }
+                p."text-sm" { "EACH CELL SHOWS HOW MANY VOTERS PREFERRED THE ROW OPTION OVER THE COLUMN OPTION." }
+
+                @for cycle in &cycles {
+                    div."alert" data-testid="pairwise-cycle" {
+                        "CYCLE WITH NO CLEAR WINNER: " (cycle.join(" > "))
+                    }
+                }
+
+                div."matrix-wrapper" {
+                    table."matrix" {
+                        tr {
+                            th {}
+                            @for option in &pairwise.options {
+                                th."code text-sm" { (option) }
+                            }
+                        }
+
+                        @for (i, option) in pairwise.options.iter().enumerate() {
+                            tr {
+                                th."code text-sm" { (option) }
+                                @for j in 0..pairwise.len() {
+                                    @if i == j {
+                                        td."matrix__cell matrix__cell--self" {}
+                                    } @else {
+                                        @let class = if pairwise.beats(i, j) {
+                                            "matrix__cell matrix__cell--win"
+                                        } else if pairwise.beats(j, i) {
+                                            "matrix__cell matrix__cell--loss"
+                                        } else {
+                                            "matrix__cell"
+                                        };
+                                        @let margin = pairwise.prefs[i][j] as i64 - pairwise.prefs[j][i] as i64;
+
+                                        td class=(class) {
+                                            (utils::format_num(pairwise.prefs[i][j] as i32))
+                                            span."matrix__margin" { " (" (format!("{margin:+}")) ")" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{rounds_page, RoundsPage};
+        use crate::tally::{Election, InstantRunoff, Method, Tally, TieBreak};
+
+        #[test]
+        fn rounds_page_charts_transfers() {
+            // B and C tie for fewest votes, C is eliminated and its ballot runs out of options.
+            let ballots = [vec!["A"], vec!["A"], vec!["B", "A"], vec!["C"]];
+            let election = Election {
+                options: ["A", "B", "C"].map(str::to_owned).to_vec(),
+                ballots: ballots
+                    .iter()
+                    .map(|ranking| ranking.iter().map(|o| vec![(*o).to_owned()]).collect())
+                    .collect(),
+                ratings: Vec::new(),
+                seats: 1,
+                tie_break: TieBreak::Alphabetical,
+                seed: 0,
+            };
+            let rounds = InstantRunoff.tally(&election).rounds;
+
+            let page = rounds_page(RoundsPage {
+                room_name: "Lunch".to_owned(),
+                method: Method::InstantRunoff,
+                rounds,
+            })
+            .into_string();
+
+            assert_eq!(page.matches("data-testid=\"round\"").count(), 2);
+            assert!(page.contains("TIED FOR FEWEST VOTES: B, C"));
+            assert!(page.contains("ELIMINATED: C"));
+            assert!(page.contains("1 VOTE FROM C WENT TO NO ONE (EXHAUSTED)"));
+            assert!(page.contains("ELECTED: A"));
+
+            // A band for each option staying in the count and one for C's exhausted ballot.
+            assert_eq!(page.matches("class=\"sankey__link\"").count(), 2);
+            assert_eq!(page.matches("sankey__link--transfer").count(), 1);
+            assert!(page.contains("sankey__node sankey__node--eliminated"));
+            assert!(page.contains("sankey__node sankey__node--exhausted"));
+            assert!(page.contains("sankey__node sankey__node--elected"));
+        }
+
+        #[test]
+        fn rounds_page_without_rounds() {
+            let page = rounds_page(RoundsPage {
+                room_name: "Lunch".to_owned(),
+                method: Method::Borda,
+                rounds: Vec::new(),
+            })
+            .into_string();
+
+            assert!(page.contains("BORDA COUNT IS NOT COUNTED IN ROUNDS."));
+            assert!(!page.contains("<svg"));
+        }
+    }
+}
+
+mod tally {
+    use std::collections::{BTreeMap, HashMap, VecDeque};
+
+    use serde::{Deserialize, Serialize};
+    use utoipa::ToSchema;
+
+    /// Votes are fractional once surpluses are transferred, so comparisons allow for rounding.
+    const EPSILON: f64 = 1e-9;
+
+    /// The counting method a room uses to turn ballots into results.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Method {
+        #[default]
+        Borda,
+        InstantRunoff,
+        Schulze,
+        RankedPairs,
+        SingleTransferableVote,
+        Approval,
+        Score,
+        Star,
+        MajorityJudgment,
+    }
+
+    impl Method {
+        pub const ALL: [Method; 9] = [
+            Method::Borda,
+            Method::InstantRunoff,
+            Method::Schulze,
+            Method::RankedPairs,
+            Method::SingleTransferableVote,
+            Method::Approval,
+            Method::Score,
+            Method::Star,
+            Method::MajorityJudgment,
+        ];
+
+        /// The value stored in the `rooms.method` column.
+        pub fn code(self) -> i64 {
+            match self {
+                Method::Borda => 0,
+                Method::InstantRunoff => 1,
+                Method::Schulze => 2,
+                Method::RankedPairs => 3,
+                Method::SingleTransferableVote => 4,
+                Method::Approval => 5,
+                Method::Score => 6,
+                Method::Star => 7,
+                Method::MajorityJudgment => 8,
+            }
+        }
+
+        /// The kind of ballot the method counts.
+        pub fn ballot_type(self) -> BallotType {
+            match self {
+                Method::Approval => BallotType::Approval,
+                Method::Score => BallotType::Score,
+                Method::Star => BallotType::Star,
+                Method::MajorityJudgment => BallotType::MajorityJudgment,
+                _ => BallotType::Ranking,
+            }
+        }
+
+        pub fn from_code(code: i64) -> Option<Self> {
+            Self::ALL.into_iter().find(|m| m.code() == code)
+        }
+
+        pub fn value(self) -> &'static str {
+            match self {
+                Method::Borda => "borda",
+                Method::InstantRunoff => "instant-runoff",
+                Method::Schulze => "schulze",
+                Method::RankedPairs => "ranked-pairs",
+                Method::SingleTransferableVote => "single-transferable-vote",
+                Method::Approval => "approval",
+                Method::Score => "score",
+                Method::Star => "star",
+                Method::MajorityJudgment => "majority-judgment",
+            }
+        }
+
+        pub fn label(self) -> &'static str {
+            match self {
+                Method::Borda => "BORDA COUNT",
+                Method::InstantRunoff => "INSTANT-RUNOFF VOTING",
+                Method::Schulze => "SCHULZE METHOD",
+                Method::RankedPairs => "RANKED PAIRS",
+                Method::SingleTransferableVote => "SINGLE TRANSFERABLE VOTE",
+                Method::Approval => "APPROVAL VOTING",
+                Method::Score => "SCORE VOTING",
+                Method::Star => "STAR VOTING",
+                Method::MajorityJudgment => "MAJORITY JUDGMENT",
+            }
+        }
+
+        /// What the numbers produced by this method represent.
+        pub fn score_label(self) -> &'static str {
+            match self {
+                Method::Borda => "SCORE",
+                Method::InstantRunoff | Method::SingleTransferableVote => "VOTES",
+                Method::Schulze | Method::RankedPairs => "WINS",
+                Method::Approval => "APPROVALS",
+                Method::Score | Method::Star => "TOTAL SCORE",
+                Method::MajorityJudgment => "MAJORITY GRADE",
+            }
+        }
+
+        /// How options a voter left off a truncated ballot are counted.
+        pub fn unranked_rule(self) -> &'static str {
+            match self {
+                Method::Borda => "UNRANKED OPTIONS GET NO POINTS, AND A BALLOT RANKING N OPTIONS GIVES N POINTS TO ITS FIRST CHOICE.",
+                Method::InstantRunoff | Method::SingleTransferableVote => {
+                    "BALLOTS WITH NO RANKED OPTIONS LEFT IN THE COUNT ARE SET ASIDE AS EXHAUSTED."
+                }
+                Method::Schulze | Method::RankedPairs => {
+                    "RANKED OPTIONS ARE PREFERRED OVER UNRANKED ONES. UNRANKED OPTIONS ARE NOT PREFERRED OVER EACH OTHER."
+                }
+                Method::Approval => "OPTIONS A VOTER DOES NOT APPROVE GET NOTHING FROM THEIR BALLOT.",
+                Method::Score | Method::Star => "EVERY OPTION IS SCORED ON EVERY BALLOT.",
+                Method::MajorityJudgment => "EVERY OPTION IS GRADED ON EVERY BALLOT.",
+            }
+        }
+
+        pub fn tally(self) -> &'static dyn Tally {
+            match self {
+                Method::Borda => &Borda,
+                Method::InstantRunoff => &InstantRunoff,
+                Method::Schulze => &Schulze,
+                Method::RankedPairs => &RankedPairs,
+                Method::SingleTransferableVote => &SingleTransferableVote,
+                Method::Approval => &Approval,
+                Method::Score => &Score,
+                Method::Star => &Star,
+                Method::MajorityJudgment => &MajorityJudgment,
+            }
+        }
+    }
+
+    /// What voters fill in: a ranking of the options, or a rating of each option.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum BallotType {
+        #[default]
+        Ranking,
+        /// Approve any number of options.
+        Approval,
+        /// Score every option from 0 to the room's maximum score.
+        Score,
+        /// Score every option, then the two highest totals go to an automatic runoff.
+        Star,
+        /// Grade every option from [`GRADES`].
+        MajorityJudgment,
+    }
+
+    impl BallotType {
+        pub const ALL: [BallotType; 5] = [
+            BallotType::Ranking,
+            BallotType::Approval,
+            BallotType::Score,
+            BallotType::Star,
+            BallotType::MajorityJudgment,
+        ];
+
+        /// The value stored in the `rooms.ballot_type` column.
+        pub fn code(self) -> i64 {
+            match self {
+                BallotType::Ranking => 0,
+                BallotType::Approval => 1,
+                BallotType::Score => 2,
+                BallotType::Star => 3,
+                BallotType::MajorityJudgment => 4,
+            }
+        }
+
+        pub fn from_code(code: i64) -> Option<Self> {
+            Self::ALL.into_iter().find(|b| b.code() == code)
+        }
+
+        pub fn value(self) -> &'static str {
+            match self {
+                BallotType::Ranking => "ranking",
+                BallotType::Approval => "approval",
+                BallotType::Score => "score",
+                BallotType::Star => "star",
+                BallotType::MajorityJudgment => "majority-judgment",
+            }
+        }
+
+        pub fn label(self) -> &'static str {
+            match self {
+                BallotType::Ranking => "RANKING",
+                BallotType::Approval => "APPROVAL",
+                BallotType::Score => "SCORE",
+                BallotType::Star => "STAR (SCORE THEN AUTOMATIC RUNOFF)",
+                BallotType::MajorityJudgment => "MAJORITY JUDGMENT (GRADES)",
+            }
+        }
+
+        /// The counting method for ballots of this type, if it does not rank options.
+        pub fn method(self) -> Option<Method> {
+            match self {
+                BallotType::Ranking => None,
+                BallotType::Approval => Some(Method::Approval),
+                BallotType::Score => Some(Method::Score),
+                BallotType::Star => Some(Method::Star),
+                BallotType::MajorityJudgment => Some(Method::MajorityJudgment),
+            }
+        }
+    }
+
+    /// The grades of a Majority Judgment ballot, worst first, so a grade is stored as its
+    /// index in this list.
+    pub const GRADES: [&str; 6] = [
+        "REJECT",
+        "POOR",
+        "ACCEPTABLE",
+        "GOOD",
+        "VERY GOOD",
+        "EXCELLENT",
+    ];
+
+    /// How options with equal standing are ordered.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum TieBreak {
+        #[default]
+        Alphabetical,
+        /// A shuffle of the options seeded with the room's published seed.
+        Random,
+        /// More first preferences wins, falling back to alphabetical order.
+        FirstPreferences,
+        /// Ties are reported without being broken. Counts that must pick an option to
+        /// eliminate fall back to alphabetical order.
+        LeaveTied,
+    }
+
+    impl TieBreak {
+        pub const ALL: [TieBreak; 4] = [
+            TieBreak::Alphabetical,
+            TieBreak::Random,
+            TieBreak::FirstPreferences,
+            TieBreak::LeaveTied,
+        ];
+
+        /// The value stored in the `rooms.tie_break` column.
+        pub fn code(self) -> i64 {
+            match self {
+                TieBreak::Alphabetical => 0,
+                TieBreak::Random => 1,
+                TieBreak::FirstPreferences => 2,
+                TieBreak::LeaveTied => 3,
+            }
+        }
+
+        pub fn from_code(code: i64) -> Option<Self> {
+            Self::ALL.into_iter().find(|t| t.code() == code)
+        }
+
+        pub fn value(self) -> &'static str {
+            match self {
+                TieBreak::Alphabetical => "alphabetical",
+                TieBreak::Random => "random",
+                TieBreak::FirstPreferences => "first-preferences",
+                TieBreak::LeaveTied => "leave-tied",
+            }
+        }
+
+        pub fn label(self) -> &'static str {
+            match self {
+                TieBreak::Alphabetical => "ALPHABETICAL ORDER",
+                TieBreak::Random => "RANDOM ORDER",
+                TieBreak::FirstPreferences => "FIRST PREFERENCE COUNT",
+                TieBreak::LeaveTied => "LEAVE TIED",
+            }
+        }
+    }
+
+    /// Groups of options, best first. Options in the same group are ranked equally.
+    pub type Ballot = Vec<Vec<String>>;
+
+    /// The score a voter gave each option. Approvals are scores of 1.
+    pub type Ratings = BTreeMap<String, u32>;
+
+    /// The ranking implied by `ratings`, with equal scores ranked equally.
+    pub fn ranking(ratings: &Ratings) -> Ballot {
+        let mut scores = ratings.values().copied().collect::<Vec<_>>();
+        scores.sort_unstable_by(|a, b| b.cmp(a));
+        scores.dedup();
+
+        scores
+            .into_iter()
+            .map(|score| {
+                ratings
+                    .iter()
+                    .filter(|(_, s)| **s == score)
+                    .map(|(option, _)| option.clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Everything a counting method needs to know about a finished vote.
+    pub struct Election {
+        pub options: Vec<String>,
+        /// Rankings of the options. For rated ballots, the ranking implied by the ratings.
+        pub ballots: Vec<Ballot>,
+        /// The ratings of each voter, empty unless the room uses rated ballots.
+        pub ratings: Vec<Ratings>,
+        pub seats: usize,
+        pub tie_break: TieBreak,
+        /// Seeds the shuffle used by [`TieBreak::Random`].
+        pub seed: u64,
+    }
+
+    impl Election {
+        /// Every option in the order used to break ties, best first.
+        pub fn tie_order(&self) -> Vec<String> {
+            let mut order = self.options.clone();
+            order.sort();
+
+            match self.tie_break {
+                TieBreak::Alphabetical | TieBreak::LeaveTied => {}
+                TieBreak::Random => {
+                    // Fisher-Yates shuffle driven by SplitMix64, so anyone can reproduce it from the seed.
+                    let mut state = self.seed;
+                    for i in (1..order.len()).rev() {
+                        let j = (splitmix64(&mut state) % (i as u64 + 1)) as usize;
+                        order.swap(i, j);
+                    }
+                }
+                TieBreak::FirstPreferences => {
+                    let firsts = |option: &String| {
+                        self.ballots
+                            .iter()
+                            .filter(|b| b.first().is_some_and(|group| group.contains(option)))
+                            .count()
+                    };
+                    order.sort_by_key(|o| std::cmp::Reverse(firsts(o)));
+                }
+            }
+
+            order
+        }
+    }
+
+    fn splitmix64(state: &mut u64) -> u64 {
+        *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Where `option` falls in the tie-break order; lower is better.
+    fn tie_rank(order: &[String], option: &str) -> usize {
+        order
+            .iter()
+            .position(|o| o == option)
+            .unwrap_or(order.len())
+    }
+
+    pub struct Outcome {
+        /// Every option with its final score, best first.
+        pub scores: Vec<(String, f64)>,
+        /// The options that won a seat, in the order they were elected.
+        pub elected: Vec<String>,
+        /// The number of votes needed to be elected, for methods that use one.
+        pub quota: Option<f64>,
+        /// The counts of each round, for methods that eliminate options one round at a time.
+        pub rounds: Vec<Round>,
+        /// Groups of options that could only be told apart by the tie-break policy.
+        pub ties: Vec<Vec<String>>,
+        /// The head-to-head runoff between the finalists, for methods that end with one.
+        pub runoff: Option<Runoff>,
+    }
+
+    #[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+    pub struct Runoff {
+        /// The two finalists with the number of voters preferring each, winner first.
+        pub finalists: [(String, usize); 2],
+        /// Voters who scored both finalists equally.
+        pub no_preference: usize,
+    }
+
+    impl Outcome {
+        /// An outcome for methods that produce a single ranking, where the top `seats` options win
+        /// and options with equal scores are tied.
+        fn ranked(election: &Election, mut scores: Vec<(String, f64)>) -> Self {
+            sort_scores(&mut scores, &election.tie_order());
+            let elected = scores
+                .iter()
+                .take(election.seats)
+                .map(|(o, _)| o.clone())
+                .collect();
+
+            let mut ties = Vec::<Vec<String>>::new();
+            for (idx, (option, score)) in scores.iter().enumerate() {
+                let tied_with_prev = idx > 0 && (scores[idx - 1].1 - score).abs() < EPSILON;
+                match ties.last_mut() {
+                    Some(group) if tied_with_prev && group.contains(&scores[idx - 1].0) => {
+                        group.push(option.clone())
+                    }
+                    _ if tied_with_prev => {
+                        ties.push(vec![scores[idx - 1].0.clone(), option.clone()])
+                    }
+                    _ => {}
+                }
+            }
+
+            Self {
+                scores,
+                elected,
+                quota: None,
+                rounds: Vec::new(),
+                ties,
+                runoff: None,
+            }
+        }
+
+        /// An outcome for methods that count in rounds, where the only ties are those
+        /// between options with the fewest votes in a round.
+        fn runoff(
+            scores: Vec<(String, f64)>,
+            elected: Vec<String>,
+            quota: Option<f64>,
+            rounds: Vec<Round>,
+        ) -> Self {
+            let ties = rounds
+                .iter()
+                .filter(|r| r.tied.len() > 1)
+                .map(|r| r.tied.clone())
+                .collect();
+
+            Self {
+                scores,
+                elected,
+                quota,
+                rounds,
+                ties,
+                runoff: None,
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+    pub struct Round {
+        /// The votes held by each option still in the count, in option order.
+        pub tallies: Vec<(String, f64)>,
+        pub elected: Vec<String>,
+        pub eliminated: Vec<String>,
+        /// Votes on ballots with no remaining options left to transfer to.
+        pub exhausted: f64,
+        /// Where the votes of the options elected or eliminated in this round went.
+        pub transfers: Vec<Transfer>,
+        /// The options that had the fewest votes, when more than one did and an option was eliminated.
+        pub tied: Vec<String>,
+    }
+
+    #[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+    pub struct Transfer {
+        pub from: String,
+        /// `None` when the ballots had no remaining options and were exhausted.
+        pub to: Option<String>,
+        pub votes: f64,
+    }
+
+    /// Turns the ballots of an election into its outcome.
+    pub trait Tally {
+        fn tally(&self, election: &Election) -> Outcome;
+    }
+
+    /// Sorts scores best first, ordering equal scores by the tie-break `order`.
+    fn sort_scores(scores: &mut [(String, f64)], order: &[String]) {
+        scores.sort_by(|(x, a), (y, b)| {
+            b.total_cmp(a)
+                .then_with(|| tie_rank(order, x).cmp(&tie_rank(order, y)))
+        });
+    }
+
+    /// Every ballot gives `n - idx` points to the option at position `idx`, where `n` is the
+    /// number of options on the ballot. Unranked options get no points, and options ranked
+    /// equally share the average of the points for the positions they take up.
+    pub struct Borda;
+
+    impl Tally for Borda {
+        fn tally(&self, election: &Election) -> Outcome {
+            let init = election
+                .options
+                .iter()
+                .map(|o| (o.clone(), 0.0))
+                .collect::<HashMap<String, f64>>();
+
+            let scores = election.ballots.iter().fold(init, |map, ballot| {
+                let ballot_len = ballot.iter().map(Vec::len).sum::<usize>();
+                let mut idx = 0;
+                ballot.iter().fold(map, |mut map, group| {
+                    let curr_score =
+                        ballot_len as f64 - idx as f64 - (group.len() - 1) as f64 / 2.0;
+                    idx += group.len();
+                    for choice in group {
+                        map.entry(choice.clone())
+                            .and_modify(|score| *score += curr_score)
+                            .or_insert(curr_score);
+                    }
+                    map
+                })
+            });
+
+            Outcome::ranked(election, scores.into_iter().collect())
+        }
+    }
+
+    /// Each approval counts as one point.
+    pub struct Approval;
+
+    impl Tally for Approval {
+        fn tally(&self, election: &Election) -> Outcome {
+            Outcome::ranked(election, totals(election, |score| score.min(1)))
+        }
+    }
+
+    /// Every option's score is the sum of the scores voters gave it.
+    pub struct Score;
+
+    impl Tally for Score {
+        fn tally(&self, election: &Election) -> Outcome {
+            Outcome::ranked(election, totals(election, |score| score))
+        }
+    }
+
+    /// Score Then Automatic Runoff: the two options with the highest total scores are
+    /// finalists, and the one scored higher by more voters wins. A runoff with as many
+    /// voters on each side goes to the finalist with the higher total score.
+    ///
+    /// The finalists come first, winner first, followed by the rest by total score.
+    pub struct Star;
+
+    impl Tally for Star {
+        fn tally(&self, election: &Election) -> Outcome {
+            let mut outcome = Outcome::ranked(election, totals(election, |score| score));
+            if outcome.scores.len() < 2 {
+                return outcome;
+            }
+
+            let (a, b) = (outcome.scores[0].0.clone(), outcome.scores[1].0.clone());
+            let (mut prefer_a, mut prefer_b, mut no_preference) = (0, 0, 0);
+            for ratings in &election.ratings {
+                let score = |option: &String| ratings.get(option).copied().unwrap_or(0);
+                match score(&a).cmp(&score(&b)) {
+                    std::cmp::Ordering::Greater => prefer_a += 1,
+                    std::cmp::Ordering::Less => prefer_b += 1,
+                    std::cmp::Ordering::Equal => no_preference += 1,
+                }
+            }
+
+            // The scores are already ordered, so the runner-up only wins the runoff outright.
+            if prefer_b > prefer_a {
+                outcome.scores.swap(0, 1);
+            }
+
+            // A tie on total score between just the finalists is settled by the runoff.
+            if prefer_a != prefer_b {
+                outcome.ties.retain(|group| {
+                    !(group.len() == 2 && group.contains(&a) && group.contains(&b))
+                });
+            }
+
+            outcome.elected = vec![outcome.scores[0].0.clone()];
+            outcome.runoff = Some(Runoff {
+                finalists: match prefer_b > prefer_a {
+                    true => [(b, prefer_b), (a, prefer_a)],
+                    false => [(a, prefer_a), (b, prefer_b)],
+                },
+                no_preference,
+            });
+
+            outcome
+        }
+    }
+
+    /// Majority Judgment: every option gets its median grade, the lower one when there is an
+    /// even number of grades, and the best median wins. Options with the same median are told
+    /// apart by removing one median grade from each and comparing again, until they differ.
+    ///
+    /// Each score is the index of the option's majority grade in [`GRADES`].
+    pub struct MajorityJudgment;
+
+    impl Tally for MajorityJudgment {
+        fn tally(&self, election: &Election) -> Outcome {
+            let order = election.tie_order();
+            let mut values = election
+                .options
+                .iter()
+                .map(|option| {
+                    let mut grades = election
+                        .ratings
+                        .iter()
+                        .map(|ratings| ratings.get(option).copied().unwrap_or(0))
+                        .collect::<Vec<_>>();
+                    grades.sort_unstable();
+                    (option.clone(), majority_values(grades))
+                })
+                .collect::<Vec<_>>();
+
+            values.sort_by(|(x, a), (y, b)| {
+                b.cmp(a)
+                    .then_with(|| tie_rank(&order, x).cmp(&tie_rank(&order, y)))
+            });
+
+            let mut ties = Vec::<Vec<String>>::new();
+            for idx in 1..values.len() {
+                if values[idx - 1].1 != values[idx].1 {
+                    continue;
+                }
+
+                match ties.last_mut() {
+                    Some(group) if group.contains(&values[idx - 1].0) => {
+                        group.push(values[idx].0.clone())
+                    }
+                    _ => ties.push(vec![values[idx - 1].0.clone(), values[idx].0.clone()]),
+                }
+            }
+
+            Outcome {
+                elected: values
+                    .iter()
+                    .take(election.seats)
+                    .map(|(o, _)| o.clone())
+                    .collect(),
+                scores: values
+                    .into_iter()
+                    .map(|(o, v)| (o, v.first().copied().unwrap_or(0) as f64))
+                    .collect(),
+                quota: None,
+                rounds: Vec::new(),
+                ties,
+                runoff: None,
+            }
+        }
+    }
+
+    /// The sequence of lower medians found by repeatedly removing the median from `grades`,
+    /// which must be sorted. Comparing these sequences applies the Majority Judgment tie-break.
+    fn majority_values(mut grades: Vec<u32>) -> Vec<u32> {
+        let mut values = Vec::with_capacity(grades.len());
+        while !grades.is_empty() {
+            values.push(grades.remove((grades.len() - 1) / 2));
+        }
+        values
+    }
+
+    /// How many voters gave each option each grade, indexed like [`GRADES`].
+    pub fn merit_profiles(election: &Election) -> Vec<(String, Vec<usize>)> {
+        election
+            .options
+            .iter()
+            .map(|option| {
+                let mut counts = vec![0; GRADES.len()];
+                for ratings in &election.ratings {
+                    let grade = ratings.get(option).copied().unwrap_or(0) as usize;
+                    counts[grade.min(GRADES.len() - 1)] += 1;
+                }
+                (option.clone(), counts)
+            })
+            .collect()
+    }
+
+    /// The sum of `points(score)` over every voter's ratings, for each option.
+    fn totals(election: &Election, points: impl Fn(u32) -> u32) -> Vec<(String, f64)> {
+        election
+            .options
+            .iter()
+            .map(|option| {
+                let total = election
+                    .ratings
+                    .iter()
+                    .filter_map(|ratings| ratings.get(option))
+                    .map(|score| points(*score) as f64)
+                    .sum();
+                (option.clone(), total)
+            })
+            .collect()
+    }
+
+    /// The options of the highest ranked group on `ballot` that still has `continuing`
+    /// options, leaving out the ones that are not continuing.
+    fn top<'a>(ballot: &'a Ballot, continuing: &[String]) -> Vec<&'a String> {
+        ballot
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .filter(|choice| continuing.contains(choice))
+                    .collect::<Vec<_>>()
+            })
+            .find(|group| !group.is_empty())
+            .unwrap_or_default()
+    }
+
+    /// Counts every ballot, at its weight, for its highest ranked option that is still `continuing`.
+    /// A ballot ranking several such options equally splits its weight between them.
+    ///
+    /// Returns the tally of each continuing option, in option order, and the exhausted weight.
+    fn count(
+        ballots: &[Ballot],
+        weights: &[f64],
+        continuing: &[String],
+    ) -> (Vec<(String, f64)>, f64) {
+        let mut tallies = continuing
+            .iter()
+            .map(|o| (o.clone(), 0.0))
+            .collect::<Vec<_>>();
+        let mut exhausted = 0.0;
+
+        for (ballot, weight) in ballots.iter().zip(weights) {
+            let top = top(ballot, continuing);
+            if top.is_empty() {
+                exhausted += weight;
+            }
+
+            for choice in &top {
+                if let Some((_, votes)) = tallies.iter_mut().find(|(option, _)| option == *choice) {
+                    *votes += weight / top.len() as f64;
+                }
+            }
+        }
+
+        (tallies, exhausted)
+    }
+
+    /// Follows the ballots of every option in `before` but not in `after` to their next
+    /// choice in `after`, at their current weight.
+    fn transfers(
+        ballots: &[Ballot],
+        weights: &[f64],
+        before: &[String],
+        after: &[String],
+    ) -> Vec<Transfer> {
+        let mut transfers = Vec::<Transfer>::new();
+
+        for (ballot, weight) in ballots.iter().zip(weights) {
+            if *weight <= EPSILON {
+                continue;
+            }
+
+            let from = top(ballot, before);
+            let to = top(ballot, after);
+            let share = weight / from.len() as f64;
+
+            for from in from.iter().filter(|choice| !after.contains(choice)) {
+                let targets = match to.is_empty() {
+                    true => vec![None],
+                    false => to.iter().map(|choice| Some((*choice).clone())).collect(),
+                };
+                let votes = share / targets.len() as f64;
+
+                for to in targets {
+                    match transfers
+                        .iter_mut()
+                        .find(|t| t.from == **from && t.to == to)
+                    {
+                        Some(transfer) => transfer.votes += votes,
+                        None => transfers.push(Transfer {
+                            from: (*from).clone(),
+                            to,
+                            votes,
+                        }),
+                    }
+                }
+            }
+        }
+
+        transfers
+    }
+
+    /// The option with the fewest votes, picking the last in the tie-break `order` between
+    /// equals, along with every option that shared the fewest votes.
+    fn lowest(tallies: &[(String, f64)], order: &[String]) -> ((String, f64), Vec<String>) {
+        let fewest = tallies
+            .iter()
+            .map(|(_, votes)| *votes)
+            .fold(f64::INFINITY, f64::min);
+
+        let tied = tallies
+            .iter()
+            .filter(|(_, votes)| *votes - fewest < EPSILON)
+            .map(|(option, _)| option.clone())
+            .collect::<Vec<_>>();
+
+        let loser = tallies
+            .iter()
+            .filter(|(option, _)| tied.contains(option))
+            .max_by_key(|(option, _)| tie_rank(order, option))
+            .cloned()
+            .unwrap();
+
+        (loser, tied)
+    }
+
+    /// Repeatedly eliminates the option with the fewest first preferences and transfers its
+    /// ballots to their next remaining choice until one option holds a majority.
+    ///
+    /// The winner comes first, followed by the other options in reverse order of elimination.
+    /// Each score is the number of votes the option held in the last round it took part in.
+    pub struct InstantRunoff;
+
+    impl Tally for InstantRunoff {
+        fn tally(&self, election: &Election) -> Outcome {
+            let order = election.tie_order();
+            let weights = vec![1.0; election.ballots.len()];
+            let mut continuing = election.options.clone();
+            let mut eliminated = Vec::new();
+            let mut rounds = Vec::new();
+
+            loop {
+                let (mut tallies, exhausted) = count(&election.ballots, &weights, &continuing);
+
+                let total = tallies.iter().map(|(_, v)| v).sum::<f64>();
+                let leader = tallies.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+
+                if tallies.len() <= 1 || leader * 2.0 > total {
+                    let mut round = Round {
+                        tallies: tallies.clone(),
+                        elected: Vec::new(),
+                        eliminated: Vec::new(),
+                        exhausted,
+                        transfers: Vec::new(),
+                        tied: Vec::new(),
+                    };
+
+                    sort_scores(&mut tallies, &order);
+                    let elected = tallies
+                        .iter()
+                        .take(1)
+                        .map(|(o, _)| o.clone())
+                        .collect::<Vec<_>>();
+                    round.elected.clone_from(&elected);
+                    rounds.push(round);
+
+                    eliminated.reverse();
+                    tallies.extend(eliminated);
+
+                    return Outcome::runoff(tallies, elected, None, rounds);
+                }
+
+                let (loser, tied) = lowest(&tallies, &order);
+                let before = std::mem::take(&mut continuing);
+                continuing = before.iter().filter(|o| **o != loser.0).cloned().collect();
+
+                rounds.push(Round {
+                    tallies,
+                    elected: Vec::new(),
+                    eliminated: vec![loser.0.clone()],
+                    exhausted,
+                    transfers: transfers(&election.ballots, &weights, &before, &continuing),
+                    tied: if tied.len() > 1 { tied } else { Vec::new() },
+                });
+                eliminated.push(loser);
+            }
+        }
+    }
+
+    /// Fills `seats` seats using the Droop quota. Options reaching the quota are elected and
+    /// their surplus is passed on at a fractional weight (the Gregory method), one surplus per
+    /// round, largest first; otherwise the option with the fewest votes is eliminated and its
+    /// ballots are passed on at full weight.
+    ///
+    /// The elected options come first in the order they were elected, followed by the rest
+    /// in reverse order of elimination. Each score is the number of votes the option held in
+    /// the last round it took part in.
+    pub struct SingleTransferableVote;
+
+    impl Tally for SingleTransferableVote {
+        fn tally(&self, election: &Election) -> Outcome {
+            let seats = election.seats.clamp(1, election.options.len().max(1));
+            let quota = (election.ballots.len() / (seats + 1) + 1) as f64;
+            let order = election.tie_order();
+
+            let mut weights = vec![1.0; election.ballots.len()];
+            let mut continuing = election.options.clone();
+            let mut elected = Vec::<(String, f64)>::new();
+            let mut eliminated = Vec::<(String, f64)>::new();
+            let mut rounds = Vec::new();
+            let mut last = Vec::new();
+
+            while elected.len() < seats && !continuing.is_empty() {
+                let (tallies, exhausted) = count(&election.ballots, &weights, &continuing);
+                let mut round = Round {
+                    tallies: tallies.clone(),
+                    elected: Vec::new(),
+                    eliminated: Vec::new(),
+                    exhausted,
+                    transfers: Vec::new(),
+                    tied: Vec::new(),
+                };
+                last.clone_from(&tallies);
+                let before = continuing.clone();
+
+                // Once there are no more options left than seats to fill, they are all elected.
+                let fill = elected.len() + continuing.len() <= seats;
+                let mut winners = if fill {
+                    tallies.clone()
+                } else {
+                    tallies
+                        .iter()
+                        .filter(|(_, votes)| *votes + EPSILON >= quota)
+                        .cloned()
+                        .collect::<Vec<_>>()
+                };
+
+                if winners.is_empty() {
+                    let (loser, tied) = lowest(&tallies, &order);
+                    continuing.retain(|o| *o != loser.0);
+                    round.eliminated.push(loser.0.clone());
+                    if tied.len() > 1 {
+                        round.tied = tied;
+                    }
+                    eliminated.push(loser);
+                } else {
+                    sort_scores(&mut winners, &order);
+
+                    // A surplus can push other options further over the quota, so only the
+                    // largest is transferred before counting again.
+                    winners.truncate(if fill { seats - elected.len() } else { 1 });
+
+                    for (winner, votes) in &winners {
+                        let transfer = ((votes - quota) / votes).max(0.0);
+                        for (ballot, weight) in election.ballots.iter().zip(weights.iter_mut()) {
+                            // Only the share of the ballot held by the winner is reduced.
+                            let top = top(ballot, &continuing);
+                            if top.contains(&winner) {
+                                *weight *= 1.0 - (1.0 - transfer) / top.len() as f64;
+                            }
+                        }
+                    }
+
+                    continuing.retain(|o| winners.iter().all(|(w, _)| w != o));
+                    round.elected = winners.iter().map(|(o, _)| o.clone()).collect();
+                    elected.extend(winners);
+                }
+
+                if elected.len() < seats && !continuing.is_empty() {
+                    round.transfers = transfers(&election.ballots, &weights, &before, &continuing);
+                }
+
+                rounds.push(round);
+            }
+
+            let mut remaining = last
+                .into_iter()
+                .filter(|(o, _)| continuing.contains(o))
+                .collect::<Vec<_>>();
+            sort_scores(&mut remaining, &order);
+            eliminated.reverse();
+
+            Outcome::runoff(
+                elected
+                    .iter()
+                    .cloned()
+                    .chain(remaining)
+                    .chain(eliminated)
+                    .collect(),
+                elected.into_iter().map(|(o, _)| o).collect(),
+                Some(quota),
+                rounds,
+            )
+        }
+    }
+
+    /// Head-to-head results between every pair of options.
+    ///
+    /// `prefs[i][j]` is the number of ballots ranking `options[i]` above `options[j]`, where
+    /// a ranked option is above every unranked one. Equally ranked options are not preferred
+    /// over each other.
+    #[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+    pub struct Pairwise {
+        pub options: Vec<String>,
+        pub prefs: Vec<Vec<usize>>,
+    }
+
+    impl Pairwise {
+        pub fn new(election: &Election) -> Self {
+            let options = &election.options;
+            let n = options.len();
+            let mut prefs = vec![vec![0; n]; n];
+
+            for ballot in &election.ballots {
+                let ranks = options
+                    .iter()
+                    .map(|o| ballot.iter().position(|group| group.contains(o)))
+                    .collect::<Vec<_>>();
+
+                for i in 0..n {
+                    for j in 0..n {
+                        // Ranked options are preferred over unranked ones.
+                        let prefers = match (ranks[i], ranks[j]) {
+                            (Some(ri), Some(rj)) => ri < rj,
+                            (Some(_), None) => true,
+                            (None, _) => false,
+                        };
+
+                        if prefers {
+                            prefs[i][j] += 1;
+                        }
+                    }
+                }
+            }
+
+            Self {
+                options: options.to_vec(),
+                prefs,
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.options.len()
+        }
+
+        pub fn beats(&self, i: usize, j: usize) -> bool {
+            self.prefs[i][j] > self.prefs[j][i]
+        }
+
+        /// Groups of options that beat each other in a circle, e.g. `A > B > C > A`.
+        ///
+        /// Each cycle is returned as the path around it, starting and ending with the same option.
+        pub fn cycles(&self) -> Vec<Vec<String>> {
+            let n = self.len();
+            let reach = closure(n, |i, j| self.beats(i, j));
+
+            let mut seen = vec![false; n];
+            let mut cycles = Vec::new();
+
+            for start in 0..n {
+                if seen[start] || !reach[start][start] {
+                    continue;
+                }
+
+                let component = (0..n)
+                    .filter(|&k| reach[start][k] && reach[k][start])
+                    .collect::<Vec<_>>();
+                for &k in &component {
+                    seen[k] = true;
+                }
+
+                // Walk back from `start` to itself along the shortest chain of wins.
+                let mut prev = vec![None; n];
+                let mut queue = VecDeque::from([start]);
+                while let Some(curr) = queue.pop_front() {
+                    for &k in &component {
+                        if self.beats(curr, k) && prev[k].is_none() {
+                            prev[k] = Some(curr);
+                            queue.push_back(k);
+                        }
+                    }
+                }
+
+                let mut path = vec![start];
+                let mut curr = prev[start].unwrap();
+                while curr != start {
+                    path.push(curr);
+                    curr = prev[curr].unwrap();
+                }
+                path.push(start);
+                path.reverse();
+
+                cycles.push(path.into_iter().map(|k| self.options[k].clone()).collect());
+            }
+
+            cycles
+        }
+    }
+
+    /// Transitive closure of the relation `edge` over `n` nodes.
+    fn closure(n: usize, edge: impl Fn(usize, usize) -> bool) -> Vec<Vec<bool>> {
+        let mut reach = (0..n)
+            .map(|i| (0..n).map(|j| edge(i, j)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        for k in 0..n {
+            for i in 0..n {
+                for j in 0..n {
+                    if reach[i][k] && reach[k][j] {
+                        reach[i][j] = true;
+                    }
+                }
+            }
+        }
+
+        reach
+    }
+
+    /// Orders options by `wins[i]`, best first.
+    fn rank_by_wins(election: &Election, wins: Vec<usize>) -> Outcome {
+        let scores = election
+            .options
+            .iter()
+            .cloned()
+            .zip(wins.into_iter().map(|w| w as f64))
+            .collect::<Vec<_>>();
+
+        Outcome::ranked(election, scores)
+    }
+
+    /// Compares options through the strongest path of pairwise wins between them.
+    ///
+    /// Each score is the number of options beaten by strongest path.
+    pub struct Schulze;
+
+    impl Tally for Schulze {
+        fn tally(&self, election: &Election) -> Outcome {
+            let pairwise = Pairwise::new(election);
+            let n = pairwise.len();
+
+            let mut paths = (0..n)
+                .map(|i| {
+                    (0..n)
+                        .map(|j| {
+                            if pairwise.beats(i, j) {
+                                pairwise.prefs[i][j]
+                            } else {
+                                0
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            for k in 0..n {
+                for i in 0..n {
+                    for j in 0..n {
+                        if i != j && i != k && j != k {
+                            paths[i][j] = paths[i][j].max(paths[i][k].min(paths[k][j]));
+                        }
+                    }
+                }
+            }
+
+            let wins = (0..n)
+                .map(|i| (0..n).filter(|&j| paths[i][j] > paths[j][i]).count())
+                .collect();
+
+            rank_by_wins(election, wins)
+        }
+    }
+
+    /// Locks in pairwise wins from the largest to the smallest, skipping any that would
+    /// create a cycle with those already locked. Wins of equal strength are locked in the
+    /// tie-break order of their winners, then of their losers from last to first.
+    ///
+    /// Each score is the number of options the option is locked above.
+    pub struct RankedPairs;
+
+    impl Tally for RankedPairs {
+        fn tally(&self, election: &Election) -> Outcome {
+            let pairwise = Pairwise::new(election);
+            let n = pairwise.len();
+            let order = election.tie_order();
+            let rank = |i: usize| tie_rank(&order, &pairwise.options[i]);
+
+            let mut pairs = (0..n)
+                .flat_map(|i| (0..n).map(move |j| (i, j)))
+                .filter(|&(i, j)| pairwise.beats(i, j))
+                .collect::<Vec<_>>();
+            pairs.sort_by_key(|&(i, j)| {
+                let (win, lose) = (pairwise.prefs[i][j], pairwise.prefs[j][i]);
+                (
+                    std::cmp::Reverse((win, win - lose)),
+                    rank(i),
+                    std::cmp::Reverse(rank(j)),
+                )
+            });
+
+            let mut locked = vec![vec![false; n]; n];
+            for (i, j) in pairs {
+                locked[i][j] = true;
+                if closure(n, |a, b| locked[a][b])[j][i] {
+                    locked[i][j] = false;
+                }
+            }
+
+            let reach = closure(n, |a, b| locked[a][b]);
+            let wins = reach
+                .iter()
+                .map(|row| row.iter().filter(|&&r| r).count())
+                .collect();
+
+            rank_by_wins(election, wins)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{
+            majority_values, merit_profiles, ranking, Approval, Ballot, Borda, Election,
+            InstantRunoff, MajorityJudgment, Outcome, Pairwise, RankedPairs, Ratings, Schulze,
+            Score, SingleTransferableVote, Star, Tally, TieBreak,
+        };
+
+        /// A ranking written as `A>B=C`, best first, with `=` between equal options.
+        fn ballot(ranking: &str) -> Ballot {
+            ranking
+                .split('>')
+                .filter(|group| !group.is_empty())
+                .map(|group| group.split('=').map(str::to_owned).collect())
+                .collect()
+        }
+
+        /// An election filling one seat, with each ranking cast as many times as its count.
+        fn election(options: &str, ballots: &[(usize, &str)]) -> Election {
+            Election {
+                options: options.split(' ').map(str::to_owned).collect(),
+                ballots: ballots
+                    .iter()
+                    .flat_map(|(count, ranking)| std::iter::repeat_n(ballot(ranking), *count))
+                    .collect(),
+                ratings: Vec::new(),
+                seats: 1,
+                tie_break: TieBreak::Alphabetical,
+                seed: 0,
+            }
+        }
+
+        /// An election filling one seat with rated ballots written as `A:5 B:0`, each cast as
+        /// many times as its count.
+        fn rated(options: &str, ballots: &[(usize, &str)]) -> Election {
+            let ratings = ballots
+                .iter()
+                .flat_map(|(count, scores)| {
+                    let ratings = scores
+                        .split(' ')
+                        .map(|rating| {
+                            let (option, score) = rating.split_once(':').unwrap();
+                            (option.to_owned(), score.parse().unwrap())
+                        })
+                        .collect::<Ratings>();
+                    std::iter::repeat_n(ratings, *count)
+                })
+                .collect::<Vec<_>>();
+
+            Election {
+                ballots: ratings.iter().map(ranking).collect(),
+                ratings,
+                ..election(options, &[])
+            }
+        }
+
+        fn scores(outcome: &Outcome) -> Vec<(&str, f64)> {
+            outcome
+                .scores
+                .iter()
+                .map(|(option, score)| (option.as_str(), *score))
+                .collect()
+        }
+
+        #[test]
+        fn borda_gives_points_by_position() {
+            // Truncated ballots give points for the options they rank, and equal options
+            // share the points of the positions they take up.
+            let election = election(
+                "A B C",
+                &[(2, "A>B>C"), (1, "B>C>A"), (1, "C>B"), (1, "A=B>C")],
+            );
+            let outcome = Borda.tally(&election);
+
+            assert_eq!(scores(&outcome), [("B", 10.5), ("A", 9.5), ("C", 7.0)]);
+            assert_eq!(outcome.elected, ["B"]);
+            assert!(outcome.ties.is_empty());
+        }
+
+        #[test]
+        fn borda_flags_equal_scores() {
+            let election = election("A B C", &[(1, "A>B>C"), (1, "B>A>C")]);
+            let outcome = Borda.tally(&election);
+
+            assert_eq!(scores(&outcome), [("A", 5.0), ("B", 5.0), ("C", 2.0)]);
+            assert_eq!(outcome.ties, [["A", "B"]]);
+        }
+
+        #[test]
+        fn instant_runoff_eliminates_until_a_majority() {
+            let election = election(
+                "A B C D",
+                &[(4, "A>B"), (3, "B>C"), (2, "C>B"), (1, "D"), (2, "D>C")],
+            );
+            let outcome = InstantRunoff.tally(&election);
+
+            let eliminated = outcome
+                .rounds
+                .iter()
+                .map(|round| round.eliminated.clone())
+                .collect::<Vec<_>>();
+            assert_eq!(eliminated, [vec!["C"], vec!["D"], vec![]]);
+            assert_eq!(outcome.elected, ["B"]);
+            assert_eq!(
+                scores(&outcome),
+                [("B", 5.0), ("A", 4.0), ("D", 3.0), ("C", 2.0)]
+            );
+
+            // C's ballots move to B, then D's run out of continuing options.
+            let first = &outcome.rounds[0].transfers;
+            assert_eq!(first.len(), 1);
+            assert_eq!((first[0].to.as_deref(), first[0].votes), (Some("B"), 2.0));
+            let second = &outcome.rounds[1].transfers;
+            assert_eq!(second.len(), 1);
+            assert_eq!((second[0].to.as_deref(), second[0].votes), (None, 3.0));
+
+            let exhausted = outcome
+                .rounds
+                .iter()
+                .map(|round| round.exhausted)
+                .collect::<Vec<_>>();
+            assert_eq!(exhausted, [0.0, 0.0, 3.0]);
+        }
+
+        #[test]
+        fn instant_runoff_needs_a_majority_of_continuing_ballots() {
+            // Once exhausted ballots are set aside, 3 of 5 remaining votes are a majority.
+            let election = election("A B C", &[(3, "A"), (2, "B"), (2, "C")]);
+            let outcome = InstantRunoff.tally(&election);
+
+            assert_eq!(outcome.rounds.len(), 2);
+            assert_eq!(outcome.rounds[0].eliminated, ["C"]);
+            assert_eq!(outcome.rounds[0].tied, ["B", "C"]);
+            assert_eq!(outcome.rounds[1].exhausted, 2.0);
+            assert_eq!(outcome.elected, ["A"]);
+        }
+
+        #[test]
+        fn tie_order_follows_the_policy() {
+            let mut election = election("C A B D", &[(1, "D>A"), (2, "B"), (1, "D")]);
+            assert_eq!(election.tie_order(), ["A", "B", "C", "D"]);
+
+            election.tie_break = TieBreak::LeaveTied;
+            assert_eq!(election.tie_order(), ["A", "B", "C", "D"]);
+
+            // Equal first preference counts fall back to alphabetical order.
+            election.tie_break = TieBreak::FirstPreferences;
+            assert_eq!(election.tie_order(), ["B", "D", "A", "C"]);
+
+            // A shuffle anyone can repeat from the seed, whatever order the options are in.
+            election.tie_break = TieBreak::Random;
+            let orders = (0..8)
+                .map(|seed| {
+                    election.seed = seed;
+                    election.tie_order()
+                })
+                .collect::<Vec<_>>();
+            for (seed, order) in orders.iter().enumerate() {
+                let mut sorted = order.clone();
+                sorted.sort();
+                assert_eq!(sorted, ["A", "B", "C", "D"]);
+
+                election.seed = seed as u64;
+                election.options.reverse();
+                assert_eq!(election.tie_order(), *order);
+            }
+            assert!(orders.iter().any(|order| *order != orders[0]));
+        }
+
+        #[test]
+        fn ties_are_broken_by_the_policy_and_flagged() {
+            // A and B both score 2, but B is the first choice of more voters.
+            let mut election = election("A B C", &[(2, "B"), (1, "A>C")]);
+
+            let outcome = Borda.tally(&election);
+            assert_eq!(scores(&outcome), [("A", 2.0), ("B", 2.0), ("C", 1.0)]);
+            assert_eq!(outcome.elected, ["A"]);
+            assert_eq!(outcome.ties, [["A", "B"]]);
+
+            election.tie_break = TieBreak::FirstPreferences;
+            let outcome = Borda.tally(&election);
+            assert_eq!(scores(&outcome), [("B", 2.0), ("A", 2.0), ("C", 1.0)]);
+            assert_eq!(outcome.elected, ["B"]);
+            assert_eq!(outcome.ties, [["B", "A"]]);
+        }
+
+        #[test]
+        fn instant_runoff_eliminates_the_last_of_the_tied() {
+            let ballots = [(2, "A"), (1, "B>A"), (1, "C>A")];
+
+            let mut election = election("A B C", &ballots);
+            let outcome = InstantRunoff.tally(&election);
+            assert_eq!(outcome.rounds[0].eliminated, ["C"]);
+            assert_eq!(outcome.ties, [["B", "C"]]);
+
+            // Random order eliminates whichever tied option comes last in the shuffle.
+            election.tie_break = TieBreak::Random;
+            for seed in 0..8 {
+                election.seed = seed;
+                let order = election.tie_order();
+                let last = order.iter().rev().find(|o| *o != "A").unwrap();
+                let outcome = InstantRunoff.tally(&election);
+                assert_eq!(outcome.rounds[0].eliminated, [last.as_str()]);
+            }
+        }
+
+        #[test]
+        fn pairwise_finds_condorcet_cycles() {
+            let pairwise = Pairwise::new(&election(
+                "A B C",
+                &[(4, "A>B>C"), (3, "B>C>A"), (2, "C>A>B")],
+            ));
+
+            assert_eq!(pairwise.prefs, [[0, 6, 4], [3, 0, 7], [5, 2, 0]]);
+            assert!(pairwise.beats(0, 1) && pairwise.beats(1, 2) && pairwise.beats(2, 0));
+            assert_eq!(pairwise.cycles(), [["A", "B", "C", "A"]]);
+
+            // Unranked options lose to ranked ones and don't beat each other.
+            let pairwise = Pairwise::new(&election("A B C", &[(2, "A")]));
+            assert_eq!(pairwise.prefs, [[0, 2, 2], [0, 0, 0], [0, 0, 0]]);
+            assert!(pairwise.cycles().is_empty());
+        }
+
+        #[test]
+        fn condorcet_methods_resolve_a_cycle() {
+            // A beats B 6-3, B beats C 7-2 and C beats A 5-4, so the weakest win, C over A,
+            // gives way.
+            let election = election("A B C", &[(4, "A>B>C"), (3, "B>C>A"), (2, "C>A>B")]);
+
+            for outcome in [Schulze.tally(&election), RankedPairs.tally(&election)] {
+                assert_eq!(scores(&outcome), [("A", 2.0), ("B", 1.0), ("C", 0.0)]);
+                assert_eq!(outcome.elected, ["A"]);
+            }
+        }
+
+        #[test]
+        fn condorcet_methods_elect_the_condorcet_winner() {
+            // B beats both others head to head without holding a majority of first choices.
+            let election = election("A B C", &[(3, "A>B>C"), (2, "B>C>A"), (2, "C>B>A")]);
+
+            for outcome in [Schulze.tally(&election), RankedPairs.tally(&election)] {
+                assert_eq!(outcome.elected, ["B"]);
+                assert_eq!(outcome.scores[0], ("B".to_owned(), 2.0));
+            }
+        }
+
+        #[test]
+        fn ranked_pairs_breaks_equal_wins_by_the_tie_break() {
+            // Every win is 2-1, so only the tie-break decides which one gives way.
+            let ballots = [(1, "A>B>C"), (1, "B>C>A"), (1, "C>A>B")];
+            assert_eq!(
+                RankedPairs.tally(&election("C B A", &ballots)).elected,
+                ["A"]
+            );
+
+            for seed in 0..8 {
+                let outcomes = ["A B C", "C B A", "B A C"].map(|options| {
+                    let mut election = election(options, &ballots);
+                    election.tie_break = TieBreak::Random;
+                    election.seed = seed;
+                    RankedPairs.tally(&election).elected
+                });
+                assert!(outcomes.iter().all(|elected| *elected == outcomes[0]));
+            }
+        }
+
+        #[test]
+        fn single_transferable_vote_transfers_each_surplus() {
+            // A and B both start at the quota of 6. A's surplus of 2 reaches B first, so B's
+            // surplus is 2 as well and is shared by every ballot B holds.
+            let mut election = election("A B C D", &[(8, "A>B>D"), (6, "B>C"), (2, "C"), (4, "D")]);
+            election.seats = 3;
+            let outcome = SingleTransferableVote.tally(&election);
+
+            assert_eq!(outcome.quota, Some(6.0));
+            let elected = outcome
+                .rounds
+                .iter()
+                .map(|round| round.elected.clone())
+                .collect::<Vec<_>>();
+            assert_eq!(elected, [vec!["A"], vec!["B"], vec![], vec!["D"]]);
+            assert_eq!(outcome.rounds[2].eliminated, ["C"]);
+            assert_eq!(
+                outcome.rounds[1].tallies,
+                [
+                    ("B".to_owned(), 8.0),
+                    ("C".to_owned(), 2.0),
+                    ("D".to_owned(), 4.0)
+                ]
+            );
+            assert_eq!(
+                outcome.rounds[2].tallies,
+                [("C".to_owned(), 3.5), ("D".to_owned(), 4.5)]
+            );
+            assert_eq!(outcome.elected, ["A", "B", "D"]);
+            assert_eq!(
+                scores(&outcome),
+                [("A", 8.0), ("B", 8.0), ("D", 4.5), ("C", 3.5)]
+            );
+        }
+
+        #[test]
+        fn single_transferable_vote_fills_the_last_seats() {
+            // Once as many options remain as seats, they are elected below the quota.
+            let mut election = election("A B C D", &[(5, "A"), (2, "B"), (1, "C"), (1, "D")]);
+            election.seats = 3;
+            let outcome = SingleTransferableVote.tally(&election);
+
+            assert_eq!(outcome.quota, Some(3.0));
+            assert_eq!(outcome.rounds.len(), 3);
+            assert_eq!(outcome.rounds[1].eliminated, ["D"]);
+            assert_eq!(outcome.rounds[1].tied, ["C", "D"]);
+            assert_eq!(outcome.rounds[2].elected, ["B", "C"]);
+            assert_eq!(outcome.elected, ["A", "B", "C"]);
+
+            // A's surplus has nowhere to go, and neither do D's ballots.
+            let exhausted = outcome
+                .rounds
+                .iter()
+                .map(|round| round.exhausted)
+                .collect::<Vec<_>>();
+            assert_eq!(exhausted, [0.0, 2.0, 3.0]);
+        }
+
+        #[test]
+        fn approval_counts_each_approval_once() {
+            let election = rated(
+                "A B C",
+                &[(2, "A:1 B:1 C:0"), (1, "A:0 B:1 C:0"), (1, "A:0 B:0 C:1")],
+            );
+            let outcome = Approval.tally(&election);
+
+            assert_eq!(scores(&outcome), [("B", 3.0), ("A", 2.0), ("C", 1.0)]);
+            assert_eq!(outcome.elected, ["B"]);
+        }
+
+        #[test]
+        fn score_sums_the_scores() {
+            let election = rated("A B C", &[(2, "A:3 B:1 C:0"), (1, "A:0 B:5 C:2")]);
+            let outcome = Score.tally(&election);
+
+            assert_eq!(scores(&outcome), [("B", 7.0), ("A", 6.0), ("C", 2.0)]);
+            assert_eq!(outcome.elected, ["B"]);
+            assert!(outcome.runoff.is_none());
+        }
+
+        #[test]
+        fn star_runoff_can_elect_the_runner_up() {
+            // A has the highest total, but more voters score B above A.
+            let election = rated(
+                "A B C",
+                &[(1, "A:5 B:0 C:0"), (2, "A:2 B:3 C:0"), (1, "A:1 B:1 C:5")],
+            );
+            let outcome = Star.tally(&election);
+
+            assert_eq!(scores(&outcome), [("B", 7.0), ("A", 10.0), ("C", 5.0)]);
+            assert_eq!(outcome.elected, ["B"]);
+            let runoff = outcome.runoff.unwrap();
+            assert_eq!(runoff.finalists, [("B".to_owned(), 2), ("A".to_owned(), 1)]);
+            assert_eq!(runoff.no_preference, 1);
+        }
+
+        #[test]
+        fn star_runoff_settles_equal_totals() {
+            let election = rated("A B", &[(1, "A:4 B:0"), (2, "A:0 B:2")]);
+            let outcome = Star.tally(&election);
+
+            assert_eq!(scores(&outcome), [("B", 4.0), ("A", 4.0)]);
+            assert_eq!(outcome.elected, ["B"]);
+            assert!(outcome.ties.is_empty());
+
+            // With as many voters on each side, the higher total wins the runoff.
+            let election = rated("A B", &[(1, "A:5 B:0"), (1, "A:0 B:3")]);
+            let outcome = Star.tally(&election);
+
+            assert_eq!(outcome.elected, ["A"]);
+            let runoff = outcome.runoff.unwrap();
+            assert_eq!(runoff.finalists, [("A".to_owned(), 1), ("B".to_owned(), 1)]);
+        }
+
+        #[test]
+        fn majority_values_remove_one_median_at_a_time() {
+            assert_eq!(majority_values(vec![1, 2, 2, 4]), [2, 2, 1, 4]);
+            assert_eq!(majority_values(vec![0, 2, 2, 3]), [2, 2, 0, 3]);
+            assert_eq!(majority_values(vec![0, 3, 5]), [3, 0, 5]);
+            assert!(majority_values(Vec::new()).is_empty());
+        }
+
+        #[test]
+        fn majority_judgment_breaks_equal_medians() {
+            // A and B share the median grade 2, so medians are removed until A's 1 beats B's 0.
+            let election = rated(
+                "A B C",
+                &[
+                    (1, "A:1 B:2 C:0"),
+                    (1, "A:2 B:2 C:0"),
+                    (1, "A:2 B:3 C:1"),
+                    (1, "A:4 B:0 C:0"),
+                ],
+            );
+            let outcome = MajorityJudgment.tally(&election);
+
+            assert_eq!(scores(&outcome), [("A", 2.0), ("B", 2.0), ("C", 0.0)]);
+            assert_eq!(outcome.elected, ["A"]);
+            assert!(outcome.ties.is_empty());
+
+            assert_eq!(
+                merit_profiles(&election),
+                [
+                    ("A".to_owned(), vec![0, 1, 2, 0, 1, 0]),
+                    ("B".to_owned(), vec![1, 0, 2, 1, 0, 0]),
+                    ("C".to_owned(), vec![3, 1, 0, 0, 0, 0]),
+                ]
+            );
+        }
+
+        #[test]
+        fn majority_judgment_flags_equal_grades() {
+            let mut election = rated("A B", &[(1, "A:5 B:1"), (1, "A:1 B:5")]);
+            election.tie_break = TieBreak::FirstPreferences;
+            let outcome = MajorityJudgment.tally(&election);
+
+            assert_eq!(scores(&outcome), [("A", 1.0), ("B", 1.0)]);
+            assert_eq!(outcome.ties, [["A", "B"]]);
+        }
+    }
+}
+
+mod store {
+    use std::sync::Arc;
+
+    use futures_util::future::BoxFuture;
+    use serde::{Deserialize, Serialize};
+    use sqlx::{migrate::MigrateDatabase, FromRow, Pool};
+    use utoipa::ToSchema;
+
+    /// `rooms.status` of a room waiting for its vote to start.
+    pub const WAITING: i64 = 0;
+    /// `rooms.status` of a room whose vote has started.
+    pub const STARTED: i64 = 1;
+    /// `rooms.status` of a room whose vote has ended.
+    pub const ENDED: i64 = 2;
+
+    /// The storage shared by every handler.
+    pub type Db = Arc<dyn Store>;
+
+    /// Connects to the database at `url`, creating it if it doesn't exist yet, and runs its
+    /// migrations. The scheme of the URL picks the backend: `sqlite:` or `postgres:`.
+    pub async fn connect(url: &str) -> color_eyre::Result<Db> {
+        if url.starts_with("postgres:") || url.starts_with("postgresql:") {
+            if !sqlx::Postgres::database_exists(url).await? {
+                sqlx::Postgres::create_database(url).await?;
+            }
+
+            let pool = Pool::connect(url).await?;
+            sqlx::migrate!("./migrations/postgres").run(&pool).await?;
+
+            Ok(Arc::new(Postgres { pool }))
+        } else if url.starts_with("sqlite:") {
+            if !sqlx::Sqlite::database_exists(url).await.unwrap_or(false) {
+                sqlx::Sqlite::create_database(url).await?;
+            }
+
+            let pool = Pool::connect(url).await?;
+            sqlx::migrate!("./migrations/sqlite").run(&pool).await?;
+
+            Ok(Arc::new(Sqlite { pool }))
+        } else {
+            color_eyre::eyre::bail!("Unsupported database URL. Use a `sqlite:` or `postgres:` URL.")
+        }
+    }
+
+    pub struct Counts {
+        pub rooms: i32,
+        pub voters: i32,
+    }
+
+    #[derive(FromRow)]
+    pub struct Room {
+        pub id: i64,
+        pub name: String,
+        pub admin_code: String,
+        pub status: i64,
+        pub method: i64,
+        pub seats: i64,
+        pub tie_break: i64,
+        pub seed: i64,
+        pub partial: bool,
+        pub max_ranks: Option<i64>,
+        pub ballot_type: i64,
+        pub max_score: i64,
+        pub visibility: i64,
+        pub live: bool,
+        /// Seconds until the room is deleted.
+        pub expires_in: i64,
+    }
+
+    impl Room {
+        /// The room if its vote is at `status`, or `RowNotFound` like a query filtering on it.
+        pub fn in_status(self, status: i64) -> Result<Self, sqlx::Error> {
+            if self.status == status {
+                Ok(self)
+            } else {
+                Err(sqlx::Error::RowNotFound)
+            }
+        }
+    }
+
+    /// A room about to be created, with the codes of its settings.
+    pub struct NewRoom {
+        pub name: String,
+        /// The labels of the options, in the order they appear on ballots.
+        pub options: Vec<String>,
+        pub admin_code: String,
+        pub method: i64,
+        pub seats: i64,
+        pub tie_break: i64,
+        pub seed: i64,
+        pub partial: bool,
+        pub max_ranks: Option<i64>,
+        pub ballot_type: i64,
+        pub max_score: i64,
+        pub visibility: i64,
+        pub live: bool,
+        /// Hours until the room is deleted.
+        pub lifetime: i64,
+    }
+
+    /// One of the options of a room. Ballots refer to options by ID, so options may share a
+    /// label.
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema, FromRow)]
+    pub struct RoomOption {
+        pub id: i64,
+        pub label: String,
+    }
+
+    #[derive(FromRow)]
+    pub struct Voter {
+        pub id: i64,
+        pub voter_code: String,
+        pub room_id: i64,
+        pub approved: bool,
+        pub voted: bool,
+    }
+
+    /// Where a ballot places one option.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct BallotEntry {
+        pub option_id: i64,
+        /// 0 for the first choice. Options ranked equal share a rank, and rated ballots rank
+        /// options by score.
+        pub rank: i64,
+        /// The score on rated ballots.
+        pub score: Option<i64>,
+    }
+
+    #[derive(FromRow)]
+    struct EntryRow {
+        voter_id: i64,
+        option_id: i64,
+        rank: i64,
+        score: Option<i64>,
+    }
+
+    /// Splits the entries of a room, sorted by voter, into one ballot per voter.
+    fn ballots(rows: Vec<EntryRow>) -> Vec<Vec<BallotEntry>> {
+        let mut ballots = Vec::<(i64, Vec<BallotEntry>)>::new();
+
+        for row in rows {
+            let entry = BallotEntry {
+                option_id: row.option_id,
+                rank: row.rank,
+                score: row.score,
+            };
+
+            match ballots.last_mut() {
+                Some((voter_id, ballot)) if *voter_id == row.voter_id => ballot.push(entry),
+                _ => ballots.push((row.voter_id, vec![entry])),
+            }
+        }
+
+        ballots.into_iter().map(|(_, ballot)| ballot).collect()
+    }
+
+    /// `result_outcomes.outcome` of an option elected in a round.
+    const ELECTED: i64 = 0;
+    /// `result_outcomes.outcome` of an option eliminated in a round.
+    const ELIMINATED: i64 = 1;
+    /// `result_outcomes.outcome` of an option tied for the fewest votes in a round.
+    const TIED: i64 = 2;
+
+    /// The counted results of a room, with the codes of the settings they were counted with.
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct CountedResult {
+        pub method: i64,
+        pub seats: i64,
+        pub quota: Option<f64>,
+        pub tie_break: i64,
+        pub seed: i64,
+        pub partial: bool,
+        pub max_score: i64,
+        /// The number of ballots counted.
+        pub voters: i64,
+        /// The score of each option, best first.
+        pub scores: Vec<ResultScore>,
+        pub ties: Vec<Vec<String>>,
+        /// Each option in ballot order, with how many ballots prefer it over each option.
+        pub pairwise: Vec<(String, Vec<i64>)>,
+        pub rounds: Vec<ResultRound>,
+        pub runoff: Option<ResultRunoff>,
+        /// Each option with how many voters gave it each grade.
+        pub profiles: Vec<(String, Vec<i64>)>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, FromRow)]
+    pub struct ResultScore {
+        pub label: String,
+        pub score: f64,
+        pub elected: bool,
+        pub tied: bool,
+    }
+
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct ResultRound {
+        pub tallies: Vec<(String, f64)>,
+        pub elected: Vec<String>,
+        pub eliminated: Vec<String>,
+        pub tied: Vec<String>,
+        pub exhausted: f64,
+        /// The option the votes came from, the option they went to, and how many there were.
+        pub transfers: Vec<(String, Option<String>, f64)>,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct ResultRunoff {
+        /// The two finalists, each with the ballots preferring it.
+        pub finalists: [(String, i64); 2],
+        pub no_preference: i64,
+    }
+
+    #[derive(FromRow)]
+    struct ResultRow {
+        method: i64,
+        seats: i64,
+        quota: Option<f64>,
+        tie_break: i64,
+        seed: i64,
+        partial: bool,
+        max_score: i64,
+        voters: i64,
+        runoff_first: Option<String>,
+        runoff_first_voters: Option<i64>,
+        runoff_second: Option<String>,
+        runoff_second_voters: Option<i64>,
+        runoff_no_preference: Option<i64>,
+    }
+
+    #[derive(FromRow)]
+    struct TieRow {
+        tie: i64,
+        label: String,
+    }
+
+    #[derive(FromRow)]
+    struct PairRow {
+        position: i64,
+        voters: i64,
+    }
+
+    #[derive(FromRow)]
+    struct TallyRow {
+        round: i64,
+        label: String,
+        votes: f64,
+    }
+
+    #[derive(FromRow)]
+    struct OutcomeRow {
+        round: i64,
+        outcome: i64,
+        label: String,
+    }
+
+    #[derive(FromRow)]
+    struct TransferRow {
+        round: i64,
+        from_label: String,
+        to_label: Option<String>,
+        votes: f64,
+    }
+
+    #[derive(FromRow)]
+    struct ProfileRow {
+        position: i64,
+        label: String,
+        voters: i64,
+    }
+
+    /// The rows of the `result_*` tables of a room, each sorted by its key.
+    struct ResultRows {
+        result: ResultRow,
+        scores: Vec<ResultScore>,
+        ties: Vec<TieRow>,
+        options: Vec<String>,
+        pairs: Vec<PairRow>,
+        exhausted: Vec<f64>,
+        tallies: Vec<TallyRow>,
+        outcomes: Vec<OutcomeRow>,
+        transfers: Vec<TransferRow>,
+        profiles: Vec<ProfileRow>,
+    }
+
+    impl From<ResultRows> for CountedResult {
+        fn from(rows: ResultRows) -> Self {
+            let ResultRows {
+                result,
+                scores,
+                ties,
+                options,
+                pairs,
+                exhausted,
+                tallies,
+                outcomes,
+                transfers,
+                profiles,
+            } = rows;
+
+            let runoff = match result {
+                ResultRow {
+                    runoff_first: Some(first),
+                    runoff_first_voters: Some(first_voters),
+                    runoff_second: Some(second),
+                    runoff_second_voters: Some(second_voters),
+                    runoff_no_preference: Some(no_preference),
+                    ..
+                } => Some(ResultRunoff {
+                    finalists: [(first, first_voters), (second, second_voters)],
+                    no_preference,
+                }),
+                _ => None,
+            };
+
+            let mut grouped_ties = Vec::<(i64, Vec<String>)>::new();
+            for row in ties {
+                match grouped_ties.last_mut() {
+                    Some((tie, labels)) if *tie == row.tie => labels.push(row.label),
+                    _ => grouped_ties.push((row.tie, vec![row.label])),
+                }
+            }
+
+            let pairwise = options
+                .into_iter()
+                .enumerate()
+                .map(|(position, label)| {
+                    let prefs = pairs
+                        .iter()
+                        .filter(|pair| pair.position == position as i64)
+                        .map(|pair| pair.voters)
+                        .collect();
+                    (label, prefs)
+                })
+                .collect();
+
+            let rounds = exhausted
+                .into_iter()
+                .enumerate()
+                .map(|(round, exhausted)| {
+                    let round = round as i64;
+                    let outcome = |outcome: i64| {
+                        outcomes
+                            .iter()
+                            .filter(|row| row.round == round && row.outcome == outcome)
+                            .map(|row| row.label.clone())
+                            .collect()
+                    };
+
+                    ResultRound {
+                        tallies: tallies
+                            .iter()
+                            .filter(|row| row.round == round)
+                            .map(|row| (row.label.clone(), row.votes))
+                            .collect(),
+                        elected: outcome(ELECTED),
+                        eliminated: outcome(ELIMINATED),
+                        tied: outcome(TIED),
+                        exhausted,
+                        transfers: transfers
+                            .iter()
+                            .filter(|row| row.round == round)
+                            .map(|row| (row.from_label.clone(), row.to_label.clone(), row.votes))
+                            .collect(),
+                    }
+                })
+                .collect();
+
+            let mut grouped_profiles = Vec::<(i64, String, Vec<i64>)>::new();
+            for row in profiles {
+                match grouped_profiles.last_mut() {
+                    Some((position, _, grades)) if *position == row.position => {
+                        grades.push(row.voters)
+                    }
+                    _ => grouped_profiles.push((row.position, row.label, vec![row.voters])),
+                }
+            }
+
+            CountedResult {
+                method: result.method,
+                seats: result.seats,
+                quota: result.quota,
+                tie_break: result.tie_break,
+                seed: result.seed,
+                partial: result.partial,
+                max_score: result.max_score,
+                voters: result.voters,
+                scores,
+                ties: grouped_ties.into_iter().map(|(_, labels)| labels).collect(),
+                pairwise,
+                rounds,
+                runoff,
+                profiles: grouped_profiles
+                    .into_iter()
+                    .map(|(_, label, grades)| (label, grades))
+                    .collect(),
+            }
+        }
+    }
+
+    /// An event shared through the `events` table.
+    #[derive(FromRow)]
+    pub struct StoredEvent {
+        pub id: i64,
+        pub room_id: i64,
+        /// JSON of the event, `None` when the room's streams end.
+        pub event: Option<String>,
+    }
+
+    /// Reads and writes rooms, voters, their ballots and shared events.
+    pub trait Store: Send + Sync {
+        /// How many rooms and voters there are.
+        fn counts(&self) -> BoxFuture<'_, Result<Counts, sqlx::Error>>;
+
+        fn room(&self, room_id: i64) -> BoxFuture<'_, Result<Room, sqlx::Error>>;
+
+        /// Creates a room along with its options, returning its ID.
+        fn insert_room(&self, room: NewRoom) -> BoxFuture<'_, Result<i64, sqlx::Error>>;
+
+        /// The options of a room, in the order they appear on ballots.
+        fn options(&self, room_id: i64) -> BoxFuture<'_, Result<Vec<RoomOption>, sqlx::Error>>;
+
+        fn set_status(&self, room_id: i64, status: i64) -> BoxFuture<'_, Result<(), sqlx::Error>>;
+
+        fn voter(&self, voter_id: i64) -> BoxFuture<'_, Result<Voter, sqlx::Error>>;
+
+        fn voter_by_code(
+            &self,
+            room_id: i64,
+            voter_code: String,
+        ) -> BoxFuture<'_, Result<Option<Voter>, sqlx::Error>>;
+
+        /// Every voter of a room, approved or not.
+        fn voters(&self, room_id: i64) -> BoxFuture<'_, Result<Vec<Voter>, sqlx::Error>>;
+
+        fn voter_count(&self, room_id: i64) -> BoxFuture<'_, Result<i32, sqlx::Error>>;
+
+        /// How many voters of a room have voted.
+        fn vote_count(&self, room_id: i64) -> BoxFuture<'_, Result<i32, sqlx::Error>>;
+
+        /// Adds a voter to a room, returning their ID.
+        fn insert_voter(
+            &self,
+            room_id: i64,
+            voter_code: String,
+        ) -> BoxFuture<'_, Result<i64, sqlx::Error>>;
+
+        fn approve_voter(&self, voter_id: i64) -> BoxFuture<'_, Result<(), sqlx::Error>>;
+
+        /// Replaces the ballot of a voter.
+        fn record_vote(
+            &self,
+            voter_id: i64,
+            entries: Vec<BallotEntry>,
+        ) -> BoxFuture<'_, Result<(), sqlx::Error>>;
+
+        /// Adds an approved voter with their ballot for each of the given voter codes and ends
+        /// the room, in one transaction.
+        fn import_ballots(
+            &self,
+            room_id: i64,
+            ballots: Vec<(String, Vec<BallotEntry>)>,
+        ) -> BoxFuture<'_, Result<(), sqlx::Error>>;
+
+        /// The ballots cast by the approved voters of a room, each sorted by rank.
+        fn ballots(
+            &self,
+            room_id: i64,
+        ) -> BoxFuture<'_, Result<Vec<Vec<BallotEntry>>, sqlx::Error>>;
+
+        /// Keeps the counted results of a room. Results never change once counted, so this
+        /// does nothing when the room already has some.
+        fn insert_result(
+            &self,
+            room_id: i64,
+            result: CountedResult,
+        ) -> BoxFuture<'_, Result<(), sqlx::Error>>;
+
+        /// The counted results of a room, if it has been counted.
+        fn result(&self, room_id: i64)
+            -> BoxFuture<'_, Result<Option<CountedResult>, sqlx::Error>>;
+
+        /// Deletes every expired room along with its voters and results, and old events, in one
+        /// transaction, returning the deleted room IDs.
+        fn sweep(&self) -> BoxFuture<'_, Result<Vec<i64>, sqlx::Error>>;
+
+        fn publish_event(
+            &self,
+            room_id: i64,
+            event: Option<String>,
+        ) -> BoxFuture<'_, Result<(), sqlx::Error>>;
+
+        /// The events published after `event_id`, in order.
+        fn events_after(
+            &self,
+            event_id: i64,
+        ) -> BoxFuture<'_, Result<Vec<StoredEvent>, sqlx::Error>>;
+
+        /// The ID of the last published event, or 0.
+        fn last_event_id(&self) -> BoxFuture<'_, Result<i64, sqlx::Error>>;
+    }
+
+    pub struct Sqlite {
+        pool: Pool<sqlx::Sqlite>,
+    }
+
+    impl Store for Sqlite {
+        fn counts(&self) -> BoxFuture<'_, Result<Counts, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_as!(
+                    Counts,
+                    r#"
+                SELECT (SELECT count(id) FROM rooms) AS "rooms!: i32",
+                    (SELECT count(id) FROM voters) AS "voters!: i32"
+                    "#
+                )
+                .fetch_one(&self.pool)
+                .await
+            })
+        }
+
+        fn room(&self, room_id: i64) -> BoxFuture<'_, Result<Room, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_as!(
+                    Room,
+                    r#"
+                SELECT id, name, admin_code, status, method, seats, tie_break, seed, partial, max_ranks, ballot_type, max_score, visibility, live,
+                    CAST(strftime('%s', expires_at) - strftime('%s', 'now') AS INTEGER) AS "expires_in!: i64"
+                FROM rooms
+                WHERE id = ?1
+                    "#,
+                    room_id
+                )
+                .fetch_one(&self.pool)
+                .await
+            })
+        }
+
+        fn insert_room(&self, room: NewRoom) -> BoxFuture<'_, Result<i64, sqlx::Error>> {
+            Box::pin(async move {
+                let mut tx = self.pool.begin().await?;
+
+                let room_id = sqlx::query!(
+                    r#"
+                INSERT INTO rooms (name, admin_code, method, seats, tie_break, seed, partial, max_ranks, ballot_type, max_score, visibility, live, expires_at)
+                VALUES ( ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, datetime('now', '+' || ?13 || ' hours') )
+                    "#,
+                    room.name,
+                    room.admin_code,
+                    room.method,
+                    room.seats,
+                    room.tie_break,
+                    room.seed,
+                    room.partial,
+                    room.max_ranks,
+                    room.ballot_type,
+                    room.max_score,
+                    room.visibility,
+                    room.live,
+                    room.lifetime
+                )
+                .execute(&mut *tx)
+                .await?
+                .last_insert_rowid();
+
+                for (position, label) in room.options.iter().enumerate() {
+                    let position = position as i64;
+                    sqlx::query!(
+                        r#"
+                    INSERT INTO room_options (room_id, label, position)
+                    VALUES (?1, ?2, ?3)
+                        "#,
+                        room_id,
+                        label,
+                        position
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+                }
+
+                tx.commit().await?;
+
+                Ok(room_id)
+            })
+        }
+
+        fn options(&self, room_id: i64) -> BoxFuture<'_, Result<Vec<RoomOption>, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_as!(
+                    RoomOption,
+                    r#"
+                SELECT id, label
+                FROM room_options
+                WHERE room_id = ?1
+                ORDER BY position, id
+                    "#,
+                    room_id
+                )
+                .fetch_all(&self.pool)
+                .await
+            })
+        }
+
+        fn set_status(&self, room_id: i64, status: i64) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query!(
+                    r#"
+                UPDATE rooms
+                SET status = ?1
+                WHERE id = ?2
+                    "#,
+                    status,
+                    room_id
+                )
+                .execute(&self.pool)
+                .await?;
+
+                Ok(())
+            })
+        }
+
+        fn voter(&self, voter_id: i64) -> BoxFuture<'_, Result<Voter, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_as!(
+                    Voter,
+                    r#"
+                SELECT id, voter_code, room_id, approved,
+                    EXISTS (SELECT 1 FROM ballot_entries WHERE voter_id = voters.id) AS "voted!: bool"
+                FROM voters
+                WHERE id = ?1
+                    "#,
+                    voter_id
+                )
+                .fetch_one(&self.pool)
+                .await
+            })
+        }
+
+        fn voter_by_code(
+            &self,
+            room_id: i64,
+            voter_code: String,
+        ) -> BoxFuture<'_, Result<Option<Voter>, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_as!(
+                    Voter,
+                    r#"
+                SELECT id, voter_code, room_id, approved,
+                    EXISTS (SELECT 1 FROM ballot_entries WHERE voter_id = voters.id) AS "voted!: bool"
+                FROM voters
+                WHERE voter_code = ?1 AND room_id = ?2
+                    "#,
+                    voter_code,
+                    room_id
+                )
+                .fetch_optional(&self.pool)
+                .await
+            })
+        }
+
+        fn voters(&self, room_id: i64) -> BoxFuture<'_, Result<Vec<Voter>, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_as!(
+                    Voter,
+                    r#"
+                SELECT id, voter_code, room_id, approved,
+                    EXISTS (SELECT 1 FROM ballot_entries WHERE voter_id = voters.id) AS "voted!: bool"
+                FROM voters
+                WHERE room_id = ?1
+                ORDER BY id
+                    "#,
+                    room_id
+                )
+                .fetch_all(&self.pool)
+                .await
+            })
+        }
+
+        fn voter_count(&self, room_id: i64) -> BoxFuture<'_, Result<i32, sqlx::Error>> {
+            Box::pin(async move {
+                let count = sqlx::query!(
+                    "SELECT count(id) as count FROM voters WHERE room_id = ?1",
+                    room_id
+                )
+                .fetch_one(&self.pool)
+                .await?
+                .count;
+
+                Ok(count)
+            })
+        }
+
+        fn vote_count(&self, room_id: i64) -> BoxFuture<'_, Result<i32, sqlx::Error>> {
+            Box::pin(async move {
+                let count = sqlx::query!(
+                    r#"
+                SELECT count(DISTINCT voter_id) as "count!: i32"
+                FROM ballot_entries
+                WHERE voter_id IN (SELECT id FROM voters WHERE room_id = ?1)
+                    "#,
+                    room_id
+                )
+                .fetch_one(&self.pool)
+                .await?
+                .count;
+
+                Ok(count)
+            })
+        }
+
+        fn insert_voter(
+            &self,
+            room_id: i64,
+            voter_code: String,
+        ) -> BoxFuture<'_, Result<i64, sqlx::Error>> {
+            Box::pin(async move {
+                let voter_id = sqlx::query!(
+                    r#"
+                INSERT INTO voters (voter_code, room_id)
+                VALUES (?1, ?2)
+                    "#,
+                    voter_code,
+                    room_id
+                )
+                .execute(&self.pool)
+                .await?
+                .last_insert_rowid();
+
+                Ok(voter_id)
+            })
+        }
+
+        fn approve_voter(&self, voter_id: i64) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query!(
+                    r#"UPDATE voters SET approved = true WHERE id = ?1"#,
+                    voter_id
+                )
+                .execute(&self.pool)
+                .await?;
+
+                Ok(())
+            })
+        }
+
+        fn record_vote(
+            &self,
+            voter_id: i64,
+            entries: Vec<BallotEntry>,
+        ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async move {
+                let mut tx = self.pool.begin().await?;
+
+                sqlx::query!("DELETE FROM ballot_entries WHERE voter_id = ?1", voter_id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                for entry in entries {
+                    sqlx::query!(
+                        r#"
+                    INSERT INTO ballot_entries (voter_id, option_id, rank, score)
+                    VALUES (?1, ?2, ?3, ?4)
+                        "#,
+                        voter_id,
+                        entry.option_id,
+                        entry.rank,
+                        entry.score
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+                }
+
+                tx.commit().await?;
+
+                Ok(())
+            })
+        }
+
+        fn import_ballots(
+            &self,
+            room_id: i64,
+            ballots: Vec<(String, Vec<BallotEntry>)>,
+        ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async move {
+                let mut tx = self.pool.begin().await?;
+
+                for (voter_code, entries) in ballots {
+                    let voter_id = sqlx::query!(
+                        r#"
+                    INSERT INTO voters (voter_code, room_id, approved)
+                    VALUES (?1, ?2, true)
+                        "#,
+                        voter_code,
+                        room_id
+                    )
+                    .execute(&mut *tx)
+                    .await?
+                    .last_insert_rowid();
+
+                    for entry in entries {
+                        sqlx::query!(
+                            r#"
+                        INSERT INTO ballot_entries (voter_id, option_id, rank, score)
+                        VALUES (?1, ?2, ?3, ?4)
+                            "#,
+                            voter_id,
+                            entry.option_id,
+                            entry.rank,
+                            entry.score
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
+
+                sqlx::query!("UPDATE rooms SET status = ?1 WHERE id = ?2", ENDED, room_id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                tx.commit().await?;
+
+                Ok(())
+            })
+        }
+        fn ballots(
+            &self,
+            room_id: i64,
+        ) -> BoxFuture<'_, Result<Vec<Vec<BallotEntry>>, sqlx::Error>> {
+            Box::pin(async move {
+                let rows = sqlx::query_as!(
+                    EntryRow,
+                    r#"
+                SELECT ballot_entries.voter_id, ballot_entries.option_id, ballot_entries.rank, ballot_entries.score
+                FROM ballot_entries
+                JOIN voters ON voters.id = ballot_entries.voter_id
+                JOIN room_options ON room_options.id = ballot_entries.option_id
+                WHERE voters.room_id = ?1 AND voters.approved = TRUE
+                ORDER BY ballot_entries.voter_id, ballot_entries.rank, room_options.position
+                    "#,
+                    room_id
+                )
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(ballots(rows))
+            })
+        }
+
+        fn insert_result(
+            &self,
+            room_id: i64,
+            result: CountedResult,
+        ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async move {
+                let mut tx = self.pool.begin().await?;
+
+                let (first, second, no_preference) = match result.runoff {
+                    Some(ResultRunoff {
+                        finalists: [first, second],
+                        no_preference,
+                    }) => (Some(first), Some(second), Some(no_preference)),
+                    None => (None, None, None),
+                };
+                let (runoff_first, runoff_first_voters) = first.unzip();
+                let (runoff_second, runoff_second_voters) = second.unzip();
+
+                let inserted = sqlx::query!(
+                    r#"
+                INSERT INTO results (room_id, method, seats, quota, tie_break, seed, partial, max_score, voters, runoff_first, runoff_first_voters, runoff_second, runoff_second_voters, runoff_no_preference)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+                ON CONFLICT (room_id) DO NOTHING
+                    "#,
+                    room_id,
+                    result.method,
+                    result.seats,
+                    result.quota,
+                    result.tie_break,
+                    result.seed,
+                    result.partial,
+                    result.max_score,
+                    result.voters,
+                    runoff_first,
+                    runoff_first_voters,
+                    runoff_second,
+                    runoff_second_voters,
+                    no_preference
+                )
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+
+                if inserted == 0 {
+                    return Ok(());
+                }
+
+                for (position, score) in result.scores.into_iter().enumerate() {
+                    let position = position as i64;
+                    sqlx::query!(
+                        r#"
+                    INSERT INTO result_scores (room_id, position, label, score, elected, tied)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                        "#,
+                        room_id,
+                        position,
+                        score.label,
+                        score.score,
+                        score.elected,
+                        score.tied
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+                }
+
+                for (tie, labels) in result.ties.into_iter().enumerate() {
+                    let tie = tie as i64;
+                    for (position, label) in labels.into_iter().enumerate() {
+                        let position = position as i64;
+                        sqlx::query!(
+                            r#"
+                        INSERT INTO result_ties (room_id, tie, position, label)
+                        VALUES (?1, ?2, ?3, ?4)
+                            "#,
+                            room_id,
+                            tie,
+                            position,
+                            label
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
+
+                for (position, (label, prefs)) in result.pairwise.into_iter().enumerate() {
+                    let position = position as i64;
+                    sqlx::query!(
+                        r#"
+                    INSERT INTO result_options (room_id, position, label)
+                    VALUES (?1, ?2, ?3)
+                        "#,
+                        room_id,
+                        position,
+                        label
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    for (against, voters) in prefs.into_iter().enumerate() {
+                        let against = against as i64;
+                        sqlx::query!(
+                            r#"
+                        INSERT INTO result_pairwise (room_id, position, against, voters)
+                        VALUES (?1, ?2, ?3, ?4)
+                            "#,
+                            room_id,
+                            position,
+                            against,
+                            voters
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
+
+                for (round, counted) in result.rounds.into_iter().enumerate() {
+                    let round = round as i64;
+                    sqlx::query!(
+                        r#"
+                    INSERT INTO result_rounds (room_id, round, exhausted)
+                    VALUES (?1, ?2, ?3)
+                        "#,
+                        room_id,
+                        round,
+                        counted.exhausted
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    for (position, (label, votes)) in counted.tallies.into_iter().enumerate() {
+                        let position = position as i64;
+                        sqlx::query!(
+                            r#"
+                        INSERT INTO result_tallies (room_id, round, position, label, votes)
+                        VALUES (?1, ?2, ?3, ?4, ?5)
+                            "#,
+                            room_id,
+                            round,
+                            position,
+                            label,
+                            votes
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+
+                    let outcomes = [
+                        (ELECTED, counted.elected),
+                        (ELIMINATED, counted.eliminated),
+                        (TIED, counted.tied),
+                    ];
+                    for (outcome, labels) in outcomes {
+                        for (position, label) in labels.into_iter().enumerate() {
+                            let position = position as i64;
+                            sqlx::query!(
+                                r#"
+                            INSERT INTO result_outcomes (room_id, round, outcome, position, label)
+                            VALUES (?1, ?2, ?3, ?4, ?5)
+                                "#,
+                                room_id,
+                                round,
+                                outcome,
+                                position,
+                                label
+                            )
+                            .execute(&mut *tx)
+                            .await?;
+                        }
+                    }
+
+                    for (position, (from, to, votes)) in counted.transfers.into_iter().enumerate() {
+                        let position = position as i64;
+                        sqlx::query!(
+                            r#"
+                        INSERT INTO result_transfers (room_id, round, position, from_label, to_label, votes)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                            "#,
+                            room_id,
+                            round,
+                            position,
+                            from,
+                            to,
+                            votes
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
+
+                for (position, (label, grades)) in result.profiles.into_iter().enumerate() {
+                    let position = position as i64;
+                    for (grade, voters) in grades.into_iter().enumerate() {
+                        let grade = grade as i64;
+                        sqlx::query!(
+                            r#"
+                        INSERT INTO result_profiles (room_id, position, label, grade, voters)
+                        VALUES (?1, ?2, ?3, ?4, ?5)
+                            "#,
+                            room_id,
+                            position,
+                            label,
+                            grade,
+                            voters
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
+
+                tx.commit().await?;
+
+                Ok(())
+            })
+        }
+
+        fn result(
+            &self,
+            room_id: i64,
+        ) -> BoxFuture<'_, Result<Option<CountedResult>, sqlx::Error>> {
+            Box::pin(async move {
+                let mut tx = self.pool.begin().await?;
+
+                let result = sqlx::query_as!(
+                    ResultRow,
+                    r#"
+                SELECT method, seats, quota, tie_break, seed, partial, max_score, voters, runoff_first, runoff_first_voters, runoff_second, runoff_second_voters, runoff_no_preference
+                FROM results
+                WHERE room_id = ?1
+                    "#,
+                    room_id
+                )
+                .fetch_optional(&mut *tx)
+                .await?;
+
+                let Some(result) = result else {
+                    return Ok(None);
+                };
+
+                let scores = sqlx::query_as!(
+                    ResultScore,
+                    r#"
+                SELECT label, score, elected, tied
+                FROM result_scores
+                WHERE room_id = ?1
+                ORDER BY position
+                    "#,
+                    room_id
+                )
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let ties = sqlx::query_as!(
+                    TieRow,
+                    r#"
+                SELECT tie, label
+                FROM result_ties
+                WHERE room_id = ?1
+                ORDER BY tie, position
+                    "#,
+                    room_id
+                )
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let options = sqlx::query_scalar!(
+                    r#"
+                SELECT label
+                FROM result_options
+                WHERE room_id = ?1
+                ORDER BY position
+                    "#,
+                    room_id
+                )
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let pairs = sqlx::query_as!(
+                    PairRow,
+                    r#"
+                SELECT position, voters
+                FROM result_pairwise
+                WHERE room_id = ?1
+                ORDER BY position, against
+                    "#,
+                    room_id
+                )
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let exhausted = sqlx::query_scalar!(
+                    r#"
+                SELECT exhausted
+                FROM result_rounds
+                WHERE room_id = ?1
+                ORDER BY round
+                    "#,
+                    room_id
+                )
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let tallies = sqlx::query_as!(
+                    TallyRow,
+                    r#"
+                SELECT round, label, votes
+                FROM result_tallies
+                WHERE room_id = ?1
+                ORDER BY round, position
+                    "#,
+                    room_id
+                )
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let outcomes = sqlx::query_as!(
+                    OutcomeRow,
+                    r#"
+                SELECT round, outcome, label
+                FROM result_outcomes
+                WHERE room_id = ?1
+                ORDER BY round, outcome, position
+                    "#,
+                    room_id
+                )
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let transfers = sqlx::query_as!(
+                    TransferRow,
+                    r#"
+                SELECT round, from_label, to_label, votes
+                FROM result_transfers
+                WHERE room_id = ?1
+                ORDER BY round, position
+                    "#,
+                    room_id
+                )
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let profiles = sqlx::query_as!(
+                    ProfileRow,
+                    r#"
+                SELECT position, label, voters
+                FROM result_profiles
+                WHERE room_id = ?1
+                ORDER BY position, grade
+                    "#,
+                    room_id
+                )
+                .fetch_all(&mut *tx)
+                .await?;
+
+                tx.commit().await?;
+
+                Ok(Some(
+                    ResultRows {
+                        result,
+                        scores,
+                        ties,
+                        options,
+                        pairs,
+                        exhausted,
+                        tallies,
+                        outcomes,
+                        transfers,
+                        profiles,
+                    }
+                    .into(),
+                ))
+            })
+        }
+
+        fn sweep(&self) -> BoxFuture<'_, Result<Vec<i64>, sqlx::Error>> {
+            Box::pin(async move {
+                let mut tx = self.pool.begin().await?;
+
+                let room_ids = sqlx::query!(
+                    r#"
+                SELECT id
+                FROM rooms
+                WHERE COALESCE(expires_at, datetime(created_at, '+1 hour')) <= datetime('now')
+                    "#
+                )
+                .fetch_all(&mut *tx)
+                .await?
+                .into_iter()
+                .map(|r| r.id)
+                .collect::<Vec<_>>();
+
+                for room_id in &room_ids {
+                    sqlx::query!(
+                        r#"
+                    DELETE FROM ballot_entries
+                    WHERE voter_id IN (SELECT id FROM voters WHERE room_id = ?1)
+                        "#,
+                        room_id
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    sqlx::query!(
+                        r#"
+                    DELETE FROM results
+                    WHERE room_id = ?1
+                        "#,
+                        room_id
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    sqlx::query!(
+                        r#"
+                    DELETE FROM room_options
+                    WHERE room_id = ?1
+                        "#,
+                        room_id
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    sqlx::query!(
+                        r#"
+                    DELETE FROM voters
+                    WHERE room_id = ?1
+                        "#,
+                        room_id
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    sqlx::query!(
+                        r#"
+                    DELETE FROM rooms
+                    WHERE id = ?1
+                        "#,
+                        room_id
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+                }
+
+                // Instances poll the events table every fraction of a second, so old events
+                // are of no use to anyone.
+                sqlx::query!(
+                    r#"
+                DELETE FROM events
+                WHERE created_at <= datetime('now', '-1 hour')
+                    "#
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                tx.commit().await?;
+
+                Ok(room_ids)
+            })
+        }
+
+        fn publish_event(
+            &self,
+            room_id: i64,
+            event: Option<String>,
+        ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query!(
+                    r#"
+                INSERT INTO events (room_id, event)
+                VALUES (?1, ?2)
+                    "#,
+                    room_id,
+                    event
+                )
+                .execute(&self.pool)
+                .await?;
+
+                Ok(())
+            })
+        }
+
+        fn events_after(
+            &self,
+            event_id: i64,
+        ) -> BoxFuture<'_, Result<Vec<StoredEvent>, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_as!(
+                    StoredEvent,
+                    r#"
+                SELECT id, room_id, event
+                FROM events
+                WHERE id > ?1
+                ORDER BY id
+                    "#,
+                    event_id
+                )
+                .fetch_all(&self.pool)
+                .await
+            })
+        }
+
+        fn last_event_id(&self) -> BoxFuture<'_, Result<i64, sqlx::Error>> {
+            Box::pin(async move {
+                let id = sqlx::query!(r#"SELECT COALESCE(MAX(id), 0) AS "id!: i64" FROM events"#)
+                    .fetch_one(&self.pool)
+                    .await?
+                    .id;
+
+                Ok(id)
+            })
+        }
+    }
+
+    /// The same queries as [`Sqlite`] for PostgreSQL. The query macros are checked against the
+    /// SQLite schema only, so these are checked by the tests instead.
+    pub struct Postgres {
+        pool: Pool<sqlx::Postgres>,
+    }
+
+    impl Store for Postgres {
+        fn counts(&self) -> BoxFuture<'_, Result<Counts, sqlx::Error>> {
+            Box::pin(async move {
+                let (rooms, voters) = sqlx::query_as(
+                    r#"
+                SELECT (SELECT count(id)::INT FROM rooms), (SELECT count(id)::INT FROM voters)
+                    "#,
+                )
+                .fetch_one(&self.pool)
+                .await?;
+
+                Ok(Counts { rooms, voters })
+            })
+        }
+
+        fn room(&self, room_id: i64) -> BoxFuture<'_, Result<Room, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_as(
+                    r#"
+                SELECT id, name, admin_code, status, method, seats, tie_break, seed, partial, max_ranks, ballot_type, max_score, visibility, live,
+                    EXTRACT(EPOCH FROM expires_at - now())::BIGINT AS expires_in
+                FROM rooms
+                WHERE id = $1
+                    "#,
+                )
+                .bind(room_id)
+                .fetch_one(&self.pool)
+                .await
+            })
+        }
+
+        fn insert_room(&self, room: NewRoom) -> BoxFuture<'_, Result<i64, sqlx::Error>> {
+            Box::pin(async move {
+                let mut tx = self.pool.begin().await?;
+
+                let room_id: i64 = sqlx::query_scalar(
+                    r#"
+                INSERT INTO rooms (name, admin_code, method, seats, tie_break, seed, partial, max_ranks, ballot_type, max_score, visibility, live, expires_at)
+                VALUES ( $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, now() + make_interval(hours => $13::INT) )
+                RETURNING id
+                    "#,
+                )
+                .bind(room.name)
+                .bind(room.admin_code)
+                .bind(room.method)
+                .bind(room.seats)
+                .bind(room.tie_break)
+                .bind(room.seed)
+                .bind(room.partial)
+                .bind(room.max_ranks)
+                .bind(room.ballot_type)
+                .bind(room.max_score)
+                .bind(room.visibility)
+                .bind(room.live)
+                .bind(room.lifetime)
+                .fetch_one(&mut *tx)
+                .await?;
+
+                for (position, label) in room.options.into_iter().enumerate() {
+                    sqlx::query(
+                        "INSERT INTO room_options (room_id, label, position) VALUES ($1, $2, $3)",
+                    )
+                    .bind(room_id)
+                    .bind(label)
+                    .bind(position as i64)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+
+                tx.commit().await?;
+
+                Ok(room_id)
+            })
+        }
+
+        fn options(&self, room_id: i64) -> BoxFuture<'_, Result<Vec<RoomOption>, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_as(
+                    r#"
+                SELECT id, label
+                FROM room_options
+                WHERE room_id = $1
+                ORDER BY position, id
+                    "#,
+                )
+                .bind(room_id)
+                .fetch_all(&self.pool)
+                .await
+            })
+        }
+
+        fn set_status(&self, room_id: i64, status: i64) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query(
+                    r#"
+                UPDATE rooms
+                SET status = $1
+                WHERE id = $2
+                    "#,
+                )
+                .bind(status)
+                .bind(room_id)
+                .execute(&self.pool)
+                .await?;
+
+                Ok(())
+            })
+        }
+
+        fn voter(&self, voter_id: i64) -> BoxFuture<'_, Result<Voter, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_as(
+                    r#"
+                SELECT id, voter_code, room_id, approved,
+                    EXISTS (SELECT 1 FROM ballot_entries WHERE voter_id = voters.id) AS voted
+                FROM voters
+                WHERE id = $1
+                    "#,
+                )
+                .bind(voter_id)
+                .fetch_one(&self.pool)
+                .await
+            })
+        }
+
+        fn voter_by_code(
+            &self,
+            room_id: i64,
+            voter_code: String,
+        ) -> BoxFuture<'_, Result<Option<Voter>, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_as(
+                    r#"
+                SELECT id, voter_code, room_id, approved,
+                    EXISTS (SELECT 1 FROM ballot_entries WHERE voter_id = voters.id) AS voted
+                FROM voters
+                WHERE voter_code = $1 AND room_id = $2
+                    "#,
+                )
+                .bind(voter_code)
+                .bind(room_id)
+                .fetch_optional(&self.pool)
+                .await
+            })
+        }
+
+        fn voters(&self, room_id: i64) -> BoxFuture<'_, Result<Vec<Voter>, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_as(
+                    r#"
+                SELECT id, voter_code, room_id, approved,
+                    EXISTS (SELECT 1 FROM ballot_entries WHERE voter_id = voters.id) AS voted
+                FROM voters
+                WHERE room_id = $1
+                ORDER BY id
+                    "#,
+                )
+                .bind(room_id)
+                .fetch_all(&self.pool)
+                .await
+            })
+        }
+
+        fn voter_count(&self, room_id: i64) -> BoxFuture<'_, Result<i32, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_scalar("SELECT count(id)::INT FROM voters WHERE room_id = $1")
+                    .bind(room_id)
+                    .fetch_one(&self.pool)
+                    .await
+            })
+        }
+
+        fn vote_count(&self, room_id: i64) -> BoxFuture<'_, Result<i32, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_scalar(
+                    r#"
+                SELECT count(DISTINCT voter_id)::INT
+                FROM ballot_entries
+                WHERE voter_id IN (SELECT id FROM voters WHERE room_id = $1)
+                    "#,
+                )
+                .bind(room_id)
+                .fetch_one(&self.pool)
+                .await
+            })
+        }
+
+        fn insert_voter(
+            &self,
+            room_id: i64,
+            voter_code: String,
+        ) -> BoxFuture<'_, Result<i64, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_scalar(
+                    r#"
+                INSERT INTO voters (voter_code, room_id)
+                VALUES ($1, $2)
+                RETURNING id
+                    "#,
+                )
+                .bind(voter_code)
+                .bind(room_id)
+                .fetch_one(&self.pool)
+                .await
+            })
+        }
+
+        fn approve_voter(&self, voter_id: i64) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query("UPDATE voters SET approved = true WHERE id = $1")
+                    .bind(voter_id)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            })
+        }
+
+        fn record_vote(
+            &self,
+            voter_id: i64,
+            entries: Vec<BallotEntry>,
+        ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async move {
+                let mut tx = self.pool.begin().await?;
+
+                sqlx::query("DELETE FROM ballot_entries WHERE voter_id = $1")
+                    .bind(voter_id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                for entry in entries {
+                    sqlx::query(
+                        r#"
+                    INSERT INTO ballot_entries (voter_id, option_id, rank, score)
+                    VALUES ($1, $2, $3, $4)
+                        "#,
+                    )
+                    .bind(voter_id)
+                    .bind(entry.option_id)
+                    .bind(entry.rank)
+                    .bind(entry.score)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+
+                tx.commit().await?;
+
+                Ok(())
+            })
+        }
+
+        fn import_ballots(
+            &self,
+            room_id: i64,
+            ballots: Vec<(String, Vec<BallotEntry>)>,
+        ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async move {
+                let mut tx = self.pool.begin().await?;
+
+                for (voter_code, entries) in ballots {
+                    let voter_id: i64 = sqlx::query_scalar(
+                        r#"
+                    INSERT INTO voters (voter_code, room_id, approved)
+                    VALUES ($1, $2, true)
+                    RETURNING id
+                        "#,
+                    )
+                    .bind(voter_code)
+                    .bind(room_id)
+                    .fetch_one(&mut *tx)
+                    .await?;
+
+                    for entry in entries {
+                        sqlx::query(
+                            r#"
+                        INSERT INTO ballot_entries (voter_id, option_id, rank, score)
+                        VALUES ($1, $2, $3, $4)
+                            "#,
+                        )
+                        .bind(voter_id)
+                        .bind(entry.option_id)
+                        .bind(entry.rank)
+                        .bind(entry.score)
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
+
+                sqlx::query("UPDATE rooms SET status = $1 WHERE id = $2")
+                    .bind(ENDED)
+                    .bind(room_id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                tx.commit().await?;
+
+                Ok(())
+            })
+        }
+        fn ballots(
+            &self,
+            room_id: i64,
+        ) -> BoxFuture<'_, Result<Vec<Vec<BallotEntry>>, sqlx::Error>> {
+            Box::pin(async move {
+                let rows = sqlx::query_as(
+                    r#"
+                SELECT ballot_entries.voter_id, ballot_entries.option_id, ballot_entries.rank, ballot_entries.score
+                FROM ballot_entries
+                JOIN voters ON voters.id = ballot_entries.voter_id
+                JOIN room_options ON room_options.id = ballot_entries.option_id
+                WHERE voters.room_id = $1 AND voters.approved = TRUE
+                ORDER BY ballot_entries.voter_id, ballot_entries.rank, room_options.position
+                    "#,
+                )
+                .bind(room_id)
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(ballots(rows))
+            })
+        }
+
+        fn insert_result(
+            &self,
+            room_id: i64,
+            result: CountedResult,
+        ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async move {
+                let mut tx = self.pool.begin().await?;
+
+                let (first, second, no_preference) = match result.runoff {
+                    Some(ResultRunoff {
+                        finalists: [first, second],
+                        no_preference,
+                    }) => (Some(first), Some(second), Some(no_preference)),
+                    None => (None, None, None),
+                };
+                let (runoff_first, runoff_first_voters) = first.unzip();
+                let (runoff_second, runoff_second_voters) = second.unzip();
+
+                let inserted = sqlx::query(
+                    r#"
+                INSERT INTO results (room_id, method, seats, quota, tie_break, seed, partial, max_score, voters, runoff_first, runoff_first_voters, runoff_second, runoff_second_voters, runoff_no_preference)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+                ON CONFLICT (room_id) DO NOTHING
+                    "#,
+                )
+                .bind(room_id)
+                .bind(result.method)
+                .bind(result.seats)
+                .bind(result.quota)
+                .bind(result.tie_break)
+                .bind(result.seed)
+                .bind(result.partial)
+                .bind(result.max_score)
+                .bind(result.voters)
+                .bind(runoff_first)
+                .bind(runoff_first_voters)
+                .bind(runoff_second)
+                .bind(runoff_second_voters)
+                .bind(no_preference)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+
+                if inserted == 0 {
+                    return Ok(());
+                }
+
+                for (position, score) in result.scores.into_iter().enumerate() {
+                    sqlx::query(
+                        r#"
+                    INSERT INTO result_scores (room_id, position, label, score, elected, tied)
+                    VALUES ($1, $2, $3, $4, $5, $6)
+                        "#,
+                    )
+                    .bind(room_id)
+                    .bind(position as i64)
+                    .bind(score.label)
+                    .bind(score.score)
+                    .bind(score.elected)
+                    .bind(score.tied)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+
+                for (tie, labels) in result.ties.into_iter().enumerate() {
+                    for (position, label) in labels.into_iter().enumerate() {
+                        sqlx::query(
+                            "INSERT INTO result_ties (room_id, tie, position, label) VALUES ($1, $2, $3, $4)",
+                        )
+                        .bind(room_id)
+                        .bind(tie as i64)
+                        .bind(position as i64)
+                        .bind(label)
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
+
+                for (position, (label, prefs)) in result.pairwise.into_iter().enumerate() {
+                    sqlx::query(
+                        "INSERT INTO result_options (room_id, position, label) VALUES ($1, $2, $3)",
+                    )
+                    .bind(room_id)
+                    .bind(position as i64)
+                    .bind(label)
+                    .execute(&mut *tx)
+                    .await?;
+
+                    for (against, voters) in prefs.into_iter().enumerate() {
+                        sqlx::query(
+                            r#"
+                        INSERT INTO result_pairwise (room_id, position, against, voters)
+                        VALUES ($1, $2, $3, $4)
+                            "#,
+                        )
+                        .bind(room_id)
+                        .bind(position as i64)
+                        .bind(against as i64)
+                        .bind(voters)
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
+
+                for (round, counted) in result.rounds.into_iter().enumerate() {
+                    let round = round as i64;
+                    sqlx::query(
+                        "INSERT INTO result_rounds (room_id, round, exhausted) VALUES ($1, $2, $3)",
+                    )
+                    .bind(room_id)
+                    .bind(round)
+                    .bind(counted.exhausted)
+                    .execute(&mut *tx)
+                    .await?;
+
+                    for (position, (label, votes)) in counted.tallies.into_iter().enumerate() {
+                        sqlx::query(
+                            r#"
+                        INSERT INTO result_tallies (room_id, round, position, label, votes)
+                        VALUES ($1, $2, $3, $4, $5)
+                            "#,
+                        )
+                        .bind(room_id)
+                        .bind(round)
+                        .bind(position as i64)
+                        .bind(label)
+                        .bind(votes)
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+
+                    let outcomes = [
+                        (ELECTED, counted.elected),
+                        (ELIMINATED, counted.eliminated),
+                        (TIED, counted.tied),
+                    ];
+                    for (outcome, labels) in outcomes {
+                        for (position, label) in labels.into_iter().enumerate() {
+                            sqlx::query(
+                                r#"
+                            INSERT INTO result_outcomes (room_id, round, outcome, position, label)
+                            VALUES ($1, $2, $3, $4, $5)
+                                "#,
+                            )
+                            .bind(room_id)
+                            .bind(round)
+                            .bind(outcome)
+                            .bind(position as i64)
+                            .bind(label)
+                            .execute(&mut *tx)
+                            .await?;
+                        }
+                    }
+
+                    for (position, (from, to, votes)) in counted.transfers.into_iter().enumerate() {
+                        sqlx::query(
+                            r#"
+                        INSERT INTO result_transfers (room_id, round, position, from_label, to_label, votes)
+                        VALUES ($1, $2, $3, $4, $5, $6)
+                            "#,
+                        )
+                        .bind(room_id)
+                        .bind(round)
+                        .bind(position as i64)
+                        .bind(from)
+                        .bind(to)
+                        .bind(votes)
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
+
+                for (position, (label, grades)) in result.profiles.into_iter().enumerate() {
+                    for (grade, voters) in grades.into_iter().enumerate() {
+                        sqlx::query(
+                            r#"
+                        INSERT INTO result_profiles (room_id, position, label, grade, voters)
+                        VALUES ($1, $2, $3, $4, $5)
+                            "#,
+                        )
+                        .bind(room_id)
+                        .bind(position as i64)
+                        .bind(&label)
+                        .bind(grade as i64)
+                        .bind(voters)
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
+
+                tx.commit().await?;
+
+                Ok(())
+            })
+        }
+
+        fn result(
+            &self,
+            room_id: i64,
+        ) -> BoxFuture<'_, Result<Option<CountedResult>, sqlx::Error>> {
+            Box::pin(async move {
+                let mut tx = self.pool.begin().await?;
+
+                let result: Option<ResultRow> = sqlx::query_as(
+                    r#"
+                SELECT method, seats, quota, tie_break, seed, partial, max_score, voters, runoff_first, runoff_first_voters, runoff_second, runoff_second_voters, runoff_no_preference
+                FROM results
+                WHERE room_id = $1
+                    "#,
+                )
+                .bind(room_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+                let Some(result) = result else {
+                    return Ok(None);
+                };
+
+                let scores = sqlx::query_as(
+                    r#"
+                SELECT label, score, elected, tied
+                FROM result_scores
+                WHERE room_id = $1
+                ORDER BY position
+                    "#,
+                )
+                .bind(room_id)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let ties = sqlx::query_as(
+                    "SELECT tie, label FROM result_ties WHERE room_id = $1 ORDER BY tie, position",
+                )
+                .bind(room_id)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let options = sqlx::query_scalar(
+                    "SELECT label FROM result_options WHERE room_id = $1 ORDER BY position",
+                )
+                .bind(room_id)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let pairs = sqlx::query_as(
+                    r#"
+                SELECT position, voters
+                FROM result_pairwise
+                WHERE room_id = $1
+                ORDER BY position, against
+                    "#,
+                )
+                .bind(room_id)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let exhausted = sqlx::query_scalar(
+                    "SELECT exhausted FROM result_rounds WHERE room_id = $1 ORDER BY round",
+                )
+                .bind(room_id)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let tallies = sqlx::query_as(
+                    r#"
+                SELECT round, label, votes
+                FROM result_tallies
+                WHERE room_id = $1
+                ORDER BY round, position
+                    "#,
+                )
+                .bind(room_id)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let outcomes = sqlx::query_as(
+                    r#"
+                SELECT round, outcome, label
+                FROM result_outcomes
+                WHERE room_id = $1
+                ORDER BY round, outcome, position
+                    "#,
+                )
+                .bind(room_id)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let transfers = sqlx::query_as(
+                    r#"
+                SELECT round, from_label, to_label, votes
+                FROM result_transfers
+                WHERE room_id = $1
+                ORDER BY round, position
+                    "#,
+                )
+                .bind(room_id)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let profiles = sqlx::query_as(
+                    r#"
+                SELECT position, label, voters
+                FROM result_profiles
+                WHERE room_id = $1
+                ORDER BY position, grade
+                    "#,
+                )
+                .bind(room_id)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                tx.commit().await?;
+
+                Ok(Some(
+                    ResultRows {
+                        result,
+                        scores,
+                        ties,
+                        options,
+                        pairs,
+                        exhausted,
+                        tallies,
+                        outcomes,
+                        transfers,
+                        profiles,
+                    }
+                    .into(),
+                ))
+            })
+        }
+
+        fn sweep(&self) -> BoxFuture<'_, Result<Vec<i64>, sqlx::Error>> {
+            Box::pin(async move {
+                let mut tx = self.pool.begin().await?;
+
+                let room_ids: Vec<i64> =
+                    sqlx::query_scalar("SELECT id FROM rooms WHERE expires_at <= now()")
+                        .fetch_all(&mut *tx)
+                        .await?;
+
+                sqlx::query(
+                    r#"
+                DELETE FROM ballot_entries
+                WHERE voter_id IN (SELECT id FROM voters WHERE room_id = ANY($1))
+                    "#,
+                )
+                .bind(&room_ids)
+                .execute(&mut *tx)
+                .await?;
+
+                sqlx::query("DELETE FROM results WHERE room_id = ANY($1)")
+                    .bind(&room_ids)
+                    .execute(&mut *tx)
+                    .await?;
+
+                sqlx::query("DELETE FROM room_options WHERE room_id = ANY($1)")
+                    .bind(&room_ids)
+                    .execute(&mut *tx)
+                    .await?;
+
+                sqlx::query("DELETE FROM voters WHERE room_id = ANY($1)")
+                    .bind(&room_ids)
+                    .execute(&mut *tx)
+                    .await?;
+
+                sqlx::query("DELETE FROM rooms WHERE id = ANY($1)")
+                    .bind(&room_ids)
+                    .execute(&mut *tx)
+                    .await?;
+
+                sqlx::query("DELETE FROM events WHERE created_at <= now() - INTERVAL '1 hour'")
+                    .execute(&mut *tx)
+                    .await?;
+
+                tx.commit().await?;
+
+                Ok(room_ids)
+            })
+        }
+
+        fn publish_event(
+            &self,
+            room_id: i64,
+            event: Option<String>,
+        ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query("INSERT INTO events (room_id, event) VALUES ($1, $2)")
+                    .bind(room_id)
+                    .bind(event)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            })
+        }
+
+        fn events_after(
+            &self,
+            event_id: i64,
+        ) -> BoxFuture<'_, Result<Vec<StoredEvent>, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_as(
+                    r#"
+                SELECT id, room_id, event
+                FROM events
+                WHERE id > $1
+                ORDER BY id
+                    "#,
+                )
+                .bind(event_id)
+                .fetch_all(&self.pool)
+                .await
+            })
+        }
+
+        fn last_event_id(&self) -> BoxFuture<'_, Result<i64, sqlx::Error>> {
+            Box::pin(async move {
+                sqlx::query_scalar("SELECT COALESCE(MAX(id), 0) FROM events")
+                    .fetch_one(&self.pool)
+                    .await
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{
+            connect, BallotEntry, CountedResult, Db, ResultRound, ResultRunoff, ResultScore, ENDED,
+            STARTED, WAITING,
+        };
+        use crate::{tests::new_room, utils};
+
+        /// Runs every query of a backend. The database may be shared with earlier runs, so
+        /// this only looks at the rows it creates.
+        async fn exercise(store: Db) {
+            let before = store.counts().await.unwrap();
+
+            let room_id = store.insert_room(new_room(1)).await.unwrap();
+            let room = store.room(room_id).await.unwrap();
+            assert_eq!(room.name, "Lunch");
+            assert_eq!(
+                (room.method, room.seats, room.tie_break, room.seed),
+                (1, 1, 2, 42)
+            );
+            assert_eq!((room.partial, room.max_ranks), (true, Some(1)));
+            assert_eq!(
+                (room.ballot_type, room.max_score, room.visibility, room.live),
+                (0, 5, 1, true)
+            );
+            assert!((3590..=3600).contains(&room.expires_in));
+            assert!(room.in_status(WAITING).is_ok());
+
+            let options = store.options(room_id).await.unwrap();
+            let labels = options.iter().map(|o| o.label.as_str()).collect::<Vec<_>>();
+            assert_eq!(labels, ["Pasta", "Pizza", "Pizza"]);
+            let [pasta, pizza, other_pizza] = [0, 1, 2].map(|idx| options[idx].id);
+            assert_ne!(pizza, other_pizza);
+
+            let first_code = utils::generate_ulid();
+            let first = store
+                .insert_voter(room_id, first_code.clone())
+                .await
+                .unwrap();
+            let second = store
+                .insert_voter(room_id, utils::generate_ulid())
+                .await
+                .unwrap();
+            assert_eq!(store.voter_count(room_id).await.unwrap(), 2);
+
+            let voter = store.voter_by_code(room_id, first_code).await.unwrap();
+            assert_eq!(voter.map(|v| v.id), Some(first));
+            let stranger = store.voter_by_code(room_id, utils::generate_ulid());
+            assert!(stranger.await.unwrap().is_none());
+
+            store.approve_voter(first).await.unwrap();
+            let voters = store.voters(room_id).await.unwrap();
+            let voters = voters
+                .iter()
+                .map(|v| (v.id, v.approved))
+                .collect::<Vec<_>>();
+            assert_eq!(voters, [(first, true), (second, false)]);
+
+            store.set_status(room_id, STARTED).await.unwrap();
+            let room = store.room(room_id).await.unwrap();
+            assert!(matches!(
+                room.in_status(WAITING),
+                Err(sqlx::Error::RowNotFound)
+            ));
+
+            let entry = |option_id, rank| BallotEntry {
+                option_id,
+                rank,
+                score: None,
+            };
+            let ballot = vec![entry(other_pizza, 0), entry(pizza, 0), entry(pasta, 1)];
+            store.record_vote(first, ballot.clone()).await.unwrap();
+            assert_eq!(store.vote_count(room_id).await.unwrap(), 1);
+            assert!(store.voter(first).await.unwrap().voted);
+            assert!(!store.voter(second).await.unwrap().voted);
+
+            // Entries come back by rank, then in ballot order.
+            let sorted = vec![entry(pizza, 0), entry(other_pizza, 0), entry(pasta, 1)];
+            assert_eq!(store.ballots(room_id).await.unwrap(), [sorted]);
+
+            // Voting again replaces the ballot, and unapproved voters' ballots don't count.
+            store
+                .record_vote(first, vec![entry(pasta, 0)])
+                .await
+                .unwrap();
+            let rated = BallotEntry {
+                option_id: pasta,
+                rank: 0,
+                score: Some(3),
+            };
+            store.record_vote(second, vec![rated]).await.unwrap();
+            assert_eq!(store.vote_count(room_id).await.unwrap(), 2);
+            assert_eq!(
+                store.ballots(room_id).await.unwrap(),
+                [vec![entry(pasta, 0)]]
+            );
+
+            store.set_status(room_id, ENDED).await.unwrap();
+            assert!(store.room(room_id).await.unwrap().in_status(ENDED).is_ok());
+
+            // Results are kept as they were first counted.
+            assert_eq!(store.result(room_id).await.unwrap(), None);
+            let label = |label: &str| label.to_owned();
+            let result = CountedResult {
+                method: 4,
+                seats: 2,
+                quota: Some(1.5),
+                tie_break: 1,
+                seed: 7,
+                partial: true,
+                max_score: 5,
+                voters: 2,
+                scores: vec![
+                    ResultScore {
+                        label: label("Pasta"),
+                        score: 1.5,
+                        elected: true,
+                        tied: false,
+                    },
+                    ResultScore {
+                        label: label("Pizza"),
+                        score: 0.5,
+                        elected: false,
+                        tied: true,
+                    },
+                ],
+                ties: vec![vec![label("Pizza"), label("Pasta")]],
+                pairwise: vec![(label("Pasta"), vec![0, 2]), (label("Pizza"), vec![0, 0])],
+                rounds: vec![
+                    ResultRound {
+                        tallies: vec![(label("Pasta"), 1.0), (label("Pizza"), 1.0)],
+                        elected: vec![],
+                        eliminated: vec![label("Pizza")],
+                        tied: vec![label("Pizza"), label("Pasta")],
+                        exhausted: 0.0,
+                        transfers: vec![
+                            (label("Pizza"), Some(label("Pasta")), 0.5),
+                            (label("Pizza"), None, 0.5),
+                        ],
+                    },
+                    ResultRound {
+                        tallies: vec![(label("Pasta"), 1.5)],
+                        elected: vec![label("Pasta")],
+                        exhausted: 0.5,
+                        ..Default::default()
+                    },
+                ],
+                runoff: Some(ResultRunoff {
+                    finalists: [(label("Pasta"), 1), (label("Pizza"), 0)],
+                    no_preference: 1,
+                }),
+                profiles: vec![
+                    (label("Pasta"), vec![0, 1, 1]),
+                    (label("Pizza"), vec![2, 0, 0]),
+                ],
+            };
+            store.insert_result(room_id, result.clone()).await.unwrap();
+            store
+                .insert_result(room_id, CountedResult::default())
+                .await
+                .unwrap();
+            assert_eq!(store.result(room_id).await.unwrap(), Some(result.clone()));
+
+            // Imported ballots come from approved voters of a room that has ended.
+            let imported = store.insert_room(new_room(1)).await.unwrap();
+            let options = store.options(imported).await.unwrap();
+            let [pasta, pizza] = [0, 1].map(|idx| options[idx].id);
+            let imports = vec![
+                (
+                    utils::generate_ulid(),
+                    vec![entry(pizza, 0), entry(pasta, 1)],
+                ),
+                (utils::generate_ulid(), vec![entry(pasta, 0)]),
+            ];
+            store.import_ballots(imported, imports).await.unwrap();
+            assert!(store.room(imported).await.unwrap().in_status(ENDED).is_ok());
+            assert_eq!(store.vote_count(imported).await.unwrap(), 2);
+            assert_eq!(
+                store.ballots(imported).await.unwrap(),
+                [
+                    vec![entry(pizza, 0), entry(pasta, 1)],
+                    vec![entry(pasta, 0)]
+                ]
+            );
+
+            let after = store.counts().await.unwrap();
+            assert!(after.rooms > before.rooms && after.voters >= before.voters + 4);
+
+            let last = store.last_event_id().await.unwrap();
+            store
+                .publish_event(room_id, Some("{}".to_owned()))
+                .await
+                .unwrap();
+            store.publish_event(room_id, None).await.unwrap();
+            let events = store
+                .events_after(last)
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|e| e.room_id == room_id)
+                .map(|e| e.event)
+                .collect::<Vec<_>>();
+            assert_eq!(events, [Some("{}".to_owned()), None]);
+            assert!(store.last_event_id().await.unwrap() > last);
+
+            let expired = store.insert_room(new_room(0)).await.unwrap();
+            let voter = store
+                .insert_voter(expired, utils::generate_ulid())
+                .await
+                .unwrap();
+            let option_id = store.options(expired).await.unwrap()[0].id;
+            store
+                .record_vote(voter, vec![entry(option_id, 0)])
+                .await
+                .unwrap();
+            store.insert_result(expired, result).await.unwrap();
+            let swept = store.sweep().await.unwrap();
+            assert!(swept.contains(&expired) && !swept.contains(&room_id));
+            assert!(matches!(
+                store.room(expired).await,
+                Err(sqlx::Error::RowNotFound)
+            ));
+            assert!(matches!(
+                store.voter(voter).await,
+                Err(sqlx::Error::RowNotFound)
+            ));
+            assert!(store.options(expired).await.unwrap().is_empty());
+            assert_eq!(store.result(expired).await.unwrap(), None);
+        }
+
+        #[tokio::test]
+        async fn sqlite() {
+            let path = std::env::temp_dir().join(format!("ordo-{}.db", utils::generate_ulid()));
+            let store = connect(&format!("sqlite:{}", path.display()))
+                .await
+                .unwrap();
+
+            exercise(store).await;
+
+            let _ = std::fs::remove_file(path);
+        }
+
+        /// Runs against the database at `TEST_POSTGRES_URL`, such as a local Postgres started
+        /// with `docker run -e POSTGRES_PASSWORD=ordo -p 5432:5432 postgres`, and is skipped
+        /// when it isn't set.
+        #[tokio::test]
+        async fn postgres() {
+            let Ok(url) = std::env::var("TEST_POSTGRES_URL") else {
+                eprintln!("TEST_POSTGRES_URL isn't set, skipping the postgres tests");
+                return;
+            };
+
+            exercise(connect(&url).await.unwrap()).await;
+        }
+
+        /// Fills a database as it was before options and ballots had their own tables, and
+        /// checks how the migration converts it.
+        #[tokio::test]
+        async fn sqlite_rows_are_converted_to_options_and_ballot_entries() {
+            let pool = sqlx::sqlite::SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await
+                .unwrap();
+
+            let mut before = sqlx::migrate!("./migrations/sqlite");
+            before.migrations = before
+                .migrations
+                .iter()
+                .filter(|m| m.version < 20261017170000)
+                .cloned()
+                .collect::<Vec<_>>()
+                .into();
+            before.run(&pool).await.unwrap();
+
+            sqlx::query(
+                r#"
+            INSERT INTO rooms (id, admin_code, name, options) VALUES
+                (1, 'a', 'Lunch', '["Pasta", "Pizza", "Pizza"]'),
+                (2, 'b', 'Broken', 'oops');
+            INSERT INTO voters (id, voter_code, room_id, options) VALUES
+                (1, 'v1', 1, '["Pizza", "Pasta"]'),
+                (2, 'v2', 1, '[["Pasta", "Pizza"]]'),
+                (3, 'v3', 1, '{"Pasta": 4, "Pizza": 2}'),
+                (4, 'v4', 1, 'oops'),
+                (5, 'v5', 1, NULL),
+                (6, 'v6', 1, '["Pasta", "Salad"]');
+                "#,
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+
+            sqlx::migrate!("./migrations/sqlite")
+                .run(&pool)
+                .await
+                .unwrap();
+
+            let options: Vec<(i64, i64, String, i64)> = sqlx::query_as(
+                "SELECT id, room_id, label, position FROM room_options ORDER BY room_id, position",
+            )
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+            let labels = options
+                .iter()
+                .map(|(_, room_id, label, position)| (*room_id, label.as_str(), *position))
+                .collect::<Vec<_>>();
+            assert_eq!(labels, [(1, "Pasta", 0), (1, "Pizza", 1), (1, "Pizza", 2)]);
+            let (pasta, pizza) = (options[0].0, options[1].0);
+
+            // Labels go to the first option with that label, and unknown labels are left out.
+            let entries: Vec<(i64, i64, i64, Option<i64>)> = sqlx::query_as(
+                "SELECT voter_id, option_id, rank, score FROM ballot_entries ORDER BY voter_id, rank, option_id",
+            )
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+            assert_eq!(
+                entries,
+                [
+                    (1, pizza, 0, None),
+                    (1, pasta, 1, None),
+                    (2, pasta, 0, None),
+                    (2, pizza, 0, None),
+                    (3, pasta, 0, Some(4)),
+                    (3, pizza, 1, Some(2)),
+                    (6, pasta, 0, None),
+                ]
+            );
+        }
+
+        /// The same conversion on PostgreSQL, in a database of its own next to the one at
+        /// `TEST_POSTGRES_URL`.
+        #[tokio::test]
+        async fn postgres_rows_are_converted_to_options_and_ballot_entries() {
+            use sqlx::migrate::MigrateDatabase;
+
+            let Ok(url) = std::env::var("TEST_POSTGRES_URL") else {
+                eprintln!("TEST_POSTGRES_URL isn't set, skipping the postgres tests");
+                return;
+            };
+            let base = &url[..url.rfind('/').unwrap()];
+            let url = format!("{base}/ordo_{}", utils::generate_ulid().to_lowercase());
+            sqlx::Postgres::create_database(&url).await.unwrap();
+            let pool = sqlx::postgres::PgPoolOptions::new()
+                .max_connections(1)
+                .connect(&url)
+                .await
+                .unwrap();
+
+            let mut before = sqlx::migrate!("./migrations/postgres");
+            before.migrations = before
+                .migrations
+                .iter()
+                .filter(|m| m.version < 20261017170000)
+                .cloned()
+                .collect::<Vec<_>>()
+                .into();
+            before.run(&pool).await.unwrap();
+
+            sqlx::raw_sql(
+                r#"
+            INSERT INTO rooms (id, admin_code, name, options, expires_at) VALUES
+                (1, 'a', 'Lunch', '["Pasta", "Pizza", "Pizza"]', now());
+            INSERT INTO voters (id, voter_code, room_id, options) VALUES
+                (1, 'v1', 1, '["Pizza", "Pasta"]'),
+                (2, 'v2', 1, '[["Pasta", "Pizza"]]'),
+                (3, 'v3', 1, '{"Pasta": 4, "Pizza": 2}'),
+                (4, 'v4', 1, NULL),
+                (5, 'v5', 1, '["Pasta", "Salad"]');
+                "#,
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+
+            sqlx::migrate!("./migrations/postgres")
+                .run(&pool)
+                .await
+                .unwrap();
+
+            let options: Vec<(i64, i64, String, i64)> = sqlx::query_as(
+                "SELECT id, room_id, label, position FROM room_options ORDER BY room_id, position",
+            )
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+            let labels = options
+                .iter()
+                .map(|(_, room_id, label, position)| (*room_id, label.as_str(), *position))
+                .collect::<Vec<_>>();
+            assert_eq!(labels, [(1, "Pasta", 0), (1, "Pizza", 1), (1, "Pizza", 2)]);
+            let (pasta, pizza) = (options[0].0, options[1].0);
+
+            let entries: Vec<(i64, i64, i64, Option<i64>)> = sqlx::query_as(
+                "SELECT voter_id, option_id, rank, score FROM ballot_entries ORDER BY voter_id, rank, option_id",
+            )
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+            assert_eq!(
+                entries,
+                [
+                    (1, pizza, 0, None),
+                    (1, pasta, 1, None),
+                    (2, pasta, 0, None),
+                    (2, pizza, 0, None),
+                    (3, pasta, 0, Some(4)),
+                    (3, pizza, 1, Some(2)),
+                    (5, pasta, 0, None),
+                ]
+            );
+
+            pool.close().await;
+            sqlx::Postgres::drop_database(&url).await.unwrap();
+        }
+    }
+}
+
+mod sweeper {
+    use std::time::Duration;
+
+    use crate::{events::Broadcasters, store::Db};
+
+    /// Deletes expired rooms at startup and then every `interval`, so rooms left behind by a
+    /// restart are still cleaned up.
+    pub async fn run(store: Db, broadcasters: Broadcasters, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            ticker.tick().await;
+
+            match store.sweep().await {
+                Ok(room_ids) => {
+                    if !room_ids.is_empty() {
+                        tracing::debug!("deleted expired rooms: {room_ids:?}");
+                    }
+
+                    for room_id in room_ids {
+                        broadcasters.end_stream(room_id).await;
+                    }
+                }
+                Err(e) => tracing::error!("error while deleting expired rooms: {e}"),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::time::Duration;
+
+        use crate::{events::Broadcasters, tests};
+
+        #[tokio::test]
+        async fn expired_rooms_are_deleted_and_their_streams_end() {
+            for store in tests::stores().await {
+                let broadcasters = Broadcasters::new();
+                let expired = store.insert_room(tests::new_room(0)).await.unwrap();
+                let open = store.insert_room(tests::new_room(1)).await.unwrap();
+
+                let app = tests::app(store.clone(), broadcasters.clone());
+                let mut client = warp::test::ws()
+                    .path(&format!("/rooms/{expired}/ws"))
+                    .handshake(app)
+                    .await
+                    .unwrap();
+
+                // A reply to an action means the socket is following the room's events.
+                client.send_text("{}").await;
+                let reply = client.recv().await.unwrap();
+                assert!(reply.to_str().unwrap().contains("BAD_REQUEST"));
+
+                tokio::spawn(super::run(
+                    store.clone(),
+                    broadcasters,
+                    Duration::from_secs(3600),
+                ));
+
+                let closed = tokio::time::timeout(Duration::from_secs(5), client.recv_closed());
+                assert!(closed.await.is_ok());
+                assert!(matches!(
+                    store.room(expired).await,
+                    Err(sqlx::Error::RowNotFound)
+                ));
+                assert!(store.room(open).await.is_ok());
+            }
+        }
+    }
+}
+
+mod events {
+    use std::{
+        collections::{HashMap, HashSet, VecDeque},
+        convert::Infallible,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use futures_util::{future::BoxFuture, SinkExt};
+    use maud::html;
+    use serde::{Deserialize, Serialize};
+    use tokio::sync::{
+        broadcast::{self, Sender},
+        Mutex,
+    };
+    use tokio_stream::{
+        wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+        Stream, StreamExt,
+    };
+    use warp::{
+        filters::{
+            sse::{self, Event},
+            ws::{Message, WebSocket, Ws},
+        },
+        Filter,
+    };
+
+    use crate::{
+        names,
+        rejections::{self, InternalServerError, NotRoomAdmin, NotVoter},
+        store::Db,
+        utils,
+        voters::{self, BallotRules, VoteBody},
+        voting::{self, ResultPage},
+        with_state,
+    };
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub enum RoomEvents {
+        NewVoter(i64),
+        NewVoterCount(i32),
+        VoterApproved(i64),
+        VoteStartable(i64),
+        VoteEndable(i64),
+        VoteStarted(BallotRules),
+        /// The vote ended, with its results when voters may see them.
+        VoteEnded(Option<Box<ResultPage>>),
+        /// The standings of a live room after a new ballot, and whether approved voters may see
+        /// them.
+        LiveResults(Box<ResultPage>, bool),
+        NewVote(i64),
+        NewVoteCount(i32),
+        /// Someone opened their first or closed their last connection to the room.
+        Presence(Member, bool),
+    }
+
+    /// Someone listening to a room's events.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub enum Member {
+        Admin,
+        Voter(i64),
+    }
+
+    /// How many past events each room keeps for listeners that reconnect.
+    const REPLAY_CAPACITY: usize = 64;
+
+    /// The events of one room on this instance.
+    #[derive(Debug)]
+    struct Channel {
+        tx: Sender<(u64, RoomEvents)>,
+        /// The id of the latest event, or where this instance started receiving events.
+        last_id: u64,
+        /// Events after `dropped` can be replayed from `history`.
+        dropped: u64,
+        history: VecDeque<(u64, RoomEvents)>,
+        /// How many connections each member has open on this instance.
+        present: HashMap<Member, usize>,
+        /// How many instances each member is connected to.
+        online: HashMap<Member, usize>,
+    }
+
+    impl Channel {
+        fn new(start: u64) -> Self {
+            Self {
+                tx: broadcast::channel(16).0,
+                last_id: start,
+                dropped: start,
+                history: VecDeque::with_capacity(REPLAY_CAPACITY),
+                present: HashMap::new(),
+                online: HashMap::new(),
+            }
+        }
+
+        fn send(&mut self, id: u64, event: RoomEvents) {
+            self.last_id = id;
+            if self.history.len() == REPLAY_CAPACITY {
+                if let Some((dropped, _)) = self.history.pop_front() {
+                    self.dropped = dropped;
+                }
+            }
+            self.history.push_back((id, event.clone()));
+
+            let res = self.tx.send((id, event));
+            tracing::debug!("send event result: {res:?}");
+        }
+
+        /// Counts a member coming online or going offline on some instance, and tells whether
+        /// that changes whether they are online at all.
+        fn track(&mut self, member: Member, online: bool) -> bool {
+            if online {
+                let count = self.online.entry(member).or_default();
+                *count += 1;
+                return *count == 1;
+            }
+
+            match self.online.get_mut(&member) {
+                Some(count) if *count > 1 => {
+                    *count -= 1;
+                    false
+                }
+                Some(_) => {
+                    self.online.remove(&member);
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+
+    /// Keeps members marked as connected to a room until it is dropped.
+    struct Connection {
+        broadcasters: Broadcasters,
+        room_id: i64,
+        members: Vec<Member>,
+    }
+
+    impl Drop for Connection {
+        fn drop(&mut self) {
+            let broadcasters = self.broadcasters.clone();
+            let room_id = self.room_id;
+            let members = std::mem::take(&mut self.members);
+
+            tokio::spawn(async move { broadcasters.disconnect(room_id, members).await });
+        }
+    }
+
+    /// What a listener receives: an event with its id, or word that it missed events that
+    /// can no longer be replayed and has to render the page again.
+    enum Streamed {
+        Event(u64, RoomEvents),
+        Reload,
+    }
+
+    /// The rooms listened to on this instance.
+    #[derive(Clone)]
+    pub struct Listeners {
+        map: Arc<Mutex<HashMap<i64, Channel>>>,
+        /// Events up to this id were published before this instance started.
+        start: u64,
+    }
+
+    impl Listeners {
+        fn new(start: u64) -> Self {
+            Self {
+                map: Default::default(),
+                start,
+            }
+        }
+
+        /// Sends a published event to the listeners of its room, or ends their streams when
+        /// `event` is `None`.
+        async fn deliver(&self, room_id: i64, id: u64, event: Option<RoomEvents>) {
+            let mut map = self.map.lock().await;
+            let Some(event) = event else {
+                let res = map.remove(&room_id);
+                tracing::debug!("end stream result: {res:?}");
+                return;
+            };
+
+            let channel = map
+                .entry(room_id)
+                .or_insert_with(|| Channel::new(self.start));
+
+            if let RoomEvents::Presence(member, online) = event {
+                if !channel.track(member, online) {
+                    return;
+                }
+            }
+
+            channel.send(id, event);
+        }
+    }
+
+    /// How published events reach the listeners of every instance.
+    pub trait Backend: Send + Sync {
+        /// Publishes an event to a room, or the end of its streams when `event` is `None`.
+        fn publish(&self, room_id: i64, event: Option<RoomEvents>) -> BoxFuture<'_, ()>;
+    }
+
+    /// Delivers events straight to the listeners of this instance, for a single instance.
+    pub struct Memory {
+        listeners: Listeners,
+        last_id: AtomicU64,
+    }
+
+    impl Backend for Memory {
+        fn publish(&self, room_id: i64, event: Option<RoomEvents>) -> BoxFuture<'_, ()> {
+            let id = self.last_id.fetch_add(1, Ordering::SeqCst) + 1;
+            Box::pin(self.listeners.deliver(room_id, id, event))
+        }
+    }
+
+    /// Shares events through the `events` table, which every instance polls, so instances
+    /// sharing a database see each other's events. The table's ids number the events.
+    pub struct Database {
+        store: Db,
+    }
+
+    impl Backend for Database {
+        fn publish(&self, room_id: i64, event: Option<RoomEvents>) -> BoxFuture<'_, ()> {
+            Box::pin(async move {
+                let event = match event.map(|event| serde_json::to_string(&event)).transpose() {
+                    Ok(event) => event,
+                    Err(e) => {
+                        tracing::error!("error while encoding event: {e}");
+                        return;
+                    }
+                };
+
+                if let Err(e) = self.store.publish_event(room_id, event).await {
+                    tracing::error!("error while publishing event: {e}");
+                }
+            })
+        }
+    }
+
+    impl Database {
+        /// Delivers the events published by every instance to the listeners of this one.
+        async fn poll(store: Db, listeners: Listeners, mut last_id: i64, interval: Duration) {
+            let mut interval = tokio::time::interval(interval);
+
+            loop {
+                interval.tick().await;
+
+                let events = store.events_after(last_id).await;
+
+                let events = match events {
+                    Ok(events) => events,
+                    Err(e) => {
+                        tracing::error!("error while polling events: {e}");
+                        continue;
+                    }
+                };
+
+                for row in events {
+                    last_id = row.id;
+                    let event = match row.event.as_deref().map(serde_json::from_str) {
+                        Some(Ok(event)) => Some(event),
+                        Some(Err(e)) => {
+                            tracing::error!("error while decoding event {}: {e}", row.id);
+                            continue;
+                        }
+                        None => None,
+                    };
+
+                    listeners.deliver(row.room_id, row.id as u64, event).await;
+                }
+            }
+        }
+    }
+
+    #[derive(Clone)]
+    pub struct Broadcasters {
+        listeners: Listeners,
+        backend: Arc<dyn Backend>,
+    }
+
+    impl Broadcasters {
+        /// Broadcasters for a single instance, which keep events in memory.
+        pub fn new() -> Self {
+            let listeners = Listeners::new(0);
+            let backend = Memory {
+                listeners: listeners.clone(),
+                last_id: AtomicU64::new(0),
+            };
+
+            Self {
+                listeners,
+                backend: Arc::new(backend),
+            }
+        }
+
+        /// Broadcasters that share events with every instance using the same database,
+        /// looking for new events every `interval`.
+        pub async fn database(store: Db, interval: Duration) -> Result<Self, sqlx::Error> {
+            let start = store.last_event_id().await?;
+
+            let listeners = Listeners::new(start as u64);
+            tokio::spawn(Database::poll(
+                store.clone(),
+                listeners.clone(),
+                start,
+                interval,
+            ));
+
+            Ok(Self {
+                listeners,
+                backend: Arc::new(Database { store }),
+            })
+        }
+
+        pub async fn send_event(&self, room_id: i64, event: RoomEvents) {
+            self.backend.publish(room_id, Some(event)).await;
+        }
+
+        /// The voters of a room connected to any instance.
+        pub async fn online_voters(&self, room_id: i64) -> HashSet<i64> {
+            let map = self.listeners.map.lock().await;
+            map.get(&room_id)
+                .map(|channel| {
+                    channel
+                        .online
+                        .keys()
+                        .filter_map(|member| match member {
+                            Member::Voter(voter_id) => Some(*voter_id),
+                            Member::Admin => None,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+
+        /// Marks members as connected to a room, announcing those who weren't already.
+        async fn connect(&self, room_id: i64, members: Vec<Member>) -> Connection {
+            let mut joined = Vec::new();
+            {
+                let mut map = self.listeners.map.lock().await;
+                let channel = map
+                    .entry(room_id)
+                    .or_insert_with(|| Channel::new(self.listeners.start));
+
+                for member in &members {
+                    let count = channel.present.entry(*member).or_default();
+                    *count += 1;
+                    if *count == 1 {
+                        joined.push(*member);
+                    }
+                }
+            }
+
+            for member in joined {
+                self.send_event(room_id, RoomEvents::Presence(member, true))
+                    .await;
+            }
+
+            Connection {
+                broadcasters: self.clone(),
+                room_id,
+                members,
+            }
+        }
+
+        async fn disconnect(&self, room_id: i64, members: Vec<Member>) {
+            let mut left = Vec::new();
+            {
+                let mut map = self.listeners.map.lock().await;
+                let Some(channel) = map.get_mut(&room_id) else {
+                    return;
+                };
+
+                for member in members {
+                    if let Some(count) = channel.present.get_mut(&member) {
+                        *count -= 1;
+                        if *count == 0 {
+                            channel.present.remove(&member);
+                            left.push(member);
+                        }
+                    }
+                }
+            }
+
+            for member in left {
+                self.send_event(room_id, RoomEvents::Presence(member, false))
+                    .await;
+            }
+        }
+
+        /// The events of a room from now on, preceded by those after `last_event_id` when a
+        /// listener reconnects.
+        async fn get_stream(
+            &self,
+            room_id: i64,
+            last_event_id: Option<u64>,
+        ) -> impl Stream<Item = Streamed> {
+            let mut map = self.listeners.map.lock().await;
+            let channel = map
+                .entry(room_id)
+                .or_insert_with(|| Channel::new(self.listeners.start));
+            let rx = channel.tx.subscribe();
+
+            let missed = match last_event_id {
+                None => Vec::new(),
+                Some(last) if last == channel.last_id => Vec::new(),
+                Some(last) if last < channel.last_id && last >= channel.dropped => channel
+                    .history
+                    .iter()
+                    .filter(|(id, _)| *id > last)
+                    .map(|(id, event)| Streamed::Event(*id, event.clone()))
+                    .collect(),
+                Some(last) => {
+                    tracing::debug!("events after {last} in room {room_id} are gone");
+                    vec![Streamed::Reload]
+                }
+            };
+
+            let live = BroadcastStream::new(rx).map(|event| match event {
+                Ok((id, event)) => Streamed::Event(id, event),
+                Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                    tracing::warn!("listener lagged behind by {skipped} events");
+                    Streamed::Reload
+                }
+            });
+
+            tokio_stream::iter(missed).chain(live)
+        }
+
+        pub async fn end_stream(&self, room_id: i64) {
+            self.backend.publish(room_id, None).await;
+        }
+    }
+
+    /// A room event as sent on the JSON stream.
+    #[derive(Serialize)]
+    #[serde(tag = "type", rename_all = "kebab-case")]
+    enum JsonEvent {
+        NewVoter {
+            voter_id: i64,
+        },
+        VoterCount {
+            count: i32,
+        },
+        VoterApproved {
+            voter_id: i64,
+        },
+        VoteStartable,
+        VoteEndable,
+        VoteStarted {
+            ballot: BallotRules,
+        },
+        VoteEnded {
+            /// The results, when voters may see them.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            results: Option<Box<ResultPage>>,
+        },
+        LiveResults {
+            results: Box<ResultPage>,
+        },
+        NewVote {
+            voter_id: i64,
+        },
+        VoteCount {
+            count: i32,
+        },
+        VoterPresence {
+            voter_id: i64,
+            online: bool,
+        },
+        AdminPresence {
+            online: bool,
+        },
+        /// Events were missed and can't be replayed, so the listener should fetch the room again.
+        Reload,
+    }
+
+    pub fn route(
+        store: Db,
+        broadcasters: Broadcasters,
+    ) -> impl warp::Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let listen = warp::path!("rooms" / i64 / "listen")
+            .and(with_state(store.clone()))
+            .and(with_state(broadcasters.clone()))
+            .and(warp::cookie::optional(names::ROOM_ADMIN_COOKIE_NAME))
+            .and(warp::cookie::optional(names::VOTER_COOKIE_NAME))
+            .and(warp::header::optional::<String>("last-event-id"))
+            .and_then(handler);
+
+        let json = warp::path!("rooms" / i64 / "events.json")
+            .and(with_state(store.clone()))
+            .and(with_state(broadcasters.clone()))
+            .and(warp::cookie::optional(names::ROOM_ADMIN_COOKIE_NAME))
+            .and(warp::cookie::optional(names::VOTER_COOKIE_NAME))
+            .and(warp::header::optional::<String>("authorization"))
+            .and(warp::header::optional::<String>("last-event-id"))
+            .and_then(json_handler);
+
+        let socket = warp::path!("rooms" / i64 / "ws")
+            .and(warp::ws())
+            .and(with_state(store))
+            .and(with_state(broadcasters))
+            .and(warp::cookie::optional(names::ROOM_ADMIN_COOKIE_NAME))
+            .and(warp::cookie::optional(names::VOTER_COOKIE_NAME))
+            .and(warp::header::optional::<String>("authorization"))
+            .and(warp::query::<SocketQuery>())
+            .and_then(socket_handler);
+
+        listen.or(json).or(socket)
+    }
+
+    /// Someone listening to a room's events, as far as what they may see goes.
+    #[derive(Clone, Copy, Debug, Default)]
+    struct Listener {
+        /// The room ID when the listener is its admin.
+        admin: Option<i64>,
+        voter: Option<i64>,
+        /// Whether the voter has been approved. Only approved voters see results.
+        approved: bool,
+    }
+
+    impl Listener {
+        /// Notes the approval of the listener's voter when its event goes by.
+        fn hear(&mut self, event: &RoomEvents) {
+            if let RoomEvents::VoterApproved(voter_id) = event {
+                self.approved |= self.voter == Some(*voter_id);
+            }
+        }
+    }
+
+    /// Finds out whether a listener is the admin of the room and which voter they are, from
+    /// their admin and voter codes.
+    async fn listener(
+        store: &Db,
+        room_id: i64,
+        admin_code: Option<String>,
+        voter_code: Option<String>,
+    ) -> Result<Listener, warp::Rejection> {
+        if admin_code.is_none() && voter_code.is_none() {
+            return Ok(Listener::default());
+        }
+
+        let room = store.room(room_id).await.map_err(|e| {
+            tracing::error!("error while getting room: {e}");
+            match e {
+                sqlx::Error::RowNotFound => warp::reject::custom(rejections::RoomNotFound),
+                _ => warp::reject::custom(InternalServerError),
+            }
+        })?;
+        let admin = admin_code
+            .filter(|admin_code| *admin_code == room.admin_code)
+            .map(|_| room.id);
+
+        let voter = match voter_code {
+            Some(voter_code) => store
+                .voter_by_code(room_id, voter_code)
+                .await
+                .map_err(|e| {
+                    tracing::error!("error while getting voter: {e}");
+                    warp::reject::custom(InternalServerError)
+                })?,
+            None => None,
+        };
+
+        Ok(Listener {
+            admin,
+            voter: voter.as_ref().map(|v| v.id),
+            approved: voter.is_some_and(|v| v.approved),
+        })
+    }
+
+    fn members(listener: &Listener) -> Vec<Member> {
+        listener
+            .admin
+            .map(|_| Member::Admin)
+            .into_iter()
+            .chain(listener.voter.map(Member::Voter))
+            .collect()
+    }
+
+    /// The name and HTML of an event for htmx, or `None` when the listener shouldn't see it.
+    fn html_event(event: RoomEvents, listener: &Listener) -> Option<(String, String)> {
+        use RoomEvents::*;
+
+        let (name, data) = match (event, listener.admin, listener.voter) {
+            (NewVoterCount(count), Some(_), None) | (NewVoterCount(count), None, Some(_)) => (
+                names::VOTER_COUNT_EVENT.to_owned(),
+                html! {
+                    p."stat__num" data-testid="voter-count" { (utils::format_num(count)) }
+                    p."stat__desc" { (utils::pluralize(count, "voter", "voters")) " in room" }
+                },
+            ),
+
+            (NewVoter(voter_id), Some(_), None) => (
+                names::NEW_VOTER_EVENT.to_owned(),
+                html! {
+                    div."flex gap-md" {
+                        span."strech code" {
+                            span { "VOTER ID" }
+                            span { (voter_id) }
+                        }
+                        (voting::presence(voter_id, false))
+                        button."button w-fit" hx-put=(names::approve_voter_url(voter_id)) hx-swap="outerHTML" { "APPROVE" }
+                    }
+                },
+            ),
+
+            (VoterApproved(voter_id), Some(_), None) => (
+                names::voter_approved_event(voter_id),
+                html! {
+                    button."button w-fit" disabled { "APPROVED" }
+                },
+            ),
+
+            (VoterApproved(voter_id), None, Some(voter)) if voter_id == voter => (
+                names::voter_approved_event(voter_id),
+                html! {
+                    div."alert" { "VOTER HAS BEEN APPROVED." }
+                },
+            ),
+
+            (VoteStarted(rules), None, Some(voter_id)) => (
+                names::VOTE_STARTED_EVENT.to_owned(),
+                voters::ballot_form(voter_id, &rules),
+            ),
+
+            (NewVote(voter_id), Some(_), None) => (
+                names::vote_event(voter_id),
+                html! {
+                    span."boxed" { "VOTED" }
+                },
+            ),
+
+            (Presence(Member::Voter(voter_id), online), Some(_), None) => (
+                names::presence_event(voter_id),
+                voting::presence(voter_id, online),
+            ),
+
+            (NewVoteCount(votes), Some(_), None) => (
+                names::VOTE_COUNT_EVENT.to_owned(),
+                html! {
+                    p."stat__num" data-testid="votes-count" { (utils::format_num(votes)) }
+                    p."stat__desc" { "recorded " (utils::pluralize(votes, "vote", "votes")) }
+                },
+            ),
+
+            (VoteEnded(results), None, Some(_)) => (
+                names::VOTE_ENDED_EVENT.to_owned(),
+                html! {
+                    div."alert" { "VOTES HAVE ENDED." }
+                    @if let Some(page) = results.filter(|_| listener.approved) {
+                        (voting::result_page(*page, false))
+                    }
+                },
+            ),
+
+            (LiveResults(page, _), Some(_), None) | (LiveResults(page, true), None, Some(_))
+                if listener.admin.is_some() || listener.approved =>
+            {
+                (
+                    names::LIVE_RESULTS_EVENT.to_owned(),
+                    voting::live_results(&page),
+                )
+            }
+
+            (VoteStartable(room_id), Some(_), None) => (
+                names::VOTE_STARTABLE_EVENT.to_owned(),
+                html! {
+                    button."button text-lg align-left"
+                        hx-put=(names::start_vote_url(room_id))
+                        hx-target="main"
+                        hx-swap="innerHTML" { "START VOTE" }
+                },
+            ),
+
+            (VoteEndable(room_id), Some(_), None) => (
+                names::VOTE_ENDABLE_EVENT.to_owned(),
+                html! {
+                    button."button text-lg align-left"
+                        hx-put=(names::end_vote_url(room_id))
+                        hx-target="main"
+                        hx-swap="innerHTML" { "END VOTE" }
+                },
+            ),
+
+            _ => return None,
+        };
+
+        Some((name, data.into_string()))
+    }
+
+    /// An event as sent on the JSON stream, or `None` when the listener shouldn't see it.
+    fn json_event(event: RoomEvents, listener: &Listener) -> Option<JsonEvent> {
+        use RoomEvents::*;
+
+        let event = match (event, listener.admin, listener.voter) {
+            (NewVoterCount(count), Some(_), _) | (NewVoterCount(count), _, Some(_)) => {
+                JsonEvent::VoterCount { count }
+            }
+            (NewVoter(voter_id), Some(_), _) => JsonEvent::NewVoter { voter_id },
+            (VoterApproved(voter_id), Some(_), _) => JsonEvent::VoterApproved { voter_id },
+            (VoterApproved(voter_id), None, Some(voter)) if voter_id == voter => {
+                JsonEvent::VoterApproved { voter_id }
+            }
+            (VoteStarted(ballot), Some(_), _) | (VoteStarted(ballot), _, Some(_)) => {
+                JsonEvent::VoteStarted { ballot }
+            }
+            (VoteEnded(results), Some(_), _) => JsonEvent::VoteEnded { results },
+            (VoteEnded(results), None, Some(_)) => JsonEvent::VoteEnded {
+                results: results.filter(|_| listener.approved),
+            },
+            (LiveResults(results, _), Some(_), _) | (LiveResults(results, true), _, Some(_))
+                if listener.admin.is_some() || listener.approved =>
+            {
+                JsonEvent::LiveResults { results }
+            }
+            (NewVote(voter_id), Some(_), _) => JsonEvent::NewVote { voter_id },
+            (NewVoteCount(count), Some(_), _) => JsonEvent::VoteCount { count },
+            (VoteStartable(_), Some(_), _) => JsonEvent::VoteStartable,
+            (VoteEndable(_), Some(_), _) => JsonEvent::VoteEndable,
+            (Presence(Member::Voter(voter_id), online), Some(_), _) => {
+                JsonEvent::VoterPresence { voter_id, online }
+            }
+            (Presence(Member::Admin, online), Some(_), _)
+            | (Presence(Member::Admin, online), _, Some(_)) => JsonEvent::AdminPresence { online },
+            _ => return None,
+        };
+
+        Some(event)
+    }
 
-        Ok(html! {
-            button."button w-fit" disabled { "APPROVED" }
-        })
+    /// The code sent as `Authorization: Bearer <code>`.
+    fn bearer(authorization: Option<String>) -> Option<String> {
+        authorization
+            .as_deref()
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map(|code| code.trim().to_owned())
     }
 
-    async fn vote(
-        conn: sqlx::Pool<sqlx::Sqlite>,
+    fn reload_event() -> Event {
+        Event::default()
+            .event(names::RELOAD_EVENT)
+            .data(names::RELOAD_EVENT)
+    }
+
+    async fn handler(
+        room_id: i64,
+        store: Db,
         broadcasters: Broadcasters,
-        voter_id: i64,
-        voter_code: String,
-        body: VoteBody,
+        admin_code: Option<String>,
+        voter_code: Option<String>,
+        last_event_id: Option<String>,
     ) -> Result<impl warp::Reply, warp::Rejection> {
-        let voter = sqlx::query!(
-            r#"
-        SELECT voter_code, approved, room_id
-        FROM voters
-        WHERE id = ?1
-            "#,
-            voter_id
-        )
-        .fetch_one(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while getting voter: {e}");
-            warp::reject::custom(InternalServerError)
-        })?;
+        let mut listener = listener(&store, room_id, admin_code, voter_code).await?;
 
-        if voter_code != voter.voter_code {
-            return Err(warp::reject::custom(NotVoter));
-        }
-
-        let room_options = sqlx::query!(
-            r#"
-        SELECT options
-        FROM rooms
-        WHERE id = ?1 AND status = 1
-            "#,
-            voter.room_id
-        )
-        .fetch_one(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while getting room: {e}");
-            warp::reject::custom(InternalServerError)
-        })?
-        .options;
+        let stream = broadcasters
+            .get_stream(room_id, last_event_id.and_then(|id| id.parse().ok()))
+            .await;
+        let connection = broadcasters.connect(room_id, members(&listener)).await;
+        let stream = stream
+            .map(move |streamed| {
+                let _connection = &connection;
+                let (id, event) = match streamed {
+                    Streamed::Event(id, event) => (id, event),
+                    Streamed::Reload => return reload_event(),
+                };
+                tracing::debug!("new event received: {event:?}");
 
-        let room_options: Vec<String> = serde_json::from_str(&room_options).unwrap();
-        let mut voter_options = body.options.clone();
-        voter_options.sort();
+                listener.hear(&event);
+                let event = match html_event(event, &listener) {
+                    Some((name, data)) => Event::default().event(name).data(data),
+                    None => Event::default().event(names::PING_EVENT),
+                };
 
-        if room_options != voter_options {
-            return Err(warp::reject::custom(UnknownOptions));
-        }
+                event.id(id.to_string())
+            })
+            .map(Ok::<_, Infallible>);
 
-        let options = serde_json::to_string(&body.options).unwrap();
+        Ok(sse::reply(stream))
+    }
 
-        let _ = sqlx::query!(
-            r#"
-        UPDATE voters
-        SET options = ?1
-        WHERE id = ?2
-            "#,
-            options,
-            voter_id
+    /// Like [`handler`], but sends each event as a JSON object with a `type` field instead of
+    /// HTML. The admin and voter codes can also be sent as `Authorization: Bearer <code>`.
+    async fn json_handler(
+        room_id: i64,
+        store: Db,
+        broadcasters: Broadcasters,
+        admin_code: Option<String>,
+        voter_code: Option<String>,
+        authorization: Option<String>,
+        last_event_id: Option<String>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let token = bearer(authorization);
+        let mut listener = listener(
+            &store,
+            room_id,
+            admin_code.or(token.clone()),
+            voter_code.or(token),
         )
-        .execute(&conn)
-        .await
-        .map_err(|e| {
-            tracing::error!("error while storing vote options: {e}");
-            warp::reject::custom(InternalServerError)
-        });
+        .await?;
 
-        tokio::spawn(async move {
-            broadcasters
-                .send_event(voter.room_id, RoomEvents::NewVote(voter_id))
-                .await;
+        let stream = broadcasters
+            .get_stream(room_id, last_event_id.and_then(|id| id.parse().ok()))
+            .await;
+        let connection = broadcasters.connect(room_id, members(&listener)).await;
+        let stream = stream
+            .map(move |streamed| {
+                let _connection = &connection;
+                let (id, event) = match streamed {
+                    Streamed::Event(id, event) => (id, event),
+                    Streamed::Reload => {
+                        return Event::default()
+                            .json_data(JsonEvent::Reload)
+                            .unwrap_or_else(|_| reload_event())
+                    }
+                };
 
-            if let Ok(votes) = sqlx::query!(
-                r#"
-            SELECT count(id) as count
-            FROM voters
-            WHERE room_id = ?1 AND options NOT NULL
-                "#,
-                voter.room_id
-            )
-            .fetch_one(&conn)
-            .await
-            .map(|r| r.count)
-            {
-                broadcasters
-                    .send_event(voter.room_id, RoomEvents::NewVoteCount(votes))
-                    .await;
+                listener.hear(&event);
+                let event = match json_event(event, &listener) {
+                    Some(event) => Event::default()
+                        .json_data(event)
+                        .unwrap_or_else(|_| Event::default().event(names::PING_EVENT)),
+                    None => Event::default().event(names::PING_EVENT),
+                };
 
-                broadcasters
-                    .send_event(voter.room_id, RoomEvents::VoteEndable(voter.room_id))
-                    .await;
-            }
-        });
+                event.id(id.to_string())
+            })
+            .map(Ok::<_, Infallible>);
 
-        Ok(html! {
-            h2."text-md" { "THANKS FOR VOTING!" }
-        })
+        Ok(sse::reply(stream))
     }
-}
 
-mod voting {
-    use maud::{html, Markup, PreEscaped};
+    /// Whether a WebSocket carries events as HTML for htmx or as JSON.
+    #[derive(Clone, Copy, Default, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    enum Format {
+        #[default]
+        Html,
+        Json,
+    }
 
-    use crate::{names, utils};
+    #[derive(Deserialize)]
+    struct SocketQuery {
+        #[serde(default)]
+        format: Format,
+        /// Like the `Last-Event-ID` header of the event streams.
+        last_event_id: Option<u64>,
+        /// The admin or voter code, for clients that can't set cookies or headers.
+        token: Option<String>,
+    }
 
-    pub struct VoteAdminPage {
-        pub room_id: i64,
-        pub room_name: String,
-        pub recorded_votes: i32,
-        pub approved_voters: Vec<Voter>,
+    /// An event as sent on a WebSocket, with its id so a client can resume after it.
+    #[derive(Serialize)]
+    struct SocketEvent<T> {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<u64>,
+        #[serde(flatten)]
+        event: T,
     }
 
-    pub struct Voter {
-        pub id: i64,
-        pub voted: bool,
+    #[derive(Serialize)]
+    struct HtmlEvent {
+        event: String,
+        data: String,
     }
 
-    pub fn admin_page(page: VoteAdminPage) -> Markup {
-        let approved_count = utils::format_num(page.approved_voters.len() as i32);
-        let approved_label = utils::pluralize(page.approved_voters.len() as i32, "voter", "voters");
+    /// What a WebSocket client can do, sent as a JSON text message.
+    #[derive(Deserialize)]
+    #[serde(tag = "action", rename_all = "kebab-case")]
+    enum Action {
+        Vote(VoteBody),
+        Approve { voter_id: i64 },
+    }
 
-        let recorded_votes = utils::format_num(page.recorded_votes);
-        let recorded_votes_label = utils::pluralize(page.recorded_votes, "vote", "votes");
+    /// Who is on the other end of a WebSocket, with the codes their actions are checked with.
+    struct Client {
+        listener: Listener,
+        admin_code: Option<String>,
+        voter_code: Option<String>,
+    }
 
-        html! {
-            section."grid gap-lg w-800" hx-ext="sse" sse-connect=(names::room_listen_url(page.room_id)) {
-                h1."text-lg" { (page.room_name) }
+    #[allow(clippy::too_many_arguments)]
+    async fn socket_handler(
+        room_id: i64,
+        ws: Ws,
+        store: Db,
+        broadcasters: Broadcasters,
+        admin_code: Option<String>,
+        voter_code: Option<String>,
+        authorization: Option<String>,
+        query: SocketQuery,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let token = bearer(authorization).or(query.token);
+        let admin_code = admin_code.or(token.clone());
+        let voter_code = voter_code.or(token);
+        let listener = listener(&store, room_id, admin_code.clone(), voter_code.clone()).await?;
+
+        let client = Client {
+            admin_code: admin_code.filter(|_| listener.admin.is_some()),
+            voter_code: voter_code.filter(|_| listener.voter.is_some()),
+            listener,
+        };
 
-                div."alert" { "ROOM WILL CLOSE IN LESS THAN AN HOUR." }
+        Ok(ws.on_upgrade(move |socket| {
+            session(
+                socket,
+                store,
+                broadcasters,
+                room_id,
+                query.format,
+                query.last_event_id,
+                client,
+            )
+        }))
+    }
 
-                section."two-cols" {
-                    div."card card--secondary stat" {
-                        p."stat__num" { (approved_count) }
-                        p."stat__desc" { "approved " (approved_label) }
+    /// Sends the events of a room over a WebSocket, like the event streams, while carrying out
+    /// the actions the client sends back.
+    async fn session(
+        socket: WebSocket,
+        store: Db,
+        broadcasters: Broadcasters,
+        room_id: i64,
+        format: Format,
+        last_event_id: Option<u64>,
+        client: Client,
+    ) {
+        let (mut tx, mut rx) = futures_util::StreamExt::split(socket);
+        let events = broadcasters.get_stream(room_id, last_event_id).await;
+        tokio::pin!(events);
+
+        let mut listener = client.listener;
+        let _connection = broadcasters.connect(room_id, members(&listener)).await;
+
+        loop {
+            let message = tokio::select! {
+                streamed = events.next() => {
+                    let Some(streamed) = streamed else { break };
+                    if let Streamed::Event(_, event) = &streamed {
+                        listener.hear(event);
                     }
-
-                    div."card stat" hx-swap="innerHTML" sse-swap=(names::VOTE_COUNT_EVENT) {
-                        p."stat__num" data-testid="votes-count" { (recorded_votes) }
-                        p."stat__desc" { "recorded " (recorded_votes_label) }
+                    match encode(streamed, format, &listener) {
+                        Some(message) => message,
+                        None => continue,
                     }
                 }
+                message = rx.next() => {
+                    let message = match message {
+                        Some(Ok(message)) if message.is_close() => break,
+                        Some(Ok(message)) => message,
+                        Some(Err(e)) => {
+                            tracing::debug!("websocket error: {e}");
+                            break;
+                        }
+                        None => break,
+                    };
+                    let Ok(text) = message.to_str() else { continue };
 
-                @if page.recorded_votes > 0 {
-                    button."button text-lg align-left"
-                        hx-put=(names::end_vote_url(page.room_id))
-                        hx-target="main"
-                        hx-swap="innerHTML" { "END VOTE" }
-                } @else {
-                    button."button text-lg align-left"
-                        disabled
-                        sse-swap=(names::VOTE_ENDABLE_EVENT)
-                        hx-swap="outerHTML" { "AT LEAST ONE RECORDED VOTE REQUIRED TO BE ABLE TO END VOTES." }
+                    act(&store, &broadcasters, &client, text).await
                 }
+            };
 
-                section."grid gap-md" {
-                    h2."text-md" { "APPROVED VOTERS" }
+            if tx.send(Message::text(message)).await.is_err() {
+                break;
+            }
+        }
 
-                    @for voter in page.approved_voters {
-                        div."flex gap-md" {
-                            span."strech code" {
-                                span { "VOTER ID" }
-                                span { (voter.id) }
-                            }
+        let _ = tx.close().await;
+    }
 
-                            @if voter.voted {
-                                span."boxed" { "VOTED" }
-                            } @else {
-                                span."boxed" sse-swap=(names::vote_event(voter.id)) hx-swap="outerHTML" { "WAITING" }
-                            }
-                        }
-                    }
+    /// The WebSocket message for an event, or `None` when the client shouldn't see it.
+    fn encode(streamed: Streamed, format: Format, listener: &Listener) -> Option<String> {
+        let message = match (streamed, format) {
+            (Streamed::Reload, Format::Html) => serde_json::to_string(&HtmlEvent {
+                event: names::RELOAD_EVENT.to_owned(),
+                data: names::RELOAD_EVENT.to_owned(),
+            }),
+            (Streamed::Reload, Format::Json) => serde_json::to_string(&JsonEvent::Reload),
+            (Streamed::Event(id, event), Format::Html) => {
+                let (event, data) = html_event(event, listener)?;
+                serde_json::to_string(&SocketEvent {
+                    id: Some(id),
+                    event: HtmlEvent { event, data },
+                })
+            }
+            (Streamed::Event(id, event), Format::Json) => {
+                let event = json_event(event, listener)?;
+                serde_json::to_string(&SocketEvent {
+                    id: Some(id),
+                    event,
+                })
+            }
+        };
+
+        message
+            .map_err(|e| tracing::error!("error while encoding event: {e}"))
+            .ok()
+    }
+
+    /// Carries out an action sent over a WebSocket and replies with `{"type": "ok"}` or
+    /// `{"type": "error", "error": "<CODE>"}`.
+    async fn act(store: &Db, broadcasters: &Broadcasters, client: &Client, text: &str) -> String {
+        let result = match serde_json::from_str::<Action>(text) {
+            Ok(Action::Vote(body)) => match (client.listener.voter, &client.voter_code) {
+                (Some(voter_id), Some(voter_code)) => {
+                    voters::cast_vote(
+                        store.clone(),
+                        broadcasters.clone(),
+                        voter_id,
+                        voter_code.clone(),
+                        body,
+                    )
+                    .await
+                }
+                _ => Err(warp::reject::custom(NotVoter)),
+            },
+            Ok(Action::Approve { voter_id }) => match &client.admin_code {
+                Some(admin_code) => {
+                    voters::approve(
+                        store.clone(),
+                        broadcasters.clone(),
+                        voter_id,
+                        admin_code.clone(),
+                    )
+                    .await
                 }
+                None => Err(warp::reject::custom(NotRoomAdmin)),
+            },
+            Err(e) => {
+                tracing::debug!("invalid websocket action: {e}");
+                return serde_json::json!({ "type": "error", "error": "BAD_REQUEST" }).to_string();
+            }
+        };
+
+        match result {
+            Ok(()) => serde_json::json!({ "type": "ok" }).to_string(),
+            Err(rejection) => {
+                let (_, error) = rejections::reason(&rejection);
+                serde_json::json!({ "type": "error", "error": error }).to_string()
             }
         }
     }
 
-    pub struct ResultPage {
-        pub room_name: String,
-        pub scores: Vec<Score>,
-    }
+    #[cfg(test)]
+    mod tests {
+        use std::time::Duration;
+
+        use serde_json::{json, Value};
+        use tokio_stream::StreamExt;
+        use warp::http::StatusCode;
+
+        use super::{Broadcasters, Listener, Member, RoomEvents, Streamed, REPLAY_CAPACITY};
+        use crate::{names, tests};
+
+        /// The ids of the next `count` items of a stream, with `None` for a reload.
+        async fn ids(
+            stream: impl tokio_stream::Stream<Item = Streamed>,
+            count: usize,
+        ) -> Vec<Option<u64>> {
+            let items = stream.take(count).map(|streamed| match streamed {
+                Streamed::Event(id, _) => Some(id),
+                Streamed::Reload => None,
+            });
+            tokio::time::timeout(Duration::from_secs(5), items.collect())
+                .await
+                .unwrap()
+        }
 
-    pub struct Score {
-        pub option: String,
-        pub score: usize,
-    }
+        #[tokio::test]
+        async fn reconnecting_listeners_get_the_events_they_missed() {
+            let broadcasters = Broadcasters::new();
+            for count in 1..=3 {
+                broadcasters
+                    .send_event(1, RoomEvents::NewVoterCount(count))
+                    .await;
+            }
+            // Events of other rooms share the numbering but aren't replayed.
+            broadcasters
+                .send_event(2, RoomEvents::NewVoterCount(1))
+                .await;
 
-    pub fn result_page(page: ResultPage) -> Markup {
-        let labels = page
-            .scores
-            .iter()
-            .map(|Score { option, .. }| format!("\"{option}\""))
-            .collect::<Vec<_>>()
-            .join(",");
-        let data = page
-            .scores
-            .iter()
-            .map(|s| s.score.to_string())
-            .collect::<Vec<_>>()
-            .join(",");
+            let stream = broadcasters.get_stream(1, Some(1)).await;
+            broadcasters
+                .send_event(1, RoomEvents::NewVoterCount(4))
+                .await;
+            assert_eq!(ids(stream, 3).await, [Some(2), Some(3), Some(5)]);
 
-        let chart_js = format!(
-            r#"
-        <script>
-            const canvas = document.querySelector('canvas');
+            // Nothing is missed when the listener has seen the latest event.
+            let stream = broadcasters.get_stream(1, Some(5)).await;
+            broadcasters
+                .send_event(1, RoomEvents::NewVoterCount(5))
+                .await;
+            assert_eq!(ids(stream, 1).await, [Some(6)]);
+        }
 
-            const data = {{
-              labels: [{labels}],
-              datasets: [{{
-                label: 'SCORES',
-                data: [{data}],
-                borderWidth: 1
-              }}]
-            }};
+        #[tokio::test]
+        async fn listeners_reload_when_missed_events_are_gone() {
+            let broadcasters = Broadcasters::new();
+            for count in 0..=REPLAY_CAPACITY as i32 + 1 {
+                broadcasters
+                    .send_event(1, RoomEvents::NewVoterCount(count))
+                    .await;
+            }
 
-            const config = {{
-              type: 'bar',
-              data: data,
-              options: {{
-                scales: {{
-                  y: {{
-                    beginAtZero: true
-                  }}
-                }}
-              }},
-            }};
+            // The first event fell out of the replay buffer, the second is still in it.
+            let stream = broadcasters.get_stream(1, Some(1)).await;
+            assert_eq!(ids(stream, 1).await, [None]);
+            let stream = broadcasters.get_stream(1, Some(2)).await;
+            assert_eq!(ids(stream, 1).await, [Some(3)]);
 
-            new Chart(canvas, config);
-        </script>
-            "#
-        );
+            let last = REPLAY_CAPACITY as u64 + 2;
+            let stream = broadcasters.get_stream(1, Some(last - 1)).await;
+            assert_eq!(ids(stream, 1).await, [Some(last)]);
 
-        html! {
-            section."grid gap-lg w-800" {
-                h1."text-lg" { "RESULTS FOR \"" (page.room_name) "\"" }
+            // An id the room never reached, as after a restart, can't be resumed either.
+            let stream = broadcasters.get_stream(1, Some(last + 10)).await;
+            assert_eq!(ids(stream, 1).await, [None]);
+        }
 
-                section."grid gap-sm" {
-                    div."big-small gap-sm" {
-                        p."code text-sm" { "OPTION" }
-                        p."code text-sm" { "SCORE" }
-                    }
+        #[tokio::test]
+        async fn lagging_listeners_reload() {
+            let broadcasters = Broadcasters::new();
+            let stream = broadcasters.get_stream(1, None).await;
+            for count in 0..=REPLAY_CAPACITY as i32 {
+                broadcasters
+                    .send_event(1, RoomEvents::NewVoterCount(count))
+                    .await;
+            }
 
-                    @for score in page.scores {
-                        div."big-small gap-sm" {
-                            div."card" {
-                                p."text-sm" { (score.option) }
-                            }
+            assert_eq!(ids(stream, 1).await, [None]);
+        }
 
-                            div."card card--secondary" {
-                                p."text-sm" { (utils::format_num(score.score as i32)) }
-                            }
-                        }
-                    }
+        /// Waits until a room has sent `events` events and has `listeners` streams open.
+        async fn settled(broadcasters: &Broadcasters, room_id: i64, events: u64, listeners: usize) {
+            loop {
+                let map = broadcasters.listeners.map.lock().await;
+                if map.get(&room_id).is_some_and(|channel| {
+                    channel.last_id >= events && channel.tx.receiver_count() >= listeners
+                }) {
+                    return;
                 }
+                drop(map);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
 
-                canvas."card card--secondary" {}
-
-                (PreEscaped(chart_js))
+        /// Waits until a member has a connection open to a room, so its stream is following
+        /// the room's events.
+        async fn connected(broadcasters: &Broadcasters, room_id: i64, member: Member) {
+            loop {
+                let map = broadcasters.listeners.map.lock().await;
+                if map
+                    .get(&room_id)
+                    .is_some_and(|channel| channel.present.contains_key(&member))
+                {
+                    return;
+                }
+                drop(map);
+                tokio::time::sleep(Duration::from_millis(10)).await;
             }
         }
-    }
-}
-
-mod events {
-    use std::{collections::HashMap, convert::Infallible, sync::Arc};
 
-    use maud::html;
-    use tokio::sync::{
-        broadcast::{self, Sender},
-        Mutex,
-    };
-    use tokio_stream::{wrappers::BroadcastStream, StreamExt};
-    use warp::{
-        filters::sse::{self, Event},
-        Filter,
-    };
+        /// The JSON payloads of an event stream, skipping pings.
+        fn payloads(body: &str) -> Vec<Value> {
+            body.split("\n\n")
+                .filter_map(|event| event.lines().find_map(|l| l.strip_prefix("data:")))
+                .map(|data| serde_json::from_str(data).unwrap())
+                .collect()
+        }
 
-    use crate::{names, rejections::InternalServerError, utils, with_state};
+        #[tokio::test]
+        async fn json_stream_sends_each_listener_the_events_it_may_see() {
+            let broadcasters = Broadcasters::new();
+            let app = tests::app(tests::store().await, broadcasters.clone());
 
-    #[derive(Clone, Debug)]
-    pub enum RoomEvents {
-        NewVoter(i64),
-        NewVoterCount(i32),
-        VoterApproved(i64),
-        VoteStartable(i64),
-        VoteEndable(i64),
-        VoteStarted(Vec<String>),
-        VoteEnded,
-        NewVote(i64),
-        NewVoteCount(i32),
-    }
+            let resp = warp::test::request()
+                .method("POST")
+                .path("/api/v1/rooms")
+                .json(&json!({"name": "Lunch", "options": ["Pasta", "Pizza"]}))
+                .reply(&app)
+                .await;
+            let room = tests::json(&resp);
+            let room_id = room["id"].as_i64().unwrap();
+            let admin_code = room["admin_code"].as_str().unwrap().to_owned();
+
+            let voter = |app| async move {
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&format!("/api/v1/rooms/{room_id}/join"))
+                    .reply(&app)
+                    .await;
+                let voter = tests::json(&resp);
+                let id = voter["id"].as_i64().unwrap();
+                (id, voter["voter_code"].as_str().unwrap().to_owned())
+            };
+            let (voter_id, voter_code) = voter(app.clone()).await;
+            let (other_id, _) = voter(app.clone()).await;
+
+            for id in [voter_id, other_id] {
+                let resp = warp::test::request()
+                    .method("PUT")
+                    .path(&format!("/api/v1/voters/{id}/approve"))
+                    .header("authorization", format!("Bearer {admin_code}"))
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+            }
 
-    #[derive(Clone, Default)]
-    pub struct Broadcasters {
-        map: Arc<Mutex<HashMap<i64, Sender<RoomEvents>>>>,
-    }
+            // Every listener replays the room's events from the start.
+            let listen = |code: Option<String>| {
+                let app = app.clone();
+                tokio::spawn(async move {
+                    let mut req = warp::test::request()
+                        .path(&format!("/rooms/{room_id}/events.json"))
+                        .header("last-event-id", "0");
+                    if let Some(code) = code {
+                        req = req.header("authorization", format!("Bearer {code}"));
+                    }
+                    let resp = req.reply(&app).await;
+                    payloads(&String::from_utf8_lossy(resp.body()))
+                })
+            };
+            let admin_events = listen(Some(admin_code));
+            let voter_events = listen(Some(voter_code));
+            let stranger_events = listen(None);
+
+            // Two joins and two approvals send two events each, and the admin and voter
+            // coming online one more each.
+            settled(&broadcasters, room_id, 10, 3).await;
+            broadcasters.end_stream(room_id).await;
 
-    impl Broadcasters {
-        pub fn new() -> Self {
-            Default::default()
-        }
+            let admin_events = admin_events.await.unwrap();
+            for event in [
+                json!({"type": "new-voter", "voter_id": voter_id}),
+                json!({"type": "voter-count", "count": 2}),
+                json!({"type": "voter-approved", "voter_id": other_id}),
+                json!({"type": "vote-startable"}),
+            ] {
+                assert!(admin_events.contains(&event), "admin missed {event}");
+            }
 
-        pub async fn send_event(&self, room_id: i64, event: RoomEvents) {
-            let mut map = self.map.lock().await;
-            let tx = map
-                .entry(room_id)
-                .or_insert_with(|| broadcast::channel(16).0);
+            // A voter only hears about their own approval and nothing meant for the admin.
+            let voter_events = voter_events.await.unwrap();
+            assert!(voter_events.contains(&json!({"type": "voter-count", "count": 2})));
+            assert!(voter_events.contains(&json!({"type": "voter-approved", "voter_id": voter_id})));
+            for event in &voter_events {
+                assert!(
+                    ["voter-count", "voter-approved", "admin-presence"]
+                        .contains(&event["type"].as_str().unwrap()),
+                    "voter saw {event}"
+                );
+                assert_ne!(event["voter_id"], other_id);
+            }
 
-            let res = tx.send(event);
-            tracing::debug!("send event result: {res:?}");
+            assert_eq!(stranger_events.await.unwrap(), Vec::<Value>::new());
         }
 
-        async fn get_stream(&self, room_id: i64) -> BroadcastStream<RoomEvents> {
-            let mut map = self.map.lock().await;
-            let tx = map
-                .entry(room_id)
-                .or_insert_with(|| broadcast::channel(16).0);
-            let rx = tx.subscribe();
-
-            BroadcastStream::new(rx)
+        #[tokio::test]
+        async fn listeners_of_missing_rooms_are_refused() {
+            let app = tests::app(tests::store().await, Broadcasters::new());
+
+            for (header, value) in [
+                ("authorization", "Bearer nope".to_owned()),
+                ("cookie", format!("{}=nope", names::VOTER_COOKIE_NAME)),
+            ] {
+                let resp = warp::test::request()
+                    .path("/rooms/404/events.json")
+                    .header(header, value)
+                    .reply(&app)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+                assert!(tests::text(&resp).contains("ROOM_NOT_FOUND"));
+            }
         }
 
-        pub async fn end_stream(&self, room_id: i64) {
-            let mut map = self.map.lock().await;
-            let res = map.remove(&room_id);
-            tracing::debug!("end stream result: {res:?}");
+        /// Reads a WebSocket until a message matches, skipping the events before it.
+        async fn recv_until(
+            client: &mut warp::test::WsClient,
+            matches: impl Fn(&Value) -> bool,
+        ) -> Value {
+            let read = async {
+                loop {
+                    let message = client.recv().await.unwrap();
+                    let message = serde_json::from_str(message.to_str().unwrap()).unwrap();
+                    if matches(&message) {
+                        return message;
+                    }
+                }
+            };
+            tokio::time::timeout(Duration::from_secs(5), read)
+                .await
+                .unwrap()
         }
-    }
 
-    pub fn route(
-        conn: sqlx::Pool<sqlx::Sqlite>,
-        broadcasters: Broadcasters,
-    ) -> impl warp::Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-        warp::path!("rooms" / i64 / "listen")
-            .and(with_state(conn))
-            .and(with_state(broadcasters))
-            .and(warp::cookie::optional(names::ROOM_ADMIN_COOKIE_NAME))
-            .and(warp::cookie::optional(names::VOTER_COOKIE_NAME))
-            .and_then(handler)
-    }
+        #[tokio::test]
+        async fn websockets_carry_events_and_actions() {
+            let app = tests::app(tests::store().await, Broadcasters::new());
 
-    async fn handler(
-        room_id: i64,
-        conn: sqlx::Pool<sqlx::Sqlite>,
-        broadcasters: Broadcasters,
-        admin_code: Option<String>,
-        voter_code: Option<String>,
-    ) -> Result<impl warp::Reply, warp::Rejection> {
-        let admin = match admin_code {
-            Some(admin_code) => {
-                let room = sqlx::query!(
-                    r#"
-                SELECT id, admin_code
-                FROM rooms
-                WHERE id = ?1
-                    "#,
-                    room_id
+            let resp = warp::test::request()
+                .method("POST")
+                .path("/api/v1/rooms")
+                .json(&json!({"name": "Lunch", "options": ["Pasta", "Pizza"]}))
+                .reply(&app)
+                .await;
+            let room = tests::json(&resp);
+            let room_id = room["id"].as_i64().unwrap();
+            let admin_code = room["admin_code"].as_str().unwrap().to_owned();
+            let pasta = room["options"][0]["id"].as_i64().unwrap();
+            let pizza = room["options"][1]["id"].as_i64().unwrap();
+
+            let resp = warp::test::request()
+                .method("POST")
+                .path(&format!("/api/v1/rooms/{room_id}/join"))
+                .reply(&app)
+                .await;
+            let voter = tests::json(&resp);
+            let voter_id = voter["id"].as_i64().unwrap();
+            let voter_code = voter["voter_code"].as_str().unwrap();
+
+            let mut admin = warp::test::ws()
+                .path(&format!(
+                    "/rooms/{room_id}/ws?format=json&token={admin_code}"
+                ))
+                .handshake(app.clone())
+                .await
+                .unwrap();
+            let mut voter = warp::test::ws()
+                .path(&format!("/rooms/{room_id}/ws"))
+                .header(
+                    "cookie",
+                    format!("{}={voter_code}", names::VOTER_COOKIE_NAME),
                 )
-                .fetch_one(&conn)
+                .handshake(app.clone())
                 .await
-                .map_err(|e| {
-                    tracing::error!("error while getting admin code: {e}");
-                    warp::reject::custom(InternalServerError)
-                })?;
+                .unwrap();
 
-                if admin_code == room.admin_code {
-                    Some(room.id)
-                } else {
-                    None
-                }
-            }
-            None => None,
-        };
+            // Voters can't act as the admin.
+            voter
+                .send_text(json!({"action": "approve", "voter_id": voter_id}).to_string())
+                .await;
+            let reply = recv_until(&mut voter, |m| m.get("type").is_some()).await;
+            assert_eq!(reply, json!({"type": "error", "error": "NOT_ROOM_ADMIN"}));
 
-        let voter = match voter_code {
-            Some(voter_code) => sqlx::query!(
-                r#"
-            SELECT id
-            FROM voters
-            WHERE voter_code = ?1
-                "#,
-                voter_code
-            )
-            .fetch_optional(&conn)
-            .await
-            .map_err(|e| {
-                tracing::error!("error while getting admin code: {e}");
-                warp::reject::custom(InternalServerError)
-            })?
-            .map(|v| v.id),
-            None => None,
-        };
+            admin
+                .send_text(json!({"action": "approve", "voter_id": voter_id}).to_string())
+                .await;
+            let reply = recv_until(&mut admin, |m| m["type"] == "ok" || m["type"] == "error").await;
+            assert_eq!(reply, json!({"type": "ok"}));
 
-        let stream = broadcasters.get_stream(room_id).await;
-        let stream = stream
-            .filter_map(|event| match event {
-                Ok(event) => Some(event),
-                Err(error) => {
-                    tracing::error!("error while receiving events: {error}");
-                    None
-                }
+            // The voter gets the HTML event htmx would get over SSE.
+            let approved = recv_until(&mut voter, |m| {
+                m["event"] == names::voter_approved_event(voter_id)
             })
-            .map(move |event| {
-                use RoomEvents::*;
-                tracing::debug!("new event received: {event:?}");
+            .await;
+            assert!(approved["data"]
+                .as_str()
+                .unwrap()
+                .contains("VOTER HAS BEEN APPROVED."));
+            assert!(approved["id"].is_u64());
+
+            let resp = warp::test::request()
+                .method("PUT")
+                .path(&format!("/api/v1/rooms/{room_id}/start"))
+                .header("authorization", format!("Bearer {admin_code}"))
+                .reply(&app)
+                .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            recv_until(&mut voter, |m| m["event"] == names::VOTE_STARTED_EVENT).await;
 
-                match (event, admin, voter) {
-                    (NewVoterCount(count), Some(_), None) | (NewVoterCount(count), None, Some(_)) => {
-                        Event::default()
-                            .event(names::VOTER_COUNT_EVENT)
-                            .data(html! {
-                                p."stat__num" data-testid="voter-count" { (utils::format_num(count)) }
-                                p."stat__desc" { (utils::pluralize(count, "voter", "voters")) " in room" }
-                            }.into_string())
-                    }
-
-                    (NewVoter(voter_id), Some(_), None) => Event::default()
-                        .event(names::NEW_VOTER_EVENT)
-                        .data(html! {
-                            div."flex gap-md" {
-                                span."strech code" {
-                                    span { "VOTER ID" }
-                                    span { (voter_id) }
-                                }
-                                button."button w-fit" hx-put=(names::approve_voter_url(voter_id)) hx-swap="outerHTML" { "APPROVE" }
-                            }
-                        }.into_string()),
+            voter
+                .send_text(json!({"action": "vote", "options": [pizza, pasta]}).to_string())
+                .await;
+            let reply = recv_until(&mut voter, |m| m.get("type").is_some()).await;
+            assert_eq!(reply, json!({"type": "ok"}));
 
-                    (VoterApproved(voter_id), Some(_), None) => Event::default()
-                        .event(names::voter_approved_event(voter_id))
-                        .data(html! {
-                            button."button w-fit" disabled { "APPROVED" }
-                        }.into_string()),
+            let vote = recv_until(&mut admin, |m| m["type"] == "new-vote").await;
+            assert_eq!(vote["voter_id"], voter_id);
+        }
 
-                    (VoterApproved(voter_id), None, Some(listener)) if voter_id == listener => Event::default()
-                        .event(names::voter_approved_event(voter_id))
-                        .data(html! {
-                            div."alert" { "VOTER HAS BEEN APPROVED." }
-                        }.into_string()),
-
-                    (VoteStarted(options), None, Some(voter_id)) => Event::default()
-                        .event(names::VOTE_STARTED_EVENT)
-                        .data(html! {
-                            form."grid gap-md sortable" hx-ext="json-enc" hx-post=(names::vote_url(voter_id)) hx-swap="outerHTML" {
-                                h2."text-lg" { "START VOTING" }
-                                p."text-sm" { "(REORDER THE OPTIONS BY DRAGGING AND DROPPING THEM)" }
-
-                                div."grid gap-md sortable" {
-                                    @for option in options {
-                                        div."card" {
-                                            (option)
-                                            input type="hidden" name="options" value=(option) {}
-                                        }
-                                    }
-                                }
+        #[tokio::test]
+        async fn admins_see_which_voters_are_connected() {
+            let broadcasters = Broadcasters::new();
+            let app = tests::app(tests::store().await, broadcasters.clone());
 
-                                button."button align-left" type="submit" { "SUBMIT VOTE" }
-                            }
-                        }.into_string()),
-
-                    (NewVote(voter_id), Some(_), None) => Event::default()
-                        .event(names::vote_event(voter_id))
-                        .data(html! {
-                            span."boxed" { "VOTED" }
-                        }.into_string()),
-
-                    (NewVoteCount(votes), Some(_), None) => Event::default()
-                        .event(names::VOTE_COUNT_EVENT)
-                        .data(html! {
-                            p."stat__num" data-testid="votes-count" { (utils::format_num(votes)) }
-                            p."stat__desc" { "recorded " (utils::pluralize(votes, "vote", "votes")) }
-                        }.into_string()),
-
-                    (VoteEnded, None, Some(_)) => Event::default()
-                        .event(names::VOTE_ENDED_EVENT)
-                        .data(html! { div."alert" { "VOTES HAVE ENDED." } }.into_string()),
-
-                    (VoteStartable(room_id), Some(_), None) => Event::default()
-                        .event(names::VOTE_STARTABLE_EVENT)
-                        .data(html! {
-                            button."button text-lg align-left"
-                                hx-put=(names::start_vote_url(room_id))
-                                hx-target="main"
-                                hx-swap="innerHTML" { "START VOTE" }
-                        }.into_string()),
-
-                    (VoteEndable(room_id), Some(_), None) => Event::default()
-                        .event(names::VOTE_ENDABLE_EVENT)
-                        .data(html! {
-                            button."button text-lg align-left"
-                                hx-put=(names::end_vote_url(room_id))
-                                hx-target="main"
-                                hx-swap="innerHTML" { "END VOTE" }
-                        }.into_string()),
-
-                    _ => Event::default().event(names::PING_EVENT),
+            let resp = warp::test::request()
+                .method("POST")
+                .path("/api/v1/rooms")
+                .json(&json!({"name": "Lunch", "options": ["Pasta", "Pizza"]}))
+                .reply(&app)
+                .await;
+            let room = tests::json(&resp);
+            let room_id = room["id"].as_i64().unwrap();
+            let admin_code = room["admin_code"].as_str().unwrap().to_owned();
+
+            let resp = warp::test::request()
+                .method("POST")
+                .path(&format!("/api/v1/rooms/{room_id}/join"))
+                .reply(&app)
+                .await;
+            let voter = tests::json(&resp);
+            let voter_id = voter["id"].as_i64().unwrap();
+            let voter_code = voter["voter_code"].as_str().unwrap();
+
+            let mut admin = warp::test::ws()
+                .path(&format!(
+                    "/rooms/{room_id}/ws?format=json&token={admin_code}"
+                ))
+                .handshake(app.clone())
+                .await
+                .unwrap();
+            let is_presence = |m: &Value| m["type"] == "voter-presence";
+
+            let room_page = || async {
+                let resp = warp::test::request()
+                    .path(&names::room_page_url(room_id))
+                    .header(
+                        "cookie",
+                        format!("{}={admin_code}", names::ROOM_ADMIN_COOKIE_NAME),
+                    )
+                    .reply(&app)
+                    .await;
+                tests::text(&resp)
+            };
+            assert!(room_page().await.contains("OFFLINE"));
+
+            // A voter with the room open twice is announced once.
+            let mut sockets = Vec::new();
+            for _ in 0..2 {
+                let socket = warp::test::ws()
+                    .path(&format!("/rooms/{room_id}/ws?token={voter_code}"))
+                    .handshake(app.clone())
+                    .await
+                    .unwrap();
+                sockets.push(socket);
+            }
+            let presence = recv_until(&mut admin, is_presence).await;
+            assert_eq!(presence["voter_id"], voter_id);
+            assert_eq!(presence["online"], true);
+            connected(&broadcasters, room_id, Member::Voter(voter_id)).await;
+            assert!(broadcasters
+                .online_voters(room_id)
+                .await
+                .contains(&voter_id));
+            assert!(room_page().await.contains("ONLINE"));
+
+            // Closing one of them leaves the voter online, closing both takes them offline.
+            drop(sockets.pop());
+            loop {
+                let map = broadcasters.listeners.map.lock().await;
+                if map[&room_id].present[&Member::Voter(voter_id)] == 1 {
+                    break;
                 }
-            })
-            .map(Ok::<_, Infallible>);
+                drop(map);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            assert!(broadcasters
+                .online_voters(room_id)
+                .await
+                .contains(&voter_id));
+            drop(sockets);
+            let presence = recv_until(&mut admin, is_presence).await;
+            assert_eq!(presence["voter_id"], voter_id);
+            assert_eq!(presence["online"], false);
+            assert!(broadcasters.online_voters(room_id).await.is_empty());
+            assert!(room_page().await.contains("OFFLINE"));
+        }
 
-        Ok(sse::reply(stream))
+        #[tokio::test]
+        async fn instances_share_events_through_the_database() {
+            let store = tests::store().await;
+            let interval = Duration::from_millis(10);
+            let a = Broadcasters::database(store.clone(), interval)
+                .await
+                .unwrap();
+            let b = Broadcasters::database(store.clone(), interval)
+                .await
+                .unwrap();
+
+            let mut stream = Box::pin(b.get_stream(1, None).await);
+            a.send_event(1, RoomEvents::NewVoterCount(3)).await;
+            let next = tokio::time::timeout(Duration::from_secs(5), stream.next());
+            match next.await.unwrap() {
+                Some(Streamed::Event(id, RoomEvents::NewVoterCount(3))) => {
+                    assert_eq!(id as i64, store.last_event_id().await.unwrap());
+                }
+                _ => panic!("the event didn't reach the other instance"),
+            }
+
+            // A voter connected to one instance is online on both.
+            let connection = a.connect(1, vec![Member::Voter(5)]).await;
+            let online = async {
+                while !b.online_voters(1).await.contains(&5) {
+                    tokio::time::sleep(interval).await;
+                }
+            };
+            tokio::time::timeout(Duration::from_secs(5), online)
+                .await
+                .unwrap();
+            drop(connection);
+            let offline = async {
+                while !b.online_voters(1).await.is_empty() {
+                    tokio::time::sleep(interval).await;
+                }
+            };
+            tokio::time::timeout(Duration::from_secs(5), offline)
+                .await
+                .unwrap();
+
+            // Ending a room's streams on one instance ends them on the other.
+            a.end_stream(1).await;
+            loop {
+                let next = tokio::time::timeout(Duration::from_secs(5), stream.next());
+                if next.await.unwrap().is_none() {
+                    break;
+                }
+            }
+        }
+
+        #[test]
+        fn strangers_get_no_json_events() {
+            use super::RoomEvents::*;
+
+            for event in [
+                NewVoter(1),
+                NewVoterCount(1),
+                VoterApproved(1),
+                VoteStartable(1),
+                VoteEndable(1),
+                VoteEnded(None),
+                NewVote(1),
+                NewVoteCount(1),
+                Presence(Member::Voter(1), true),
+                Presence(Member::Admin, true),
+            ] {
+                assert!(super::json_event(event, &Listener::default()).is_none());
+            }
+        }
     }
 }
 
 mod utils {
+    use std::{fmt::Display, str::FromStr};
+
     use num_format::{Locale, ToFormattedString};
+    use serde::{de, Deserialize, Deserializer};
     use ulid::Ulid;
 
     pub fn format_num(num: i32) -> String {
         num.to_formatted_string(&Locale::en)
     }
 
+    /// Formats a vote count or score, showing decimals only when it has a fractional part.
+    pub fn format_score(score: f64) -> String {
+        if (score - score.round()).abs() < 1e-9 {
+            format_num(score.round() as i32)
+        } else {
+            format!("{score:.2}")
+        }
+    }
+
     pub fn pluralize(num: i32, singular: &str, plural: &str) -> String {
         if num == 1 { singular } else { plural }.to_owned()
     }
@@ -1551,16 +11228,152 @@ mod utils {
         Ulid::new().to_string()
     }
 
-    pub fn cookie(name: &str, value: &str) -> String {
-        format!("{name}={value}; HttpOnly; Max-Age=3600; Secure; Path=/; SameSite=Strict")
+    pub fn generate_seed() -> i64 {
+        Ulid::new().random() as u32 as i64
+    }
+
+    pub fn cookie(name: &str, value: &str, max_age: i64) -> String {
+        format!("{name}={value}; HttpOnly; Max-Age={max_age}; Secure; Path=/; SameSite=Strict")
+    }
+
+    /// A rough upper bound on `seconds`, e.g. "LESS THAN 3 HOURS".
+    pub fn time_left(seconds: i64) -> String {
+        let hours = (seconds.max(1) + 3599) / 3600;
+        let days = (seconds.max(1) + 86399) / 86400;
+
+        match hours {
+            1 => "LESS THAN AN HOUR".to_string(),
+            2..=48 => format!("LESS THAN {hours} HOURS"),
+            _ => format!("LESS THAN {days} DAYS"),
+        }
+    }
+
+    /// Deserializes a number that may also arrive as a string, as htmx's `json-enc` sends form values.
+    pub fn number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr + Deserialize<'de>,
+        T::Err: Display,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum NumOrStr<T> {
+            Num(T),
+            Str(String),
+        }
+
+        match NumOrStr::<T>::deserialize(deserializer)? {
+            NumOrStr::Num(num) => Ok(num),
+            NumOrStr::Str(s) => s.trim().parse().map_err(de::Error::custom),
+        }
+    }
+
+    /// Like [`number`], but an empty string or `null` means no value.
+    pub fn optional_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr + Deserialize<'de>,
+        T::Err: Display,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum NumOrStr<T> {
+            Num(T),
+            Str(String),
+        }
+
+        match Option::<NumOrStr<T>>::deserialize(deserializer)? {
+            Some(NumOrStr::Num(num)) => Ok(Some(num)),
+            Some(NumOrStr::Str(s)) if s.trim().is_empty() => Ok(None),
+            Some(NumOrStr::Str(s)) => s.trim().parse().map(Some).map_err(de::Error::custom),
+            None => Ok(None),
+        }
+    }
+
+    /// Deserializes a checkbox, which `json-enc` sends as `"on"` when checked and leaves out otherwise.
+    pub fn checkbox<'de, D>(deserializer: D) -> Result<bool, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum BoolOrStr {
+            Bool(bool),
+            Str(String),
+        }
+
+        match BoolOrStr::deserialize(deserializer)? {
+            BoolOrStr::Bool(b) => Ok(b),
+            BoolOrStr::Str(s) => Ok(matches!(s.as_str(), "on" | "true")),
+        }
+    }
+
+    /// Deserializes a list of numbers that may also arrive as strings, or as a single value when
+    /// the form has only one.
+    pub fn numbers<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr + Deserialize<'de>,
+        T::Err: Display,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum NumOrStr<T> {
+            Num(T),
+            Str(String),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum OneOrMany<T> {
+            One(NumOrStr<T>),
+            Many(Vec<NumOrStr<T>>),
+        }
+
+        let values = match OneOrMany::<T>::deserialize(deserializer)? {
+            OneOrMany::One(one) => vec![one],
+            OneOrMany::Many(many) => many,
+        };
+
+        values
+            .into_iter()
+            .map(|value| match value {
+                NumOrStr::Num(num) => Ok(num),
+                NumOrStr::Str(s) => s.trim().parse().map_err(de::Error::custom),
+            })
+            .collect()
+    }
+
+    /// Deserializes a list that `json-enc` sends as a single string when the form has one value.
+    pub fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum OneOrMany {
+            One(String),
+            Many(Vec<String>),
+        }
+
+        match OneOrMany::deserialize(deserializer)? {
+            OneOrMany::One(one) => Ok(vec![one]),
+            OneOrMany::Many(many) => Ok(many),
+        }
     }
 }
 
 mod names {
+    use crate::files::Export;
+
     pub fn rooms_url() -> String {
         "/rooms".to_owned()
     }
 
+    pub fn import_url() -> String {
+        "/rooms/import".to_owned()
+    }
+
     pub fn room_page_url(room_id: i64) -> String {
         format!("/rooms/{room_id}")
     }
@@ -1577,6 +11390,18 @@ mod names {
         format!("/rooms/{room_id}/listen")
     }
 
+    pub fn results_url(room_id: i64) -> String {
+        format!("/rooms/{room_id}/results")
+    }
+
+    pub fn export_url(room_id: i64, export: Export) -> String {
+        format!("/rooms/{room_id}/export/{}", export.file_name())
+    }
+
+    pub fn round_results_url(room_id: i64) -> String {
+        format!("/rooms/{room_id}/results/rounds")
+    }
+
     pub fn join_room_url(room_id: i64) -> String {
         format!("/rooms/{room_id}/join")
     }
@@ -1598,11 +11423,13 @@ mod names {
 
     pub const VOTE_STARTED_EVENT: &str = "vote-started";
     pub const VOTE_ENDED_EVENT: &str = "vote-ended";
+    pub const LIVE_RESULTS_EVENT: &str = "live-results";
     pub const VOTE_COUNT_EVENT: &str = "vote-count";
     pub const VOTE_STARTABLE_EVENT: &str = "vote-startable";
     pub const VOTE_ENDABLE_EVENT: &str = "vote-endable";
 
     pub const PING_EVENT: &str = "ping";
+    pub const RELOAD_EVENT: &str = "reload";
 
     pub fn voter_approved_event(voter_id: i64) -> String {
         format!("voter-approved:{voter_id}")
@@ -1612,6 +11439,10 @@ mod names {
         format!("vote:{voter_id}")
     }
 
+    pub fn presence_event(voter_id: i64) -> String {
+        format!("presence:{voter_id}")
+    }
+
     pub const ROOM_ADMIN_COOKIE_NAME: &str = "admin_code";
     pub const VOTER_COOKIE_NAME: &str = "voter_code";
 }
@@ -1697,6 +11528,14 @@ mod rejections {
     use std::convert::Infallible;
 
     use maud::{html, Markup};
+    use serde::Serialize;
+    use utoipa::{
+        openapi::{
+            schema::{ObjectBuilder, Schema, Type},
+            RefOr,
+        },
+        PartialSchema, ToSchema,
+    };
     use warp::{
         http::StatusCode,
         reject::{Reject, Rejection},
@@ -1706,29 +11545,109 @@ mod rejections {
     use crate::views;
 
     macro_rules! rejects {
-        ($($name:ident),*) => {
+        ($($name:ident => $status:ident $code:literal),* $(,)?) => {
             $(
                 #[derive(Debug)]
                 pub struct $name;
 
                 impl Reject for $name {}
             )*
+
+            /// The status and error code of a rejection raised by our own handlers.
+            fn find_custom(err: &Rejection) -> Option<(StatusCode, &'static str)> {
+                $(
+                    if err.find::<$name>().is_some() {
+                        return Some((StatusCode::$status, $code));
+                    }
+                )*
+
+                None
+            }
+
+            const CUSTOM_CODES: &[&str] = &[$($code),*];
         };
     }
 
     rejects!(
-        NotVoter,
-        EmptyName,
-        NoOptions,
-        EmptyOption,
-        NotRoomAdmin,
-        RoomNotFound,
-        VoterNotFound,
-        UnknownOptions,
-        InternalServerError
+        NotVoter => UNAUTHORIZED "NOT_VOTER",
+        EmptyName => BAD_REQUEST "EMPTY_NAME",
+        NoOptions => BAD_REQUEST "NO_OPTIONS",
+        EmptyOption => BAD_REQUEST "EMPTY_OPTION",
+        NotRoomAdmin => UNAUTHORIZED "NOT_ROOM_ADMIN",
+        RoomNotFound => BAD_REQUEST "ROOM_NOT_FOUND",
+        VoterNotFound => BAD_REQUEST "VOTER_NOT_FOUND",
+        UnknownOptions => BAD_REQUEST "UNKNOWN_OPTIONS",
+        InvalidSeats => BAD_REQUEST "INVALID_SEATS",
+        InvalidMaxRanks => BAD_REQUEST "INVALID_MAX_RANKS",
+        EmptyBallot => BAD_REQUEST "EMPTY_BALLOT",
+        TooManyRanks => BAD_REQUEST "TOO_MANY_RANKS",
+        InvalidMaxScore => BAD_REQUEST "INVALID_MAX_SCORE",
+        InvalidScore => BAD_REQUEST "INVALID_SCORE",
+        InvalidLifetime => BAD_REQUEST "INVALID_LIFETIME",
+        MissingToken => UNAUTHORIZED "MISSING_TOKEN",
+        InvalidLine => BAD_REQUEST "INVALID_LINE",
+        InvalidBallots => BAD_REQUEST "INVALID_BALLOTS",
+        NoBallots => BAD_REQUEST "NO_BALLOTS",
+        TooManyBallots => BAD_REQUEST "TOO_MANY_BALLOTS",
+        InvalidMethod => BAD_REQUEST "INVALID_METHOD",
+        InternalServerError => INTERNAL_SERVER_ERROR "INTERNAL_SERVER_ERROR",
     );
 
+    /// Error codes for rejections raised by warp itself.
+    const BUILTIN_CODES: &[&str] = &[
+        "NOT_FOUND",
+        "BAD_REQUEST",
+        "METHOD_NOT_ALLOWED",
+        "PAYLOAD_TOO_LARGE",
+        "COOKIE_NOT_AVAILABLE",
+        "UNHANDLED_REJECTION",
+    ];
+
+    /// Every error code a reply can carry.
+    pub fn error_codes() -> impl Iterator<Item = &'static str> {
+        BUILTIN_CODES.iter().chain(CUSTOM_CODES).copied()
+    }
+
+    /// The body of an API error reply.
+    #[derive(Serialize)]
+    pub struct ApiError {
+        pub error: &'static str,
+    }
+
+    impl PartialSchema for ApiError {
+        fn schema() -> RefOr<Schema> {
+            ObjectBuilder::new()
+                .property(
+                    "error",
+                    ObjectBuilder::new()
+                        .schema_type(Type::String)
+                        .enum_values(Some(error_codes())),
+                )
+                .required("error")
+                .into()
+        }
+    }
+
+    impl ToSchema for ApiError {}
+
     pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+        let (code, message) = reason(&err);
+
+        Ok(warp::reply::with_status(error_page(message), code))
+    }
+
+    /// Like [`handle_rejection`], but replies with `{"error": "<CODE>"}` for API clients.
+    pub async fn handle_api_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+        let (code, message) = reason(&err);
+
+        Ok(warp::reply::with_status(
+            warp::reply::json(&ApiError { error: message }),
+            code,
+        ))
+    }
+
+    /// The status code and error code a rejection is reported with.
+    pub fn reason(err: &Rejection) -> (StatusCode, &'static str) {
         let code;
         let message;
 
@@ -1741,36 +11660,15 @@ mod rejections {
         {
             code = StatusCode::BAD_REQUEST;
             message = "BAD_REQUEST";
-        } else if let Some(NotVoter) = err.find() {
-            code = StatusCode::UNAUTHORIZED;
-            message = "NOT_VOTER";
-        } else if let Some(EmptyName) = err.find() {
-            code = StatusCode::BAD_REQUEST;
-            message = "EMPTY_NAME";
-        } else if let Some(NoOptions) = err.find() {
-            code = StatusCode::BAD_REQUEST;
-            message = "NO_OPTIONS";
-        } else if let Some(EmptyOption) = err.find() {
-            code = StatusCode::BAD_REQUEST;
-            message = "EMPTY_OPTION";
-        } else if let Some(NotRoomAdmin) = err.find() {
-            code = StatusCode::UNAUTHORIZED;
-            message = "NOT_ROOM_ADMIN";
-        } else if let Some(RoomNotFound) = err.find() {
-            code = StatusCode::BAD_REQUEST;
-            message = "ROOM_NOT_FOUND";
-        } else if let Some(VoterNotFound) = err.find() {
-            code = StatusCode::BAD_REQUEST;
-            message = "VOTER_NOT_FOUND";
-        } else if let Some(UnknownOptions) = err.find() {
-            code = StatusCode::BAD_REQUEST;
-            message = "UNKNOWN_OPTIONS";
-        } else if let Some(InternalServerError) = err.find() {
-            code = StatusCode::INTERNAL_SERVER_ERROR;
-            message = "INTERNAL_SERVER_ERROR";
+        } else if let Some((status, custom)) = find_custom(err) {
+            code = status;
+            message = custom;
         } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
             code = StatusCode::METHOD_NOT_ALLOWED;
             message = "METHOD_NOT_ALLOWED";
+        } else if err.find::<warp::reject::PayloadTooLarge>().is_some() {
+            code = StatusCode::PAYLOAD_TOO_LARGE;
+            message = "PAYLOAD_TOO_LARGE";
         } else if err
             .find::<warp::reject::InvalidHeader>()
             .is_some_and(|e| e.name() == warp::http::header::COOKIE)
@@ -1783,7 +11681,7 @@ mod rejections {
             message = "UNHANDLED_REJECTION";
         }
 
-        Ok(warp::reply::with_status(error_page(message), code))
+        (code, message)
     }
 
     fn error_page(message: &str) -> Markup {
@@ -1805,3 +11703,146 @@ mod rejections {
         )
     }
 }
+
+/// Helpers for tests that go through the routes, as a browser or API client would.
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+    use warp::{http::header::CONTENT_ENCODING, hyper::body::Bytes, Filter};
+
+    use crate::{
+        events::Broadcasters,
+        rejections,
+        store::{self, Db},
+        utils,
+    };
+
+    /// The `--max-lifetime` of the app under test, in hours.
+    pub const MAX_LIFETIME: i64 = 48;
+
+    /// A new database of its own, kept in memory.
+    pub async fn store() -> Db {
+        let url = format!(
+            "sqlite:file:ordo-{}?mode=memory&cache=shared",
+            utils::generate_ulid()
+        );
+        store::connect(&url).await.unwrap()
+    }
+
+    /// A database of its own, followed by the Postgres database at `TEST_POSTGRES_URL` when
+    /// it is set, so tests can run against both backends.
+    pub async fn stores() -> Vec<Db> {
+        let mut stores = vec![store().await];
+        match std::env::var("TEST_POSTGRES_URL") {
+            Ok(url) => stores.push(store::connect(&url).await.unwrap()),
+            Err(_) => eprintln!("TEST_POSTGRES_URL isn't set, skipping postgres"),
+        }
+        stores
+    }
+
+    /// A room that can rank "Pasta" and two "Pizza" options, open for `lifetime` hours.
+    pub fn new_room(lifetime: i64) -> store::NewRoom {
+        store::NewRoom {
+            name: "Lunch".to_owned(),
+            options: vec!["Pasta".to_owned(), "Pizza".to_owned(), "Pizza".to_owned()],
+            admin_code: utils::generate_ulid(),
+            method: 1,
+            seats: 1,
+            tie_break: 2,
+            seed: 42,
+            partial: true,
+            max_ranks: Some(1),
+            ballot_type: 0,
+            max_score: 5,
+            visibility: 1,
+            live: true,
+            lifetime,
+        }
+    }
+
+    /// A room created through the API.
+    pub struct Room {
+        pub id: i64,
+        pub admin_code: String,
+        /// The IDs of the options, in order.
+        pub options: Vec<i64>,
+        /// The ID and code of each approved voter.
+        pub voters: Vec<(i64, String)>,
+    }
+
+    /// Creates a room from a `/api/v1/rooms` body and approves `voters` new voters.
+    pub async fn room<F>(app: &F, body: serde_json::Value, voters: usize) -> Room
+    where
+        F: Filter + 'static,
+        F::Extract: warp::Reply + Send,
+    {
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/api/v1/rooms")
+            .json(&body)
+            .reply(app)
+            .await;
+        let room = json(&resp);
+        let mut room = Room {
+            id: room["id"].as_i64().unwrap(),
+            admin_code: room["admin_code"].as_str().unwrap().to_owned(),
+            options: room["options"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|option| option["id"].as_i64().unwrap())
+                .collect(),
+            voters: Vec::new(),
+        };
+
+        for _ in 0..voters {
+            let resp = warp::test::request()
+                .method("POST")
+                .path(&format!("/api/v1/rooms/{}/join", room.id))
+                .reply(app)
+                .await;
+            let voter = json(&resp);
+            let voter_id = voter["id"].as_i64().unwrap();
+
+            warp::test::request()
+                .method("PUT")
+                .path(&format!("/api/v1/voters/{voter_id}/approve"))
+                .header("authorization", format!("Bearer {}", room.admin_code))
+                .reply(app)
+                .await;
+            let voter_code = voter["voter_code"].as_str().unwrap().to_owned();
+            room.voters.push((voter_id, voter_code));
+        }
+
+        room
+    }
+
+    /// Every route, replying to rejections as the server does.
+    pub fn app(
+        store: Db,
+        broadcasters: Broadcasters,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = std::convert::Infallible> + Clone {
+        crate::routes(store, broadcasters, MAX_LIFETIME).recover(rejections::handle_rejection)
+    }
+
+    /// The body of an API reply.
+    pub fn json(resp: &warp::http::Response<Bytes>) -> serde_json::Value {
+        serde_json::from_slice(resp.body()).unwrap()
+    }
+
+    /// The body of a reply, unzipped if the page routes compressed it.
+    pub fn text(resp: &warp::http::Response<Bytes>) -> String {
+        let mut text = String::new();
+        match resp.headers().get(CONTENT_ENCODING) {
+            Some(encoding) if encoding == "gzip" => {
+                GzDecoder::new(&resp.body()[..])
+                    .read_to_string(&mut text)
+                    .unwrap();
+            }
+            _ => text.push_str(&String::from_utf8_lossy(resp.body())),
+        }
+        text
+    }
+}
@@ -42,14 +42,96 @@ function setupSortable() {
     const sortables = document.querySelectorAll(".sortable");
 
     for (const sortable of sortables) {
+        if (sortable.dataset.ready) continue;
+        sortable.dataset.ready = "true";
+
+        if (sortable.dataset.group) {
+            setupBallot(sortable);
+            continue;
+        }
+
         new Sortable(sortable, {
             animation: 150,
             ghostClass: "sortable-active"
         });
     }
   });
+
+  // Lists sharing a group let options move between the ranking and the unranked pile.
+  // Only inputs inside the ranking are submitted.
+  function setupBallot(list) {
+    new Sortable(list, {
+        animation: 150,
+        ghostClass: "sortable-active",
+        group: {
+            name: list.dataset.group,
+            put: to => !to.el.dataset.max || to.el.children.length < Number(to.el.dataset.max)
+        },
+        onSort: () => syncBallot(list.closest("form"))
+    });
+  }
+
+  function syncBallot(form) {
+    for (const input of form.querySelectorAll("input[data-name]")) {
+        if (input.closest("[data-ranked]")) {
+            input.name = input.dataset.name;
+        } else {
+            input.removeAttribute("name");
+        }
+    }
+  }
+}
+
+// Pressing = on an option ranks it equal to the option above it.
+function setupTies() {
+  document.addEventListener("click", event => {
+    const button = event.target.closest(".tie");
+    if (!button) return;
+
+    const card = button.closest(".card");
+    const input = card.querySelector("input.tied");
+    const tied = input.value !== "true";
+
+    input.value = tied;
+    card.classList.toggle("card--tied", tied);
+  });
 }
 
+// The server sends `reload` when this page missed events it can no longer replay.
+function setupReload() {
+  const sources = new WeakSet();
+
+  document.addEventListener("htmx:sseOpen", event => {
+    const source = event.detail.source;
+    if (sources.has(source)) return;
+    sources.add(source);
+
+    source.addEventListener("reload", () => window.location.reload());
+  });
+}
+
+// A chosen ballot file is read into the import form, which sends its text.
+function importFile() {
+  window.addEventListener("load", () => {
+    const input = document.querySelector("#importFile");
+    const text = document.querySelector("#importText");
+    const format = document.querySelector("#importFormat");
+
+    if (!input || !text || !format) return;
+
+    input.addEventListener("change", async () => {
+      const file = input.files[0];
+      if (!file) return;
+
+      text.value = await file.text();
+      if (file.name.toLowerCase().endsWith(".csv")) format.value = "csv";
+      if (file.name.toLowerCase().endsWith(".blt")) format.value = "blt";
+    });
+  });
+}
 
 homepage();
+importFile();
 setupSortable();
+setupTies();
+setupReload();